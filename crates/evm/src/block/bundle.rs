@@ -0,0 +1,116 @@
+//! Atomic execution of transaction bundles.
+
+use super::BlockExecutionError;
+use alloc::vec::Vec;
+use alloy_primitives::{B256, I256};
+use revm::context::result::ExecutionResult;
+
+/// The result of executing a bundle, see
+/// [`BlockExecutor::execute_bundle`](super::BlockExecutor::execute_bundle).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BundleExecutionResult<H> {
+    /// Execution results of the bundle transactions, in order.
+    pub results: Vec<ExecutionResult<H>>,
+    /// The total gas used by the bundle transactions.
+    pub gas_used: u64,
+    /// Change of the block beneficiary balance caused by the bundle, i.e. the priority fees and
+    /// direct payments received by the beneficiary minus any value it sent.
+    pub coinbase_balance_delta: I256,
+}
+
+/// Error of executing a bundle, see
+/// [`BlockExecutor::execute_bundle`](super::BlockExecutor::execute_bundle).
+///
+/// Unless the error occurred while reverting the bundle, the executor is left in the state it was
+/// before executing the bundle.
+#[derive(Debug, thiserror::Error)]
+pub enum BundleExecutionError {
+    /// A transaction of the bundle reverted and wasn't allowed to revert.
+    #[error("bundle transaction {index} ({hash}) reverted")]
+    TransactionReverted {
+        /// Index of the transaction in the bundle.
+        index: usize,
+        /// Hash of the transaction.
+        hash: B256,
+    },
+    /// Executing the bundle failed, e.g. because a transaction is invalid.
+    #[error(transparent)]
+    Execution(#[from] BlockExecutionError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        block::{BlockExecutor, BlockExecutorFactory, BlockValidationError},
+        test_utils::{contract, evm_env, execution_ctx, factory, sign, state, COUNTER, SENDER},
+        EvmFactory,
+    };
+    use alloy_consensus::{transaction::Recovered, TxEnvelope, TxLegacy};
+    use alloy_eips::Encodable2718;
+    use alloy_primitives::{address, bytes, Address, TxKind, U256};
+    use revm::{state::AccountInfo, Database};
+
+    const COINBASE: Address = address!("0x000000000000000000000000000000000000c01b");
+    // always reverts
+    const REVERTER: Address = address!("0x000000000000000000000000000000000000dead");
+
+    fn tx(nonce: u64, to: Address) -> Recovered<TxEnvelope> {
+        let tx = TxLegacy {
+            nonce,
+            gas_price: 10,
+            gas_limit: 100_000,
+            to: TxKind::Call(to),
+            ..Default::default()
+        };
+        sign(tx, SENDER)
+    }
+
+    #[test]
+    fn executes_bundles_atomically() {
+        let mut state = state([
+            (SENDER, AccountInfo { balance: U256::from(u64::MAX), ..Default::default() }),
+            (REVERTER, contract(bytes!("0x60006000fd"))),
+        ]);
+        let factory = factory();
+        let mut evm_env = evm_env();
+        evm_env.block_env.beneficiary = COINBASE;
+        let evm = factory.evm_factory().create_evm(&mut state, evm_env.clone());
+        let mut executor = factory.create_executor(evm, execution_ctx(&evm_env));
+
+        // the second transaction reverts
+        let bundle = [tx(0, COUNTER), tx(1, REVERTER)];
+        assert!(matches!(
+            executor.execute_bundle(&bundle, &[]),
+            Err(BundleExecutionError::TransactionReverted { index: 1, hash })
+                if hash == bundle[1].inner().trie_hash()
+        ));
+
+        // the nonce is invalid
+        assert!(matches!(
+            executor.execute_bundle(&[tx(0, COUNTER), tx(0, COUNTER)], &[]),
+            Err(BundleExecutionError::Execution(BlockExecutionError::Validation(
+                BlockValidationError::InvalidTx { .. }
+            )))
+        ));
+
+        // both bundles were rolled back
+        let db = executor.evm_mut().db_mut();
+        assert_eq!(db.basic(SENDER).unwrap().unwrap().nonce, 0);
+        assert_eq!(db.storage(COUNTER, U256::ZERO).unwrap(), U256::ZERO.into());
+
+        let result = executor.execute_bundle(&bundle, &[bundle[1].inner().trie_hash()]).unwrap();
+        assert!(result.results[0].is_success());
+        assert!(!result.results[1].is_success());
+        assert_eq!(
+            result.gas_used,
+            result.results.iter().map(ExecutionResult::gas_used).sum::<u64>()
+        );
+        assert_eq!(result.coinbase_balance_delta, I256::try_from(result.gas_used * 10).unwrap());
+
+        let result = executor.apply_post_execution_changes().unwrap();
+        assert_eq!(result.receipts.len(), 2);
+        assert_eq!(state.basic(SENDER).unwrap().unwrap().nonce, 2);
+        assert_eq!(state.storage(COUNTER, U256::ZERO).unwrap(), U256::from(1).into());
+    }
+}
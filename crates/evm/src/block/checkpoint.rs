@@ -0,0 +1,232 @@
+//! Checkpoints of the state committed by a block executor.
+
+use super::{BlockExecutionError, InternalBlockExecutionError};
+use alloc::vec::Vec;
+use alloy_primitives::{map::AddressMap, Address};
+use revm::{
+    database::{CacheAccount, State, TransitionAccount},
+    state::EvmState,
+};
+
+/// A checkpoint of a [`BlockExecutor`](super::BlockExecutor), see
+/// [`BlockExecutor::checkpoint`](super::BlockExecutor::checkpoint).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockExecutorCheckpoint {
+    depth: usize,
+}
+
+impl BlockExecutorCheckpoint {
+    /// Returns the number of checkpoints that were active when this checkpoint was created.
+    pub const fn depth(&self) -> usize {
+        self.depth
+    }
+}
+
+/// Stack of checkpoints of a [`State`], used by block executors to implement
+/// [`BlockExecutor::checkpoint`](super::BlockExecutor::checkpoint).
+///
+/// Cached accounts and their transitions are only recorded once they are about to change, so
+/// executors must call [`Self::record`] before committing any state changes, and a checkpoint
+/// only costs as much as the accounts changed after it. Every checkpoint also holds executor
+/// specific data `T`, e.g. the number of receipts and the gas used, which is returned when
+/// reverting.
+///
+/// Reverting only restores the [`State`]: changes reported to a state hook or recorded elsewhere
+/// when they were committed are left to the executor.
+#[derive(Debug, Clone)]
+pub struct StateCheckpoints<T> {
+    checkpoints: Vec<StateCheckpoint<T>>,
+}
+
+#[derive(Debug, Clone)]
+struct StateCheckpoint<T> {
+    /// Accounts changed since the checkpoint, as they were at the time of the checkpoint.
+    accounts: AddressMap<CheckpointAccount>,
+    /// Executor specific data.
+    data: T,
+}
+
+/// An account at the time of a checkpoint.
+#[derive(Debug, Clone)]
+struct CheckpointAccount {
+    /// Cached account, [`None`] if not cached.
+    cached: Option<CacheAccount>,
+    /// Transition of the account in the block, [`None`] if it didn't change yet.
+    transition: Option<TransitionAccount>,
+}
+
+impl<T> Default for StateCheckpoints<T> {
+    fn default() -> Self {
+        Self { checkpoints: Vec::new() }
+    }
+}
+
+impl<T> StateCheckpoints<T> {
+    /// Returns `true` if there are no active checkpoints.
+    pub fn is_empty(&self) -> bool {
+        self.checkpoints.is_empty()
+    }
+
+    /// Creates a new checkpoint holding the given data.
+    ///
+    /// The state is not copied, changed accounts are recorded by [`Self::record`] instead.
+    pub fn checkpoint(&mut self, data: T) -> BlockExecutorCheckpoint {
+        let depth = self.checkpoints.len();
+        self.checkpoints.push(StateCheckpoint { accounts: AddressMap::default(), data });
+        BlockExecutorCheckpoint { depth }
+    }
+
+    /// Records the accounts of `state` that are changed by `changes`.
+    ///
+    /// This must be called before committing `changes` to `state`.
+    pub fn record<DB>(&mut self, state: &State<DB>, changes: &EvmState) {
+        let Some(checkpoint) = self.checkpoints.last_mut() else { return };
+        for (address, account) in changes {
+            if account.is_touched() {
+                checkpoint.record(state, *address);
+            }
+        }
+    }
+
+    /// Discards the given checkpoint and all checkpoints created after it, keeping the changes
+    /// made since.
+    ///
+    /// Changes can still be reverted by reverting an earlier checkpoint.
+    pub fn commit(
+        &mut self,
+        checkpoint: BlockExecutorCheckpoint,
+    ) -> Result<(), BlockExecutionError> {
+        self.ensure_active(checkpoint)?;
+
+        while self.checkpoints.len() > checkpoint.depth {
+            let Some(discarded) = self.checkpoints.pop() else { break };
+            if let Some(previous) = self.checkpoints.last_mut() {
+                // earlier checkpoints already hold the older account
+                for (address, account) in discarded.accounts {
+                    previous.accounts.entry(address).or_insert(account);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reverts `state` to the given checkpoint, discarding it and all checkpoints created after
+    /// it.
+    ///
+    /// Returns the data of the checkpoint.
+    pub fn revert<DB>(
+        &mut self,
+        state: &mut State<DB>,
+        checkpoint: BlockExecutorCheckpoint,
+    ) -> Result<T, BlockExecutionError> {
+        self.ensure_active(checkpoint)?;
+
+        let mut data = None;
+        while self.checkpoints.len() > checkpoint.depth {
+            let Some(reverted) = self.checkpoints.pop() else { break };
+            for (address, account) in reverted.accounts {
+                match account.cached {
+                    Some(cached) => state.cache.accounts.insert(address, cached),
+                    None => state.cache.accounts.remove(&address),
+                };
+                if let Some(transition_state) = &mut state.transition_state {
+                    match account.transition {
+                        Some(transition) => {
+                            transition_state.transitions.insert(address, transition)
+                        }
+                        None => transition_state.transitions.remove(&address),
+                    };
+                }
+            }
+            data = Some(reverted.data);
+        }
+
+        data.ok_or_else(|| InternalBlockExecutionError::UnknownCheckpoint.into())
+    }
+
+    fn ensure_active(
+        &self,
+        checkpoint: BlockExecutorCheckpoint,
+    ) -> Result<(), BlockExecutionError> {
+        if checkpoint.depth >= self.checkpoints.len() {
+            return Err(InternalBlockExecutionError::UnknownCheckpoint.into());
+        }
+        Ok(())
+    }
+}
+
+impl<T> StateCheckpoint<T> {
+    fn record<DB>(&mut self, state: &State<DB>, address: Address) {
+        self.accounts.entry(address).or_insert_with(|| CheckpointAccount {
+            cached: state.cache.accounts.get(&address).cloned(),
+            transition: state
+                .transition_state
+                .as_ref()
+                .and_then(|transition_state| transition_state.transitions.get(&address))
+                .cloned(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        block::{
+            BlockExecutionError, BlockExecutor, BlockExecutorFactory, InternalBlockExecutionError,
+        },
+        test_utils::{evm_env, execution_ctx, factory, state, tx, COUNTER, SENDER},
+        EvmFactory,
+    };
+    use alloy_primitives::U256;
+    use revm::{database::states::bundle_state::BundleRetention, Database};
+
+    #[test]
+    fn reverts_to_checkpoint() {
+        let mut expected = state([]);
+        let mut state = state([]);
+
+        let factory = factory();
+        let evm_env = evm_env();
+        let evm = factory.evm_factory().create_evm(&mut state, evm_env.clone());
+        let mut executor = factory.create_executor(evm, execution_ctx(&evm_env));
+        executor.apply_pre_execution_changes().unwrap();
+
+        let gas_used = executor.execute_transaction(&tx(SENDER, 0, COUNTER)).unwrap();
+
+        let checkpoint = executor.checkpoint().unwrap();
+        executor.execute_transaction(&tx(SENDER, 1, COUNTER)).unwrap();
+        let nested = executor.checkpoint().unwrap();
+        executor.execute_transaction(&tx(SENDER, 2, COUNTER)).unwrap();
+        executor.checkpoint_commit(nested).unwrap();
+        executor.checkpoint_revert(checkpoint).unwrap();
+
+        // the checkpoint is consumed by reverting
+        assert!(matches!(
+            executor.checkpoint_revert(checkpoint),
+            Err(BlockExecutionError::Internal(InternalBlockExecutionError::UnknownCheckpoint))
+        ));
+
+        let db = executor.evm_mut().db_mut();
+        assert_eq!(db.basic(SENDER).unwrap().unwrap().nonce, 1);
+        assert_eq!(db.storage(COUNTER, U256::ZERO).unwrap(), U256::from(1).into());
+
+        // the reverted nonce can be reused
+        executor.execute_transaction(&tx(SENDER, 1, COUNTER)).unwrap();
+        let result = executor.apply_post_execution_changes().unwrap();
+        assert_eq!(result.receipts.len(), 2);
+        assert_eq!(result.gas_used, 2 * gas_used);
+        assert_eq!(state.basic(SENDER).unwrap().unwrap().nonce, 2);
+        assert_eq!(state.storage(COUNTER, U256::ZERO).unwrap(), U256::from(2).into());
+
+        // the transitions of the reverted transactions are discarded
+        let evm = factory.evm_factory().create_evm(&mut expected, evm_env.clone());
+        factory
+            .create_executor(evm, execution_ctx(&evm_env))
+            .execute_block([&tx(SENDER, 0, COUNTER), &tx(SENDER, 1, COUNTER)])
+            .unwrap();
+        expected.merge_transitions(BundleRetention::Reverts);
+        state.merge_transitions(BundleRetention::Reverts);
+        assert_eq!(state.take_bundle(), expected.take_bundle());
+    }
+}
@@ -3,7 +3,7 @@ use alloc::{
     boxed::Box,
     string::{String, ToString},
 };
-use alloy_primitives::B256;
+use alloy_primitives::{Bloom, B256};
 use seismic_alloy_consensus::InputDecryptionElementsError;
 
 /// Block validation error.
@@ -30,6 +30,31 @@ pub enum BlockValidationError {
         /// The available block gas
         block_available_gas: u64,
     },
+    /// Error when transaction blob gas exceeds available block blob gas
+    ///
+    /// The blob gas available in a block is limited by the maximum number of blobs per block of
+    /// the active fork, see [EIP-4844] and [EIP-7691].
+    ///
+    /// [EIP-4844]: https://eips.ethereum.org/EIPS/eip-4844
+    /// [EIP-7691]: https://eips.ethereum.org/EIPS/eip-7691
+    #[error(
+        "transaction blob gas {transaction_blob_gas_used} is more than blocks available blob gas {block_available_blob_gas}"
+    )]
+    TransactionBlobGasMoreThanAvailableBlockBlobGas {
+        /// The transaction's blob gas
+        transaction_blob_gas_used: u64,
+        /// The available block blob gas
+        block_available_blob_gas: u64,
+    },
+    /// Error when the blob parameters of a block with blob transactions are unknown
+    ///
+    /// Blob transactions are rejected instead of being executed without a blob gas limit, see
+    /// [`blob_params_at_timestamp`](crate::eth::spec::EthExecutorSpec::blob_params_at_timestamp).
+    #[error("blob parameters are unknown at timestamp {timestamp}")]
+    MissingBlobParams {
+        /// The timestamp of the block
+        timestamp: u64,
+    },
     /// Error for EIP-4788 when parent beacon block root is missing
     #[error("EIP-4788 parent beacon block root missing for active Cancun block")]
     MissingParentBeaconBlockRoot,
@@ -80,6 +105,51 @@ pub enum BlockValidationError {
     /// [EIP-6110]: https://eips.ethereum.org/EIPS/eip-6110
     #[error("failed to decode deposit requests from receipts: {_0}")]
     DepositRequestDecode(String),
+    /// Error when the gas used by the block doesn't match the header.
+    #[error("block gas used {got} does not match header gas used {expected}")]
+    BlockGasUsedMismatch {
+        /// The gas used by the executed block.
+        got: u64,
+        /// The gas used in the header.
+        expected: u64,
+    },
+    /// Error when the receipts root doesn't match the header.
+    #[error("receipts root {got} does not match header receipts root {expected}")]
+    ReceiptsRootMismatch {
+        /// The receipts root of the executed block.
+        got: B256,
+        /// The receipts root in the header.
+        expected: B256,
+    },
+    /// Error when the logs bloom doesn't match the header.
+    #[error("logs bloom {got} does not match header logs bloom {expected}")]
+    LogsBloomMismatch {
+        /// The logs bloom of the executed block.
+        got: Box<Bloom>,
+        /// The logs bloom in the header.
+        expected: Box<Bloom>,
+    },
+    /// Error when the [EIP-7685] requests hash doesn't match the header.
+    ///
+    /// [EIP-7685]: https://eips.ethereum.org/EIPS/eip-7685
+    #[error("requests hash {got:?} does not match header requests hash {expected:?}")]
+    RequestsHashMismatch {
+        /// The requests hash of the executed block, [`None`] if no requests are expected and
+        /// there are none.
+        got: Option<B256>,
+        /// The requests hash in the header.
+        expected: Option<B256>,
+    },
+    /// Error when the [EIP-4844] blob gas used doesn't match the header.
+    ///
+    /// [EIP-4844]: https://eips.ethereum.org/EIPS/eip-4844
+    #[error("blob gas used {got} does not match header blob gas used {expected}")]
+    BlobGasUsedMismatch {
+        /// The blob gas used by the transactions of the block.
+        got: u64,
+        /// The blob gas used in the header, `0` if unset.
+        expected: u64,
+    },
 }
 
 /// `BlockExecutor` Errors
@@ -149,6 +219,15 @@ pub enum InternalBlockExecutionError {
     /// Unable to decrypt calldata of seismic tx
     #[error("Failed to decrypt seismic tx: {0}")]
     FailedToDecryptSeismicTx(InputDecryptionElementsError),
+    /// Checkpoint that was already committed or reverted, or that belongs to another executor.
+    #[error("unknown block executor checkpoint")]
+    UnknownCheckpoint,
+    /// The block executor does not support checkpoints.
+    #[error("block executor does not support checkpoints")]
+    CheckpointsUnsupported,
+    /// The block executor does not support executing transactions without committing them.
+    #[error("block executor does not support executing transactions without committing them")]
+    UncommittedExecutionUnsupported,
     /// Arbitrary Block Executor Errors
     #[error(transparent)]
     Other(Box<dyn core::error::Error + Send + Sync + 'static>),
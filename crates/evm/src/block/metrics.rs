@@ -0,0 +1,492 @@
+//! Execution metrics of block executors.
+//!
+//! [`MetricsBlockExecutor`] wraps a [`BlockExecutor`] and records [`BlockExecutionMetrics`]: the
+//! time spent and state accessed per transaction and per execution phase.
+
+use super::{
+    BlockExecutionError, BlockExecutionResult, BlockExecutor, BlockExecutorCheckpoint,
+    CommitChanges, ExecutableTx, OnStateHook, StateChangeSource,
+};
+use crate::{
+    precompiles::metrics::{PrecompileMetricsSnapshot, PrecompileProfiler},
+    Evm,
+};
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use alloy_primitives::{map::HashSet, Address};
+use core::{ops::AddAssign, time::Duration};
+use revm::{
+    context::result::{ExecutionResult, ResultAndState},
+    precompile::Precompiles,
+    state::EvmState,
+};
+use std::{
+    sync::{Mutex, MutexGuard},
+    time::Instant,
+};
+
+/// State accessed by a transaction or an execution phase.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StateAccessMetrics {
+    /// Number of accounts loaded.
+    pub accounts_read: usize,
+    /// Number of accounts changed.
+    pub accounts_written: usize,
+    /// Number of storage slots loaded.
+    pub storage_slots_read: usize,
+    /// Number of storage slots changed.
+    pub storage_slots_written: usize,
+}
+
+impl StateAccessMetrics {
+    /// Counts the state accessed by the given state changes.
+    pub fn from_state(state: &EvmState) -> Self {
+        let mut metrics = Self { accounts_read: state.len(), ..Default::default() };
+        for account in state.values() {
+            if account.is_touched() {
+                metrics.accounts_written += 1;
+            }
+            metrics.storage_slots_read += account.storage.len();
+            metrics.storage_slots_written +=
+                account.storage.values().filter(|slot| slot.is_changed()).count();
+        }
+        metrics
+    }
+}
+
+impl AddAssign for StateAccessMetrics {
+    fn add_assign(&mut self, other: Self) {
+        self.accounts_read += other.accounts_read;
+        self.accounts_written += other.accounts_written;
+        self.storage_slots_read += other.storage_slots_read;
+        self.storage_slots_written += other.storage_slots_written;
+    }
+}
+
+/// Metrics of a single committed transaction.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TransactionMetrics {
+    /// Time spent executing and committing the transaction.
+    pub elapsed: Duration,
+    /// Gas used by the transaction.
+    pub gas_used: u64,
+    /// State accessed by the transaction.
+    pub state: StateAccessMetrics,
+    /// Precompiles accessed by the transaction.
+    pub precompiles: Vec<Address>,
+    /// Metrics of the precompile calls of the transaction, if a [`PrecompileProfiler`] is
+    /// configured, see [`MetricsBlockExecutor::with_precompile_profiler`].
+    pub precompile_metrics: Option<PrecompileMetricsSnapshot>,
+}
+
+/// Metrics of an execution phase, i.e. the pre-execution system calls, or the post-execution
+/// requests and rewards.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PhaseMetrics {
+    /// Time spent in the phase.
+    pub elapsed: Duration,
+    /// State changed in the phase, as reported to the state hook.
+    pub state: StateAccessMetrics,
+}
+
+/// Metrics of a block execution recorded by a [`MetricsBlockExecutor`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlockExecutionMetrics {
+    /// Metrics of [`BlockExecutor::apply_pre_execution_changes`].
+    pub pre_execution: PhaseMetrics,
+    /// Metrics of the committed transactions, in block order.
+    pub transactions: Vec<TransactionMetrics>,
+    /// Metrics of [`BlockExecutor::finish`].
+    pub post_execution: PhaseMetrics,
+}
+
+impl BlockExecutionMetrics {
+    /// Returns the total time spent executing transactions.
+    pub fn transactions_elapsed(&self) -> Duration {
+        self.transactions.iter().map(|tx| tx.elapsed).sum()
+    }
+
+    /// Returns the total time spent executing the block.
+    pub fn elapsed(&self) -> Duration {
+        self.pre_execution.elapsed + self.transactions_elapsed() + self.post_execution.elapsed
+    }
+}
+
+/// A [`BlockExecutor`] recording [`BlockExecutionMetrics`] of the wrapped executor.
+///
+/// Transactions are measured in [`BlockExecutor::execute_transaction_with_commit_condition`], and
+/// in [`BlockExecutor::execute_transaction_without_commit`] and
+/// [`BlockExecutor::commit_transaction`] for executors supporting them, so all ways of executing
+/// transactions are covered. The state accessed by committed transactions and by the pre- and
+/// post-execution phases is collected with a state hook installed on the wrapped executor,
+/// forwarding to the hook set with [`BlockExecutor::set_state_hook`].
+///
+/// Precompiles accessed by a transaction are detected by their address, by default the latest
+/// Ethereum precompiles, see [`Self::with_precompiles`].
+#[derive(Debug)]
+pub struct MetricsBlockExecutor<E> {
+    inner: E,
+    /// Profiler of the precompiles of the EVM.
+    precompile_profiler: Option<PrecompileProfiler>,
+    /// Metrics of the phases and committed transactions.
+    metrics: BlockExecutionMetrics,
+    /// Time spent executing the last executed, but not yet committed transaction.
+    pending: Option<(Duration, Option<PrecompileMetricsSnapshot>)>,
+    /// Number of transaction metrics at each active checkpoint.
+    checkpoints: Vec<usize>,
+    /// State shared with the installed state hook.
+    hook: Arc<Mutex<HookState>>,
+}
+
+impl<E: BlockExecutor> MetricsBlockExecutor<E> {
+    /// Wraps the given executor.
+    ///
+    /// This replaces the state hook of the executor, hooks must be set on the returned executor
+    /// instead.
+    pub fn new(mut inner: E) -> Self {
+        let hook = Arc::new(Mutex::new(HookState {
+            precompiles: Precompiles::latest().addresses().copied().collect(),
+            ..Default::default()
+        }));
+        inner.set_state_hook(Some(Box::new(MetricsHook(hook.clone()))));
+        Self {
+            inner,
+            precompile_profiler: None,
+            metrics: BlockExecutionMetrics::default(),
+            pending: None,
+            checkpoints: Vec::new(),
+            hook,
+        }
+    }
+
+    /// Sets the addresses of the precompiles of the EVM, used to detect precompile usage.
+    pub fn with_precompiles(self, precompiles: impl IntoIterator<Item = Address>) -> Self {
+        self.hook_state().precompiles = precompiles.into_iter().collect();
+        self
+    }
+
+    /// Sets the profiler of the precompiles of the EVM, used to record
+    /// [`TransactionMetrics::precompile_metrics`].
+    ///
+    /// The profiler is reset before each transaction, so it must not be shared with other EVMs.
+    /// It only records calls of EVMs using a [`PrecompilesMap`](crate::precompiles::PrecompilesMap)
+    /// instrumented with it, see [`PrecompileProfiler`].
+    pub fn with_precompile_profiler(mut self, profiler: PrecompileProfiler) -> Self {
+        self.precompile_profiler = Some(profiler);
+        self
+    }
+
+    /// Returns the wrapped executor.
+    pub const fn inner(&self) -> &E {
+        &self.inner
+    }
+
+    /// Returns the metrics recorded so far.
+    pub fn metrics(&self) -> BlockExecutionMetrics {
+        self.hook_state().with_phase_state(self.metrics.clone())
+    }
+
+    /// Invokes [`BlockExecutor::finish`], additionally returning the recorded metrics.
+    pub fn finish_with_metrics(
+        self,
+    ) -> Result<
+        (E::Evm, BlockExecutionResult<E::Receipt>, BlockExecutionMetrics),
+        BlockExecutionError,
+    > {
+        let Self { inner, mut metrics, hook, .. } = self;
+
+        let start = Instant::now();
+        let (evm, result) = inner.finish()?;
+        metrics.post_execution.elapsed = start.elapsed();
+
+        let metrics = hook.lock().unwrap_or_else(|err| err.into_inner()).with_phase_state(metrics);
+        Ok((evm, result, metrics))
+    }
+
+    fn hook_state(&self) -> MutexGuard<'_, HookState> {
+        self.hook.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Resets the precompile profiler before executing a transaction.
+    fn reset_precompile_profiler(&self) {
+        if let Some(profiler) = &self.precompile_profiler {
+            profiler.reset();
+        }
+    }
+
+    /// Takes the precompile metrics recorded since the profiler was last reset.
+    fn take_precompile_metrics(&self) -> Option<PrecompileMetricsSnapshot> {
+        self.precompile_profiler.as_ref().map(PrecompileProfiler::take_snapshot)
+    }
+}
+
+impl<E: BlockExecutor> BlockExecutor for MetricsBlockExecutor<E> {
+    type Transaction = E::Transaction;
+    type Receipt = E::Receipt;
+    type Evm = E::Evm;
+
+    fn apply_pre_execution_changes(&mut self) -> Result<(), BlockExecutionError> {
+        let start = Instant::now();
+        self.inner.apply_pre_execution_changes()?;
+        self.metrics.pre_execution.elapsed += start.elapsed();
+        Ok(())
+    }
+
+    fn execute_transaction_with_commit_condition(
+        &mut self,
+        tx: impl ExecutableTx<Self>,
+        f: impl FnOnce(&ExecutionResult<<Self::Evm as Evm>::HaltReason>) -> CommitChanges,
+    ) -> Result<Option<u64>, BlockExecutionError> {
+        self.reset_precompile_profiler();
+
+        let start = Instant::now();
+        let gas_used = self.inner.execute_transaction_with_commit_condition(tx, f)?;
+        let elapsed = start.elapsed();
+
+        // the state of the transaction is reported to the hook when it's committed
+        let transaction = self.hook_state().transaction.take();
+        if let Some(gas_used) = gas_used {
+            let (state, precompiles) = transaction.unwrap_or_default();
+            self.metrics.transactions.push(TransactionMetrics {
+                elapsed,
+                gas_used,
+                state,
+                precompiles,
+                precompile_metrics: self.take_precompile_metrics(),
+            });
+        }
+
+        Ok(gas_used)
+    }
+
+    fn execute_transaction_without_commit(
+        &mut self,
+        tx: impl ExecutableTx<Self>,
+    ) -> Result<ResultAndState<<Self::Evm as Evm>::HaltReason>, BlockExecutionError> {
+        self.reset_precompile_profiler();
+
+        let start = Instant::now();
+        let output = self.inner.execute_transaction_without_commit(tx)?;
+        let elapsed = start.elapsed();
+
+        self.pending = Some((elapsed, self.take_precompile_metrics()));
+
+        Ok(output)
+    }
+
+    fn commit_transaction(
+        &mut self,
+        output: ResultAndState<<Self::Evm as Evm>::HaltReason>,
+        tx: impl ExecutableTx<Self>,
+    ) -> Result<u64, BlockExecutionError> {
+        let (state, precompiles) = self.hook_state().state_access(&output.state);
+
+        let start = Instant::now();
+        let gas_used = self.inner.commit_transaction(output, tx)?;
+        let (elapsed, precompile_metrics) = self.pending.take().unwrap_or_default();
+        // already measured from the output
+        self.hook_state().transaction = None;
+
+        self.metrics.transactions.push(TransactionMetrics {
+            elapsed: elapsed + start.elapsed(),
+            gas_used,
+            state,
+            precompiles,
+            precompile_metrics,
+        });
+
+        Ok(gas_used)
+    }
+
+    fn checkpoint(&mut self) -> Result<BlockExecutorCheckpoint, BlockExecutionError> {
+        let checkpoint = self.inner.checkpoint()?;
+        self.checkpoints.truncate(checkpoint.depth());
+        self.checkpoints.push(self.metrics.transactions.len());
+        Ok(checkpoint)
+    }
+
+    fn checkpoint_commit(
+        &mut self,
+        checkpoint: BlockExecutorCheckpoint,
+    ) -> Result<(), BlockExecutionError> {
+        self.inner.checkpoint_commit(checkpoint)?;
+        self.checkpoints.truncate(checkpoint.depth());
+        Ok(())
+    }
+
+    fn checkpoint_revert(
+        &mut self,
+        checkpoint: BlockExecutorCheckpoint,
+    ) -> Result<(), BlockExecutionError> {
+        self.inner.checkpoint_revert(checkpoint)?;
+        if let Some(transactions) = self.checkpoints.get(checkpoint.depth()) {
+            self.metrics.transactions.truncate(*transactions);
+        }
+        self.checkpoints.truncate(checkpoint.depth());
+        Ok(())
+    }
+
+    fn finish(
+        self,
+    ) -> Result<(Self::Evm, BlockExecutionResult<Self::Receipt>), BlockExecutionError> {
+        self.finish_with_metrics().map(|(evm, result, _)| (evm, result))
+    }
+
+    fn set_state_hook(&mut self, hook: Option<Box<dyn OnStateHook>>) {
+        self.hook_state().hook = hook;
+    }
+
+    fn set_state_diff_recording(&mut self, enabled: bool) {
+        self.inner.set_state_diff_recording(enabled);
+    }
+
+    fn evm_mut(&mut self) -> &mut Self::Evm {
+        self.inner.evm_mut()
+    }
+
+    fn evm(&self) -> &Self::Evm {
+        self.inner.evm()
+    }
+}
+
+/// State of the hook installed by [`MetricsBlockExecutor`].
+#[derive(derive_more::Debug, Default)]
+struct HookState {
+    /// Addresses of the precompiles.
+    precompiles: HashSet<Address>,
+    pre_execution: StateAccessMetrics,
+    post_execution: StateAccessMetrics,
+    /// State accessed by the last committed transaction, not yet recorded by the executor.
+    transaction: Option<(StateAccessMetrics, Vec<Address>)>,
+    /// Hook set on the [`MetricsBlockExecutor`].
+    #[debug(skip)]
+    hook: Option<Box<dyn OnStateHook>>,
+}
+
+impl HookState {
+    /// Sets the state changed by the pre- and post-execution phases on the given metrics.
+    fn with_phase_state(&self, mut metrics: BlockExecutionMetrics) -> BlockExecutionMetrics {
+        metrics.pre_execution.state = self.pre_execution;
+        metrics.post_execution.state = self.post_execution;
+        metrics
+    }
+
+    /// Returns the state and the precompiles accessed by the given transaction state changes.
+    fn state_access(&self, state: &EvmState) -> (StateAccessMetrics, Vec<Address>) {
+        let precompiles =
+            state.keys().filter(|address| self.precompiles.contains(*address)).copied().collect();
+        (StateAccessMetrics::from_state(state), precompiles)
+    }
+}
+
+/// Hook collecting the state changed by transactions and the pre- and post-execution phases.
+struct MetricsHook(Arc<Mutex<HookState>>);
+
+impl OnStateHook for MetricsHook {
+    fn on_state(&mut self, source: StateChangeSource, state: &EvmState) {
+        let mut hook = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        match source {
+            StateChangeSource::PreBlock(_) => {
+                hook.pre_execution += StateAccessMetrics::from_state(state)
+            }
+            StateChangeSource::PostBlock(_) => {
+                hook.post_execution += StateAccessMetrics::from_state(state)
+            }
+            StateChangeSource::Transaction(_) => {
+                let access = hook.state_access(state);
+                hook.transaction = Some(access);
+            }
+        }
+        if let Some(hook) = &mut hook.hook {
+            hook.on_state(source, state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        block::BlockExecutorFactory,
+        test_utils::{contract, evm_env, execution_ctx, factory, state, tx, COUNTER, SENDER},
+        EvmFactory,
+    };
+    use alloy_consensus::TxReceipt;
+    use alloy_primitives::{address, bytes};
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    const IDENTITY: Address = address!("0x0000000000000000000000000000000000000004");
+
+    #[test]
+    fn records_transaction_metrics() {
+        // calls the identity precompile
+        let caller = address!("0x000000000000000000000000000000000000ca11");
+        let mut state = state([(caller, contract(bytes!("0x600060006000600060045afa00")))]);
+
+        let factory = factory();
+        let evm_env = evm_env();
+        let evm = factory.evm_factory().create_evm(&mut state, evm_env.clone());
+        let executor = factory.create_executor(evm, execution_ctx(&evm_env));
+
+        let hook_calls = Arc::new(AtomicUsize::new(0));
+        let mut executor = MetricsBlockExecutor::new(executor).with_state_hook(Some(Box::new({
+            let hook_calls = hook_calls.clone();
+            move |_: StateChangeSource, _: &EvmState| {
+                hook_calls.fetch_add(1, Ordering::Relaxed);
+            }
+        })));
+
+        executor.apply_pre_execution_changes().unwrap();
+        executor.execute_transaction(&tx(SENDER, 0, COUNTER)).unwrap();
+        executor.execute_transaction(&tx(SENDER, 1, caller)).unwrap();
+        let (_, result, metrics) = executor.finish_with_metrics().unwrap();
+
+        assert_eq!(metrics.transactions.len(), 2);
+        for (metrics, receipt) in metrics.transactions.iter().zip(&result.receipts) {
+            assert!(metrics.gas_used > 0);
+            assert!(metrics.gas_used <= receipt.cumulative_gas_used());
+        }
+
+        let counter = &metrics.transactions[0];
+        assert_eq!(counter.state.storage_slots_read, 1);
+        assert_eq!(counter.state.storage_slots_written, 1);
+        assert!(counter.precompiles.is_empty());
+        assert_eq!(counter.precompile_metrics, None);
+
+        let caller = &metrics.transactions[1];
+        assert_eq!(caller.state.storage_slots_written, 0);
+        assert_eq!(caller.precompiles, [IDENTITY]);
+
+        assert!(metrics.elapsed() >= metrics.transactions_elapsed());
+        // both transactions and the post-block balance increments
+        assert_eq!(hook_calls.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn records_only_committed_transactions() {
+        let mut state = state([]);
+        let factory = factory();
+        let evm_env = evm_env();
+        let evm = factory.evm_factory().create_evm(&mut state, evm_env.clone());
+        let executor = factory.create_executor(evm, execution_ctx(&evm_env));
+        let mut executor = MetricsBlockExecutor::new(executor);
+
+        let gas_used = executor
+            .execute_transaction_with_commit_condition(&tx(SENDER, 0, IDENTITY), |_| {
+                CommitChanges::No
+            })
+            .unwrap();
+        assert_eq!(gas_used, None);
+        assert!(executor.metrics().transactions.is_empty());
+
+        let output = executor.execute_transaction_without_commit(&tx(SENDER, 0, IDENTITY)).unwrap();
+        executor.commit_transaction(output, &tx(SENDER, 0, IDENTITY)).unwrap();
+        executor.execute_transaction(&tx(SENDER, 1, IDENTITY)).unwrap();
+
+        let metrics = executor.metrics();
+        assert_eq!(metrics.transactions.len(), 2);
+        for metrics in &metrics.transactions {
+            assert_eq!(metrics.precompiles, [IDENTITY]);
+            assert!(metrics.state.accounts_written > 0);
+        }
+    }
+}
@@ -2,9 +2,14 @@
 
 use crate::{Database, Evm, EvmFactory, FromRecoveredTx, FromTxWithEncoded, RecoveredTx, ToTxEnv};
 use alloc::{boxed::Box, vec::Vec};
-use alloy_eips::eip7685::Requests;
+use alloy_consensus::TxReceipt;
+use alloy_eips::{eip7685::Requests, Encodable2718};
+use alloy_primitives::{Address, Bloom, Log, B256, I256, U256};
 use revm::{
-    context::result::ExecutionResult, database::State, inspector::NoOpInspector, Inspector,
+    context::result::{ExecutionResult, ResultAndState},
+    database::State,
+    inspector::NoOpInspector,
+    Inspector,
 };
 
 mod error;
@@ -13,13 +18,29 @@ pub use error::*;
 mod state_hook;
 pub use state_hook::*;
 
+mod checkpoint;
+pub use checkpoint::*;
+
+mod bundle;
+pub use bundle::*;
+
 pub mod system_calls;
 pub use system_calls::*;
 
 pub mod state_changes;
 
+pub mod state_diff;
+
 pub mod calc;
 
+pub mod validation;
+
+#[cfg(feature = "parallel")]
+pub mod parallel;
+
+#[cfg(feature = "std")]
+pub mod metrics;
+
 /// The result of executing a block.
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct BlockExecutionResult<T> {
@@ -29,6 +50,46 @@ pub struct BlockExecutionResult<T> {
     pub requests: Requests,
     /// The total gas used by the block.
     pub gas_used: u64,
+    /// The total [EIP-4844] blob gas used by the block.
+    ///
+    /// [EIP-4844]: https://eips.ethereum.org/EIPS/eip-4844
+    pub blob_gas_used: u64,
+    /// State diffs of the block, [`None`] unless enabled with
+    /// [`BlockExecutor::set_state_diff_recording`].
+    pub state_diffs: Option<state_diff::BlockStateDiffs>,
+}
+
+impl<T> BlockExecutionResult<T> {
+    /// Calculates the receipts root of the block, see [`validation::calculate_receipts_root`].
+    pub fn receipts_root(&self) -> B256
+    where
+        T: Encodable2718,
+    {
+        validation::calculate_receipts_root(&self.receipts)
+    }
+
+    /// Calculates the logs bloom of the block, see [`validation::calculate_logs_bloom`].
+    pub fn logs_bloom(&self) -> Bloom
+    where
+        T: TxReceipt<Log = Log>,
+    {
+        validation::calculate_logs_bloom(&self.receipts)
+    }
+
+    /// Calculates the [EIP-7685] requests hash of the block.
+    ///
+    /// Note that only blocks after the Prague hardfork commit to the requests hash.
+    ///
+    /// [EIP-7685]: https://eips.ethereum.org/EIPS/eip-7685
+    pub fn requests_hash(&self) -> B256 {
+        self.requests.requests_hash()
+    }
+}
+
+/// Returns the balance of the given account.
+fn coinbase_balance<E: Evm>(evm: &mut E, coinbase: Address) -> Result<U256, BlockExecutionError> {
+    let account = evm.db_mut().basic(coinbase).map_err(BlockExecutionError::other)?;
+    Ok(account.map(|account| account.balance).unwrap_or_default())
 }
 
 /// Helper trait to encapsulate requirements for a type to be used as input for [`BlockExecutor`].
@@ -191,11 +252,158 @@ pub trait BlockExecutor {
     ///
     /// Returns [`None`] if committing changes from the transaction should be skipped via
     /// [`CommitChanges::No`], otherwise returns the gas used by the transaction.
+    ///
+    /// By default, this is implemented with [`BlockExecutor::execute_transaction_without_commit`]
+    /// and [`BlockExecutor::commit_transaction`]. Executors have to implement either this method
+    /// or both of those.
     fn execute_transaction_with_commit_condition(
         &mut self,
         tx: impl ExecutableTx<Self>,
         f: impl FnOnce(&ExecutionResult<<Self::Evm as Evm>::HaltReason>) -> CommitChanges,
-    ) -> Result<Option<u64>, BlockExecutionError>;
+    ) -> Result<Option<u64>, BlockExecutionError> {
+        let output = self.execute_transaction_without_commit(&tx)?;
+
+        if !f(&output.result).should_commit() {
+            return Ok(None);
+        }
+
+        let gas_used = self.commit_transaction(output, tx)?;
+        Ok(Some(gas_used))
+    }
+
+    /// Executes a single transaction without committing its changes.
+    ///
+    /// This validates the transaction against the block, e.g. its gas limit against the gas left
+    /// in the block, and returns the [`ResultAndState`] produced by the EVM. The output can be
+    /// committed with [`BlockExecutor::commit_transaction`].
+    ///
+    /// By default, executors don't support this and return
+    /// [`InternalBlockExecutionError::UncommittedExecutionUnsupported`].
+    fn execute_transaction_without_commit(
+        &mut self,
+        tx: impl ExecutableTx<Self>,
+    ) -> Result<ResultAndState<<Self::Evm as Evm>::HaltReason>, BlockExecutionError> {
+        let _ = tx;
+        Err(InternalBlockExecutionError::UncommittedExecutionUnsupported.into())
+    }
+
+    /// Commits the output of a transaction executed with
+    /// [`BlockExecutor::execute_transaction_without_commit`] to the internal state and builds its
+    /// receipt.
+    ///
+    /// The output must have been produced on top of the current state of the executor. The
+    /// transaction is validated against the block again, so outputs of transactions executed
+    /// independently of each other can be committed safely.
+    ///
+    /// Returns the gas used by the transaction.
+    ///
+    /// By default, executors don't support this and return
+    /// [`InternalBlockExecutionError::UncommittedExecutionUnsupported`].
+    fn commit_transaction(
+        &mut self,
+        output: ResultAndState<<Self::Evm as Evm>::HaltReason>,
+        tx: impl ExecutableTx<Self>,
+    ) -> Result<u64, BlockExecutionError> {
+        let _ = (output, tx);
+        Err(InternalBlockExecutionError::UncommittedExecutionUnsupported.into())
+    }
+
+    /// Creates a checkpoint of the executor, covering its state changes, receipts and gas used.
+    ///
+    /// Transactions committed after the checkpoint can be undone with
+    /// [`BlockExecutor::checkpoint_revert`]. Checkpoints can be nested and are meant to be taken
+    /// after [`BlockExecutor::apply_pre_execution_changes`].
+    ///
+    /// By default, executors don't support checkpoints and return
+    /// [`InternalBlockExecutionError::CheckpointsUnsupported`].
+    fn checkpoint(&mut self) -> Result<BlockExecutorCheckpoint, BlockExecutionError> {
+        Err(InternalBlockExecutionError::CheckpointsUnsupported.into())
+    }
+
+    /// Discards the given checkpoint and all checkpoints created after it, keeping all changes
+    /// committed since.
+    fn checkpoint_commit(
+        &mut self,
+        checkpoint: BlockExecutorCheckpoint,
+    ) -> Result<(), BlockExecutionError> {
+        let _ = checkpoint;
+        Err(InternalBlockExecutionError::CheckpointsUnsupported.into())
+    }
+
+    /// Reverts the executor to the given checkpoint, undoing the state changes, receipts and gas
+    /// used of all transactions committed since. The checkpoint and all checkpoints created after
+    /// it are discarded.
+    ///
+    /// The state hook is invoked with the changes of every transaction when it's committed, so it
+    /// has already seen the reverted changes and is not notified of the revert. Hooks that must
+    /// only observe the final state of the block should not be combined with checkpoints.
+    fn checkpoint_revert(
+        &mut self,
+        checkpoint: BlockExecutorCheckpoint,
+    ) -> Result<(), BlockExecutionError> {
+        let _ = checkpoint;
+        Err(InternalBlockExecutionError::CheckpointsUnsupported.into())
+    }
+
+    /// Executes the given transactions as an atomic bundle.
+    ///
+    /// The transactions are executed in order on top of the current state. If any transaction
+    /// fails to execute, or reverts while its hash is not included in `reverting_tx_hashes`, all
+    /// changes of the bundle are rolled back and an error is returned. Otherwise all transactions
+    /// are committed.
+    ///
+    /// This is built on top of [`BlockExecutor::checkpoint`], and thus requires the executor to
+    /// support checkpoints.
+    fn execute_bundle(
+        &mut self,
+        transactions: impl IntoIterator<Item = impl ExecutableTx<Self>>,
+        reverting_tx_hashes: &[B256],
+    ) -> Result<BundleExecutionResult<<Self::Evm as Evm>::HaltReason>, BundleExecutionError>
+    where
+        Self: Sized,
+        Self::Transaction: Encodable2718,
+    {
+        let coinbase = self.evm().block().beneficiary;
+        let checkpoint = self.checkpoint()?;
+        let balance_before = coinbase_balance(self.evm_mut(), coinbase)?;
+
+        let mut results = Vec::new();
+        let mut gas_used = 0;
+        for (index, tx) in transactions.into_iter().enumerate() {
+            let hash = tx.tx().trie_hash();
+            let mut tx_result = None;
+            let outcome = self.execute_transaction_with_commit_condition(tx, |result| {
+                tx_result = Some(result.clone());
+                if result.is_success() || reverting_tx_hashes.contains(&hash) {
+                    CommitChanges::Yes
+                } else {
+                    CommitChanges::No
+                }
+            });
+
+            match outcome {
+                Ok(Some(tx_gas_used)) => gas_used += tx_gas_used,
+                Ok(None) => {
+                    self.checkpoint_revert(checkpoint)?;
+                    return Err(BundleExecutionError::TransactionReverted { index, hash });
+                }
+                Err(err) => {
+                    self.checkpoint_revert(checkpoint)?;
+                    return Err(err.into());
+                }
+            }
+            results.extend(tx_result);
+        }
+
+        let balance_after = coinbase_balance(self.evm_mut(), coinbase)?;
+        self.checkpoint_commit(checkpoint)?;
+
+        Ok(BundleExecutionResult {
+            results,
+            gas_used,
+            coinbase_balance_delta: I256::from_raw(balance_after.wrapping_sub(balance_before)),
+        })
+    }
 
     /// Applies any necessary changes after executing the block's transactions, completes execution
     /// and returns the underlying EVM along with execution result.
@@ -214,6 +422,9 @@ pub trait BlockExecutor {
     }
 
     /// Sets a hook to be called after each state change during execution.
+    ///
+    /// Changes that are later undone with [`BlockExecutor::checkpoint_revert`], e.g. by a failed
+    /// [`BlockExecutor::execute_bundle`], have already been reported to the hook.
     fn set_state_hook(&mut self, hook: Option<Box<dyn OnStateHook>>);
 
     /// A builder-style helper to invoke [`BlockExecutor::set_state_hook`].
@@ -226,6 +437,27 @@ pub trait BlockExecutor {
         self
     }
 
+    /// Enables or disables recording of state diffs.
+    ///
+    /// When enabled, the executor records a diff of every state change, i.e. of every committed
+    /// transaction and system call, and returns them in [`BlockExecutionResult::state_diffs`].
+    /// Irregular state transitions, e.g. at the DAO fork, are not included.
+    ///
+    /// By default, executors don't support recording state diffs and ignore this.
+    fn set_state_diff_recording(&mut self, enabled: bool) {
+        let _ = enabled;
+    }
+
+    /// A builder-style helper to invoke [`BlockExecutor::set_state_diff_recording`].
+    #[must_use]
+    fn with_state_diff_recording(mut self, enabled: bool) -> Self
+    where
+        Self: Sized,
+    {
+        self.set_state_diff_recording(enabled);
+        self
+    }
+
     /// Exposes mutable reference to EVM.
     fn evm_mut(&mut self) -> &mut Self::Evm;
 
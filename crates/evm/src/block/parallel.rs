@@ -0,0 +1,493 @@
+//! Optimistic parallel execution of block transactions.
+//!
+//! [`ParallelBlockExecutor`] executes all transactions of a block speculatively and in parallel on
+//! top of the state before the block, recording the state read by each transaction. The results
+//! are then committed in block order. A transaction that read state changed by an earlier
+//! transaction of the block is re-executed on top of the up-to-date state, so the resulting state
+//! and receipts are the same as with sequential execution.
+
+use crate::{
+    block::{BlockExecutionError, BlockExecutionResult, BlockExecutor, BlockExecutorFactory},
+    Database, Evm, EvmEnv, EvmFactory, RecoveredTx, ToTxEnv,
+};
+use alloc::vec::Vec;
+use alloy_primitives::{map::HashMap, Address, FlaggedStorage, B256, U256};
+use core::{
+    error::Error,
+    num::NonZeroUsize,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use revm::{
+    bytecode::opcode,
+    context::result::ResultAndState,
+    database::State,
+    interpreter::{interpreter_types::Jumps, CallInputs, CallOutcome, Interpreter},
+    primitives::StorageKey,
+    state::{AccountInfo, Bytecode},
+    Database as _, DatabaseRef, Inspector,
+};
+use std::thread;
+
+/// Statistics of a [`ParallelBlockExecutor::execute_block`] run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParallelExecutionStats {
+    /// Number of executed transactions.
+    pub transactions: usize,
+    /// Number of transactions whose speculative result was discarded and that were executed
+    /// again on top of the up-to-date state.
+    pub reexecuted: usize,
+}
+
+/// Executes the transactions of a block in parallel.
+///
+/// Every transaction is first executed on top of the state before the block on one of
+/// [`threads`](Self::with_threads) worker threads. The speculative results are then validated
+/// and committed in block order on a regular [`BlockExecutor`]: if any account or storage slot
+/// read by the transaction has changed in the meantime, the transaction is executed again.
+///
+/// Transactions only paying fees to the block beneficiary don't conflict with each other, the
+/// beneficiary balance is only compared if the transaction observed it, e.g. via `BALANCE` or by
+/// calling it.
+#[derive(Debug, Clone)]
+pub struct ParallelBlockExecutor<'a, F> {
+    factory: &'a F,
+    threads: NonZeroUsize,
+}
+
+impl<'a, F: BlockExecutorFactory> ParallelBlockExecutor<'a, F> {
+    /// Creates a new [`ParallelBlockExecutor`] using all available cores.
+    pub fn new(factory: &'a F) -> Self {
+        Self { factory, threads: thread::available_parallelism().unwrap_or(NonZeroUsize::MIN) }
+    }
+
+    /// Sets the number of worker threads used for speculative execution.
+    pub const fn with_threads(mut self, threads: NonZeroUsize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Returns the number of worker threads used for speculative execution.
+    pub const fn threads(&self) -> NonZeroUsize {
+        self.threads
+    }
+
+    /// Executes the given transactions as a block on top of `db`, applying pre and post execution
+    /// changes.
+    ///
+    /// Since every speculative execution needs its own executor, `execution_ctx` is called once
+    /// per transaction and once for the executor committing the block.
+    ///
+    /// Pre-execution changes are applied before committing the first transaction, so transactions
+    /// reading state modified by system calls are re-executed. Speculative results are validated
+    /// against the block, e.g. its gas limit, by [`BlockExecutor::commit_transaction`].
+    pub fn execute_block<DB, T, C>(
+        &self,
+        db: &mut State<DB>,
+        evm_env: EvmEnv<<F::EvmFactory as EvmFactory>::Spec>,
+        execution_ctx: C,
+        transactions: &[T],
+    ) -> Result<(BlockExecutionResult<F::Receipt>, ParallelExecutionStats), BlockExecutionError>
+    where
+        DB: Database,
+        State<DB>: DatabaseRef<Error: Error + Send + Sync + 'static> + Sync,
+        T: ToTxEnv<<F::EvmFactory as EvmFactory>::Tx> + RecoveredTx<F::Transaction> + Sync,
+        C: for<'b> Fn(&'b EvmEnv<<F::EvmFactory as EvmFactory>::Spec>) -> F::ExecutionCtx<'b>
+            + Sync,
+        F: Sync,
+    {
+        let speculations = self.speculate(db, &evm_env, &execution_ctx, transactions);
+
+        let coinbase = evm_env.block_env.beneficiary;
+        let mut stats =
+            ParallelExecutionStats { transactions: transactions.len(), ..Default::default() };
+
+        let evm = self.factory.evm_factory().create_evm(db, evm_env.clone());
+        let mut executor = self.factory.create_executor(evm, execution_ctx(&evm_env));
+        executor.apply_pre_execution_changes()?;
+
+        for (tx, speculation) in transactions.iter().zip(speculations) {
+            let output = match speculation {
+                Some(speculation) => validate(executor.evm_mut().db_mut(), coinbase, speculation)?,
+                None => None,
+            };
+
+            let output = match output {
+                Some(output) => output,
+                None => {
+                    stats.reexecuted += 1;
+                    executor.execute_transaction_without_commit(tx)?
+                }
+            };
+
+            executor.commit_transaction(output, tx)?;
+        }
+
+        Ok((executor.apply_post_execution_changes()?, stats))
+    }
+
+    /// Executes all transactions on top of `db` in parallel.
+    ///
+    /// Returns [`None`] for transactions that failed to execute, those are re-executed when
+    /// committing.
+    fn speculate<DB, T, C>(
+        &self,
+        db: &State<DB>,
+        evm_env: &EvmEnv<<F::EvmFactory as EvmFactory>::Spec>,
+        execution_ctx: &C,
+        transactions: &[T],
+    ) -> Vec<Option<Speculation<<F::EvmFactory as EvmFactory>::HaltReason>>>
+    where
+        DB: Database,
+        State<DB>: DatabaseRef<Error: Error + Send + Sync + 'static> + Sync,
+        T: ToTxEnv<<F::EvmFactory as EvmFactory>::Tx> + RecoveredTx<F::Transaction> + Sync,
+        C: for<'b> Fn(&'b EvmEnv<<F::EvmFactory as EvmFactory>::Spec>) -> F::ExecutionCtx<'b>
+            + Sync,
+        F: Sync,
+    {
+        let next = AtomicUsize::new(0);
+        let threads = self.threads.get().min(transactions.len());
+
+        let mut speculations = Vec::new();
+        speculations.resize_with(transactions.len(), || None);
+
+        thread::scope(|scope| {
+            let workers = (0..threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut results = Vec::new();
+                        loop {
+                            let index = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(tx) = transactions.get(index) else { break };
+                            results
+                                .push((index, self.speculate_one(db, evm_env, execution_ctx, tx)));
+                        }
+                        results
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            for worker in workers {
+                let results = worker.join().unwrap_or_else(|err| std::panic::resume_unwind(err));
+                for (index, speculation) in results {
+                    speculations[index] = speculation;
+                }
+            }
+        });
+
+        speculations
+    }
+
+    /// Executes a single transaction on top of `db`, recording its reads.
+    fn speculate_one<DB, T, C>(
+        &self,
+        db: &State<DB>,
+        evm_env: &EvmEnv<<F::EvmFactory as EvmFactory>::Spec>,
+        execution_ctx: &C,
+        tx: &T,
+    ) -> Option<Speculation<<F::EvmFactory as EvmFactory>::HaltReason>>
+    where
+        DB: Database,
+        State<DB>: DatabaseRef<Error: Error + Send + Sync + 'static>,
+        T: ToTxEnv<<F::EvmFactory as EvmFactory>::Tx> + RecoveredTx<F::Transaction>,
+        C: for<'b> Fn(&'b EvmEnv<<F::EvmFactory as EvmFactory>::Spec>) -> F::ExecutionCtx<'b>,
+    {
+        let coinbase = evm_env.block_env.beneficiary;
+        let mut state = State::builder().with_database(ReadRecorder::new(db)).build();
+        let mut inspector = CoinbaseInspector { coinbase, accessed: *tx.signer() == coinbase };
+
+        let output = {
+            let evm = self.factory.evm_factory().create_evm_with_inspector(
+                &mut state,
+                evm_env.clone(),
+                &mut inspector,
+            );
+            self.factory
+                .create_executor(evm, execution_ctx(evm_env))
+                .execute_transaction_without_commit(tx)
+                .ok()?
+        };
+
+        Some(Speculation {
+            output,
+            reads: state.database.reads,
+            coinbase_accessed: inspector.accessed,
+        })
+    }
+}
+
+/// Outcome of a speculative transaction execution.
+#[derive(Debug)]
+struct Speculation<H> {
+    /// Output of the transaction.
+    output: ResultAndState<H>,
+    /// State read by the transaction.
+    reads: Reads,
+    /// Whether the transaction observed the block beneficiary.
+    coinbase_accessed: bool,
+}
+
+/// Values read from the database during a speculative execution.
+#[derive(Debug, Default)]
+struct Reads {
+    accounts: HashMap<Address, Option<AccountInfo>>,
+    storage: HashMap<(Address, StorageKey), FlaggedStorage>,
+}
+
+/// Database recording the accounts and storage read from the wrapped database.
+///
+/// Code and block hashes don't change during block execution and are not recorded.
+#[derive(Debug)]
+struct ReadRecorder<'a, DB> {
+    db: &'a DB,
+    reads: Reads,
+}
+
+impl<'a, DB> ReadRecorder<'a, DB> {
+    fn new(db: &'a DB) -> Self {
+        Self { db, reads: Reads::default() }
+    }
+}
+
+impl<DB: DatabaseRef> revm::Database for ReadRecorder<'_, DB> {
+    type Error = DB::Error;
+
+    fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        let info = self.db.basic_ref(address)?;
+        self.reads.accounts.entry(address).or_insert_with(|| info.clone());
+        Ok(info)
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        self.db.code_by_hash_ref(code_hash)
+    }
+
+    fn storage(
+        &mut self,
+        address: Address,
+        index: StorageKey,
+    ) -> Result<FlaggedStorage, Self::Error> {
+        let value = self.db.storage_ref(address, index)?;
+        self.reads.storage.entry((address, index)).or_insert(value);
+        Ok(value)
+    }
+
+    fn block_hash(&mut self, number: u64) -> Result<B256, Self::Error> {
+        self.db.block_hash_ref(number)
+    }
+}
+
+/// Inspector detecting whether a transaction observes the block beneficiary, other than by
+/// paying fees to it.
+#[derive(Debug)]
+struct CoinbaseInspector {
+    coinbase: Address,
+    accessed: bool,
+}
+
+impl<CTX> Inspector<CTX> for CoinbaseInspector {
+    fn step(&mut self, interp: &mut Interpreter, _context: &mut CTX) {
+        if matches!(
+            interp.bytecode.opcode(),
+            opcode::BALANCE | opcode::EXTCODESIZE | opcode::EXTCODECOPY | opcode::EXTCODEHASH
+        ) {
+            let target = interp.stack.peek(0).unwrap_or_default();
+            if Address::from_word(B256::from(target)) == self.coinbase {
+                self.accessed = true;
+            }
+        }
+    }
+
+    fn call(&mut self, _context: &mut CTX, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        if [inputs.target_address, inputs.bytecode_address, inputs.caller].contains(&self.coinbase)
+        {
+            self.accessed = true;
+        }
+        None
+    }
+
+    fn selfdestruct(&mut self, contract: Address, target: Address, _value: U256) {
+        if contract == self.coinbase || target == self.coinbase {
+            self.accessed = true;
+        }
+    }
+}
+
+/// Validates the reads of a speculative execution against the current state.
+///
+/// Returns the output of the transaction if it is valid on top of the current state, with the
+/// beneficiary balance adjusted to include the fees paid by earlier transactions.
+fn validate<DB: Database, H>(
+    db: &mut State<DB>,
+    coinbase: Address,
+    speculation: Speculation<H>,
+) -> Result<Option<ResultAndState<H>>, BlockExecutionError> {
+    let Speculation { mut output, reads, coinbase_accessed } = speculation;
+
+    // accounts are checked first, as `State` expects accounts to be loaded before their storage
+    let mut coinbase_balance = None;
+    for (address, read) in reads.accounts {
+        let current = db.basic(address).map_err(BlockExecutionError::other)?;
+        if address == coinbase && !coinbase_accessed {
+            // a missing beneficiary has a zero balance, e.g. before receiving its first fees
+            let (read, current) = (read.unwrap_or_default(), current.unwrap_or_default());
+            if read.nonce != current.nonce || read.code_hash != current.code_hash {
+                return Ok(None);
+            }
+            coinbase_balance = Some((read.balance, current.balance));
+        } else if read != current {
+            return Ok(None);
+        }
+    }
+
+    for ((address, index), read) in reads.storage {
+        if db.storage(address, index).map_err(BlockExecutionError::other)? != read {
+            return Ok(None);
+        }
+    }
+
+    if let Some((read, current)) = coinbase_balance {
+        if let Some(account) = output.state.get_mut(&coinbase) {
+            let Some(balance) =
+                account.info.balance.checked_sub(read).and_then(|fees| fees.checked_add(current))
+            else {
+                return Ok(None);
+            };
+            account.info.balance = balance;
+        }
+    }
+
+    Ok(Some(output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        block::BlockValidationError,
+        test_utils::{execution_ctx, factory, sign, state, tx, COUNTER, SENDER as ALICE},
+    };
+    use alloy_consensus::TxLegacy;
+    use alloy_primitives::{address, TxKind};
+    use revm::database::states::bundle_state::BundleRetention;
+
+    const BOB: Address = address!("0x0000000000000000000000000000000000000b0b");
+    const CAROL: Address = address!("0x00000000000000000000000000000000000ca201");
+    const COINBASE: Address = address!("0x00000000000000000000000000000000c014ba5e");
+
+    fn evm_env() -> EvmEnv {
+        let mut evm_env = crate::test_utils::evm_env();
+        evm_env.block_env.beneficiary = COINBASE;
+        evm_env.block_env.gas_limit = 30_000_000;
+        evm_env
+    }
+
+    #[test]
+    fn matches_sequential_execution() {
+        let factory = factory();
+        let transactions = [
+            tx(ALICE, 0, COUNTER),
+            // conflicts with the first transaction on the counter slot
+            tx(BOB, 0, COUNTER),
+            // conflicts with the first transaction on the nonce of the sender
+            tx(ALICE, 1, COUNTER),
+            tx(CAROL, 0, Address::with_last_byte(0x42)),
+        ];
+
+        let mut sequential_db = state([]);
+        let sequential = factory
+            .create_executor(
+                factory.evm_factory().create_evm(&mut sequential_db, evm_env()),
+                execution_ctx(&evm_env()),
+            )
+            .execute_block(transactions.iter())
+            .unwrap();
+        sequential_db.merge_transitions(BundleRetention::Reverts);
+
+        let mut parallel_db = state([]);
+        let (parallel, stats) = ParallelBlockExecutor::new(&factory)
+            .with_threads(NonZeroUsize::new(2).unwrap())
+            .execute_block(&mut parallel_db, evm_env(), execution_ctx, &transactions)
+            .unwrap();
+        parallel_db.merge_transitions(BundleRetention::Reverts);
+
+        assert_eq!(parallel, sequential);
+        assert_eq!(parallel_db.take_bundle(), sequential_db.take_bundle());
+        assert_eq!(stats, ParallelExecutionStats { transactions: 4, reexecuted: 2 });
+    }
+
+    #[test]
+    fn creates_beneficiary_without_conflicts() {
+        let factory = factory();
+        let paying_tx = |sender, to| {
+            sign(
+                TxLegacy {
+                    gas_price: 1,
+                    gas_limit: 100_000,
+                    to: TxKind::Call(to),
+                    ..Default::default()
+                },
+                sender,
+            )
+        };
+        // the beneficiary doesn't exist before the block, both transactions pay fees to it
+        let transactions = [
+            paying_tx(ALICE, Address::with_last_byte(0x42)),
+            paying_tx(BOB, Address::with_last_byte(0x43)),
+        ];
+        let senders =
+            [ALICE, BOB].map(|sender| (sender, AccountInfo::from_balance(U256::from(1_000_000))));
+
+        let mut sequential_db = state(senders.clone());
+        let sequential = factory
+            .create_executor(
+                factory.evm_factory().create_evm(&mut sequential_db, evm_env()),
+                execution_ctx(&evm_env()),
+            )
+            .execute_block(transactions.iter())
+            .unwrap();
+        sequential_db.merge_transitions(BundleRetention::Reverts);
+
+        let mut parallel_db = state(senders);
+        let (parallel, stats) = ParallelBlockExecutor::new(&factory)
+            .with_threads(NonZeroUsize::new(2).unwrap())
+            .execute_block(&mut parallel_db, evm_env(), execution_ctx, &transactions)
+            .unwrap();
+        parallel_db.merge_transitions(BundleRetention::Reverts);
+
+        assert_eq!(parallel, sequential);
+        assert_eq!(
+            parallel_db.basic_ref(COINBASE).unwrap().map(|account| account.balance),
+            Some(U256::from(42_000))
+        );
+        assert_eq!(parallel_db.take_bundle(), sequential_db.take_bundle());
+        assert_eq!(stats, ParallelExecutionStats { transactions: 2, reexecuted: 0 });
+    }
+
+    #[test]
+    fn enforces_block_gas_limit() {
+        let factory = factory();
+        // speculative executions don't conflict, but the second transaction doesn't fit in the
+        // block after the first one
+        let transactions = [
+            tx(ALICE, 0, Address::with_last_byte(0x42)),
+            tx(BOB, 0, Address::with_last_byte(0x43)),
+        ];
+        let mut evm_env = evm_env();
+        evm_env.block_env.gas_limit = 120_000;
+
+        let mut db = state([]);
+        let err = ParallelBlockExecutor::new(&factory)
+            .execute_block(&mut db, evm_env, execution_ctx, &transactions)
+            .unwrap_err();
+
+        assert!(matches!(
+            err,
+            BlockExecutionError::Validation(
+                BlockValidationError::TransactionGasLimitMoreThanAvailableBlockGas {
+                    transaction_gas_limit: 100_000,
+                    block_available_gas: 99_000,
+                }
+            )
+        ));
+    }
+}
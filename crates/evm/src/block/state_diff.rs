@@ -0,0 +1,255 @@
+//! Per-transaction state diffs.
+
+use super::StateChangeSource;
+use alloc::vec::Vec;
+use alloy_primitives::{
+    map::{AddressMap, HashMap},
+    Address, FlaggedStorage, U256,
+};
+use revm::{
+    primitives::{StorageKey, KECCAK_EMPTY},
+    state::{AccountInfo, Bytecode, EvmState},
+    Database,
+};
+
+/// State diffs of a block, in execution order, see
+/// [`BlockExecutor::set_state_diff_recording`](super::BlockExecutor::set_state_diff_recording).
+pub type BlockStateDiffs = Vec<(StateChangeSource, StateDiff)>;
+
+/// Changed accounts of a single state change, e.g. a transaction or system call.
+pub type StateDiff = AddressMap<AccountDiff>;
+
+/// Change of a single account.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccountDiff {
+    /// Account before the change, [`None`] if it didn't exist.
+    ///
+    /// Bytecode is not included in the account info.
+    pub pre: Option<AccountInfo>,
+    /// Account after the change, [`None`] if it was destroyed.
+    ///
+    /// Bytecode is not included in the account info, see [`Self::code`].
+    pub post: Option<AccountInfo>,
+    /// Bytecode deployed by the change, if the code hash changed.
+    pub code: Option<Bytecode>,
+    /// Changed storage slots.
+    pub storage: HashMap<StorageKey, StorageSlotDiff>,
+}
+
+impl AccountDiff {
+    /// Returns `true` if the account is unchanged.
+    pub fn is_empty(&self) -> bool {
+        self.pre == self.post && self.storage.is_empty()
+    }
+}
+
+/// Change of a single storage slot.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StorageSlotDiff {
+    /// Value before the change.
+    pub pre: FlaggedStorage,
+    /// Value after the change.
+    pub post: FlaggedStorage,
+}
+
+/// Computes the diff of the given state changes, reading the pre-state of changed accounts from
+/// `db`.
+///
+/// This must be called before committing `changes` to `db`. Accounts that were only read or are
+/// unchanged are not included.
+pub fn state_diff<DB: Database>(db: &mut DB, changes: &EvmState) -> Result<StateDiff, DB::Error> {
+    let mut diff = StateDiff::default();
+    for (address, account) in changes {
+        if !account.is_touched() {
+            continue;
+        }
+
+        let pre = db.basic(*address)?.map(strip_code);
+        // touched accounts that stay empty are not created
+        let post = (!account.is_selfdestructed() && !(pre.is_none() && account.is_empty()))
+            .then(|| strip_code(account.info.clone()));
+        let code = post
+            .as_ref()
+            .filter(|post| {
+                post.code_hash != KECCAK_EMPTY
+                    && pre.as_ref().map(|pre| pre.code_hash) != Some(post.code_hash)
+            })
+            .and(account.info.code.clone());
+        let storage = account
+            .storage
+            .iter()
+            .filter(|(_, slot)| slot.is_changed())
+            .map(|(key, slot)| {
+                (*key, StorageSlotDiff { pre: slot.original_value, post: slot.present_value })
+            })
+            .collect();
+
+        let account = AccountDiff { pre, post, code, storage };
+        if !account.is_empty() {
+            diff.insert(*address, account);
+        }
+    }
+    Ok(diff)
+}
+
+/// Computes the diff of the given balance increments, reading the pre-state of the incremented
+/// accounts from `db`.
+///
+/// This must be called before applying the increments to `db`. Zero increments are ignored.
+pub fn balance_increments_diff<DB: Database>(
+    db: &mut DB,
+    balance_increments: &HashMap<Address, u128>,
+) -> Result<StateDiff, DB::Error> {
+    let mut diff = StateDiff::default();
+    for (address, increment) in balance_increments {
+        if *increment == 0 {
+            continue;
+        }
+
+        let pre = db.basic(*address)?.map(strip_code);
+        let mut post = pre.clone().unwrap_or_default();
+        post.balance = post.balance.saturating_add(U256::from(*increment));
+        diff.insert(
+            *address,
+            AccountDiff { pre, post: Some(strip_code(post)), ..Default::default() },
+        );
+    }
+    Ok(diff)
+}
+
+fn strip_code(info: AccountInfo) -> AccountInfo {
+    AccountInfo { code: None, ..info }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        block::{BlockExecutor, BlockExecutorFactory},
+        test_utils::{evm_env, execution_ctx, factory, state, tx, COUNTER, SENDER},
+        EvmFactory,
+    };
+    use alloy_primitives::{address, bytes};
+    use revm::{
+        database::{CacheDB, EmptyDB},
+        state::{Account, AccountStatus, EvmStorageSlot},
+    };
+
+    #[test]
+    fn diffs_touched_accounts() {
+        let existing = address!("0x000000000000000000000000000000000000a11c");
+        let created = address!("0x000000000000000000000000000000000000c0de");
+        let read = address!("0x000000000000000000000000000000000000beef");
+
+        let mut db = CacheDB::new(EmptyDB::new());
+        db.insert_account_info(existing, AccountInfo { nonce: 1, ..Default::default() });
+
+        let code = Bytecode::new_raw(bytes!("0x00"));
+        let changes = EvmState::from_iter([
+            (
+                existing,
+                Account {
+                    info: AccountInfo { nonce: 2, ..Default::default() },
+                    status: AccountStatus::Touched,
+                    ..Default::default()
+                },
+            ),
+            (
+                created,
+                Account {
+                    info: AccountInfo {
+                        nonce: 1,
+                        code_hash: code.hash_slow(),
+                        code: Some(code.clone()),
+                        ..Default::default()
+                    },
+                    storage: [
+                        (U256::ZERO, U256::ZERO, U256::from(1)),
+                        (U256::from(1), U256::from(1), U256::from(1)),
+                    ]
+                    .into_iter()
+                    .map(|(key, original, present)| {
+                        let slot = EvmStorageSlot {
+                            original_value: original.into(),
+                            present_value: present.into(),
+                            is_cold: false,
+                            transaction_id: 0,
+                        };
+                        (key, slot)
+                    })
+                    .collect(),
+                    status: AccountStatus::Touched | AccountStatus::Created,
+                    ..Default::default()
+                },
+            ),
+            (read, Account::default()),
+        ]);
+
+        let diff = state_diff(&mut db, &changes).unwrap();
+        assert_eq!(diff.len(), 2);
+
+        let account = &diff[&existing];
+        assert_eq!(account.pre.as_ref().map(|info| info.nonce), Some(1));
+        assert_eq!(account.post.as_ref().map(|info| info.nonce), Some(2));
+        assert_eq!(account.code, None);
+
+        let account = &diff[&created];
+        assert_eq!(account.pre, None);
+        assert_eq!(account.post.as_ref().and_then(|info| info.code.as_ref()), None);
+        assert_eq!(account.code, Some(code));
+        assert_eq!(
+            account.storage,
+            HashMap::from_iter([(
+                U256::ZERO,
+                StorageSlotDiff { pre: U256::ZERO.into(), post: U256::from(1).into() }
+            )])
+        );
+    }
+
+    #[test]
+    fn records_block_state_diffs() {
+        let mut state = state([]);
+
+        let factory = factory();
+        let evm_env = evm_env();
+        let evm = factory.evm_factory().create_evm(&mut state, evm_env.clone());
+        let executor =
+            factory.create_executor(evm, execution_ctx(&evm_env)).with_state_diff_recording(true);
+        let result = executor.execute_block([&tx(SENDER, 0, COUNTER)]).unwrap();
+
+        let diffs = result.state_diffs.unwrap();
+        assert_eq!(diffs.len(), 1);
+        let (source, diff) = &diffs[0];
+        assert_eq!(*source, StateChangeSource::Transaction(0));
+        assert_eq!(diff.len(), 2);
+        assert_eq!(diff[&SENDER].pre, None);
+        assert_eq!(diff[&SENDER].post.as_ref().map(|info| info.nonce), Some(1));
+        assert_eq!(
+            diff[&COUNTER].storage[&U256::ZERO],
+            StorageSlotDiff { pre: U256::ZERO.into(), post: U256::from(1).into() }
+        );
+    }
+
+    #[test]
+    fn diffs_balance_increments() {
+        let existing = address!("0x000000000000000000000000000000000000a11c");
+        let created = address!("0x000000000000000000000000000000000000c0de");
+
+        let mut db = CacheDB::new(EmptyDB::new());
+        db.insert_account_info(
+            existing,
+            AccountInfo { balance: U256::from(1), ..Default::default() },
+        );
+
+        let diff = balance_increments_diff(
+            &mut db,
+            &HashMap::from_iter([(existing, 2), (created, 3), (Address::ZERO, 0)]),
+        )
+        .unwrap();
+        assert_eq!(diff.len(), 2);
+        assert_eq!(diff[&existing].pre.as_ref().map(|info| info.balance), Some(U256::from(1)));
+        assert_eq!(diff[&existing].post.as_ref().map(|info| info.balance), Some(U256::from(3)));
+        assert_eq!(diff[&created].pre, None);
+        assert_eq!(diff[&created].post.as_ref().map(|info| info.balance), Some(U256::from(3)));
+    }
+}
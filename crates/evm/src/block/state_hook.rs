@@ -7,7 +7,7 @@ pub trait OnStateHook: Send + 'static {
 }
 
 /// Source of the state change
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StateChangeSource {
     /// Transaction with its index
     Transaction(usize),
@@ -18,7 +18,7 @@ pub enum StateChangeSource {
 }
 
 /// Source of the pre-block state change
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StateChangePreBlockSource {
     /// EIP-2935 blockhashes contract
     BlockHashesContract,
@@ -29,7 +29,7 @@ pub enum StateChangePreBlockSource {
 }
 
 /// Source of the post-block state change
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StateChangePostBlockSource {
     /// Balance increments from block rewards and withdrawals
     BalanceIncrements,
@@ -1,16 +1,19 @@
 //! System contract call functions.
 
 use crate::{
-    block::{BlockExecutionError, OnStateHook},
-    Evm,
+    block::{
+        state_diff::{balance_increments_diff, state_diff, BlockStateDiffs},
+        BlockExecutionError, OnStateHook,
+    },
+    Database, Evm,
 };
-use alloc::{borrow::Cow, boxed::Box};
+use alloc::{borrow::Cow, boxed::Box, vec::Vec};
 use alloy_consensus::BlockHeader;
 use alloy_eips::{
     eip7002::WITHDRAWAL_REQUEST_TYPE, eip7251::CONSOLIDATION_REQUEST_TYPE, eip7685::Requests,
 };
 use alloy_hardforks::EthereumHardforks;
-use alloy_primitives::{Bytes, B256};
+use alloy_primitives::{map::HashMap, Address, Bytes, B256};
 use revm::{state::EvmState, DatabaseCommit};
 
 use super::{StateChangePostBlockSource, StateChangePreBlockSource, StateChangeSource};
@@ -29,13 +32,15 @@ pub struct SystemCaller<Spec> {
     /// Optional hook to be called after each state change.
     #[debug(skip)]
     hook: Option<Box<dyn OnStateHook>>,
+    /// Recorded state diffs, [`None`] if recording is disabled.
+    state_diffs: Option<BlockStateDiffs>,
 }
 
 impl<Spec> SystemCaller<Spec> {
     /// Create a new system caller with the given EVM config, database, and chain spec, and creates
     /// the EVM with the given initialized config and block environment.
     pub const fn new(spec: Spec) -> Self {
-        Self { spec, hook: None }
+        Self { spec, hook: None, state_diffs: None }
     }
 
     /// Installs a custom hook to be called after each state change.
@@ -43,6 +48,70 @@ impl<Spec> SystemCaller<Spec> {
         self.hook = hook;
         self
     }
+
+    /// Enables or disables recording of state diffs, discarding diffs recorded so far.
+    pub fn with_state_diff_recording(&mut self, enabled: bool) -> &mut Self {
+        self.state_diffs = enabled.then(Vec::new);
+        self
+    }
+
+    /// Returns the state diffs recorded so far, [`None`] if recording is disabled.
+    pub const fn state_diffs(&self) -> Option<&BlockStateDiffs> {
+        self.state_diffs.as_ref()
+    }
+
+    /// Returns a mutable reference to the recorded state diffs, [`None`] if recording is
+    /// disabled.
+    pub fn state_diffs_mut(&mut self) -> Option<&mut BlockStateDiffs> {
+        self.state_diffs.as_mut()
+    }
+
+    /// Takes the state diffs recorded so far, [`None`] if recording is disabled.
+    pub fn take_state_diffs(&mut self) -> Option<BlockStateDiffs> {
+        self.state_diffs.as_mut().map(core::mem::take)
+    }
+
+    /// Records the diff of the given state changes if recording is enabled.
+    ///
+    /// Empty diffs are not recorded.
+    ///
+    /// This must be called before committing `changes` to `db`, see
+    /// [`state_diff`](crate::block::state_diff::state_diff).
+    pub fn record_state_diff<DB: Database>(
+        &mut self,
+        source: StateChangeSource,
+        db: &mut DB,
+        changes: &EvmState,
+    ) -> Result<(), BlockExecutionError> {
+        if let Some(diffs) = &mut self.state_diffs {
+            let diff = state_diff(db, changes).map_err(BlockExecutionError::other)?;
+            if !diff.is_empty() {
+                diffs.push((source, diff));
+            }
+        }
+        Ok(())
+    }
+
+    /// Records the diff of the given post-block balance increments if recording is enabled.
+    ///
+    /// This must be called before applying the increments to `db`.
+    pub fn record_balance_increments_diff<DB: Database>(
+        &mut self,
+        db: &mut DB,
+        balance_increments: &HashMap<Address, u128>,
+    ) -> Result<(), BlockExecutionError> {
+        if let Some(diffs) = &mut self.state_diffs {
+            let diff = balance_increments_diff(db, balance_increments)
+                .map_err(BlockExecutionError::other)?;
+            if !diff.is_empty() {
+                diffs.push((
+                    StateChangeSource::PostBlock(StateChangePostBlockSource::BalanceIncrements),
+                    diff,
+                ));
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<Spec> SystemCaller<Spec>
@@ -99,6 +168,11 @@ where
                     &res.state,
                 );
             }
+            self.record_state_diff(
+                StateChangeSource::PreBlock(StateChangePreBlockSource::BlockHashesContract),
+                evm.db_mut(),
+                &res.state,
+            )?;
             evm.db_mut().commit(res.state);
         }
 
@@ -121,6 +195,11 @@ where
                     &res.state,
                 );
             }
+            self.record_state_diff(
+                StateChangeSource::PreBlock(StateChangePreBlockSource::BeaconRootContract),
+                evm.db_mut(),
+                &res.state,
+            )?;
             evm.db_mut().commit(res.state);
         }
 
@@ -142,6 +221,11 @@ where
                 &result_and_state.state,
             );
         }
+        self.record_state_diff(
+            StateChangeSource::PostBlock(StateChangePostBlockSource::WithdrawalRequestsContract),
+            evm.db_mut(),
+            &result_and_state.state,
+        )?;
         evm.db_mut().commit(result_and_state.state);
 
         eip7002::post_commit(result_and_state.result)
@@ -162,6 +246,11 @@ where
                 &result_and_state.state,
             );
         }
+        self.record_state_diff(
+            StateChangeSource::PostBlock(StateChangePostBlockSource::ConsolidationRequestsContract),
+            evm.db_mut(),
+            &result_and_state.state,
+        )?;
         evm.db_mut().commit(result_and_state.state);
 
         eip7251::post_commit(result_and_state.result)
@@ -0,0 +1,323 @@
+//! Post-execution validation of a block against its header.
+
+use super::{BlockExecutionResult, BlockValidationError};
+use alloc::boxed::Box;
+use alloy_consensus::{proofs::calculate_receipt_root, BlockHeader, Transaction, TxReceipt};
+use alloy_eips::{eip7685::Requests, Encodable2718};
+use alloy_primitives::{Bloom, Log, B256};
+
+/// Calculates the receipts root of the given receipts.
+///
+/// Receipts are encoded with their [EIP-2718] envelope, including the logs bloom.
+///
+/// [EIP-2718]: https://eips.ethereum.org/EIPS/eip-2718
+pub fn calculate_receipts_root<R: Encodable2718>(receipts: &[R]) -> B256 {
+    calculate_receipt_root(receipts)
+}
+
+/// Calculates the logs bloom of the given receipts.
+pub fn calculate_logs_bloom<'a, R>(receipts: impl IntoIterator<Item = &'a R>) -> Bloom
+where
+    R: TxReceipt<Log = Log> + 'a,
+{
+    receipts.into_iter().fold(Bloom::ZERO, |bloom, receipt| bloom | receipt.bloom())
+}
+
+/// Calculates the [EIP-4844] blob gas used by the given transactions.
+///
+/// [EIP-4844]: https://eips.ethereum.org/EIPS/eip-4844
+pub fn calculate_blob_gas_used<T: Transaction>(transactions: impl IntoIterator<Item = T>) -> u64 {
+    transactions.into_iter().filter_map(|tx| tx.blob_gas_used()).sum()
+}
+
+/// Validates the outcome of a block execution against the header of the block.
+///
+/// This checks, in order, the gas used, receipts root, logs bloom, [EIP-7685] requests hash and
+/// [EIP-4844] blob gas used, see the individual validation functions of this module. The blob gas
+/// used is the one accumulated by the executor in [`BlockExecutionResult::blob_gas_used`].
+///
+/// [EIP-7685]: https://eips.ethereum.org/EIPS/eip-7685
+/// [EIP-4844]: https://eips.ethereum.org/EIPS/eip-4844
+pub fn validate_block_post_execution<H, R>(
+    header: &H,
+    result: &BlockExecutionResult<R>,
+) -> Result<(), BlockValidationError>
+where
+    H: BlockHeader,
+    R: TxReceipt<Log = Log> + Encodable2718,
+{
+    validate_gas_used(header, result.gas_used)?;
+    validate_receipts(header, &result.receipts)?;
+    validate_requests_hash(header, &result.requests)?;
+    validate_blob_gas_used(header, result.blob_gas_used)
+}
+
+/// Validates the gas used by the block against the header.
+pub fn validate_gas_used<H: BlockHeader>(
+    header: &H,
+    gas_used: u64,
+) -> Result<(), BlockValidationError> {
+    if gas_used != header.gas_used() {
+        return Err(BlockValidationError::BlockGasUsedMismatch {
+            got: gas_used,
+            expected: header.gas_used(),
+        });
+    }
+    Ok(())
+}
+
+/// Validates the receipts root and logs bloom of the given receipts against the header.
+pub fn validate_receipts<H, R>(header: &H, receipts: &[R]) -> Result<(), BlockValidationError>
+where
+    H: BlockHeader,
+    R: TxReceipt<Log = Log> + Encodable2718,
+{
+    let receipts_root = calculate_receipts_root(receipts);
+    if receipts_root != header.receipts_root() {
+        return Err(BlockValidationError::ReceiptsRootMismatch {
+            got: receipts_root,
+            expected: header.receipts_root(),
+        });
+    }
+
+    let logs_bloom = calculate_logs_bloom(receipts);
+    if logs_bloom != header.logs_bloom() {
+        return Err(BlockValidationError::LogsBloomMismatch {
+            got: Box::new(logs_bloom),
+            expected: Box::new(header.logs_bloom()),
+        });
+    }
+
+    Ok(())
+}
+
+/// Validates the [EIP-7685] requests hash against the header.
+///
+/// If the header has no requests hash, the block must not produce any requests.
+///
+/// [EIP-7685]: https://eips.ethereum.org/EIPS/eip-7685
+pub fn validate_requests_hash<H: BlockHeader>(
+    header: &H,
+    requests: &Requests,
+) -> Result<(), BlockValidationError> {
+    let expected = header.requests_hash();
+    let got = (expected.is_some() || !requests.is_empty()).then(|| requests.requests_hash());
+    if got != expected {
+        return Err(BlockValidationError::RequestsHashMismatch { got, expected });
+    }
+    Ok(())
+}
+
+/// Validates the [EIP-4844] blob gas used by the block against the header.
+///
+/// Headers without blob gas used are treated as using no blob gas.
+///
+/// [EIP-4844]: https://eips.ethereum.org/EIPS/eip-4844
+pub fn validate_blob_gas_used<H: BlockHeader>(
+    header: &H,
+    blob_gas_used: u64,
+) -> Result<(), BlockValidationError> {
+    let expected = header.blob_gas_used().unwrap_or_default();
+    if blob_gas_used != expected {
+        return Err(BlockValidationError::BlobGasUsedMismatch { got: blob_gas_used, expected });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloy_consensus::{Header, Receipt, ReceiptEnvelope, TxEip4844};
+    use alloy_eips::eip4844::DATA_GAS_PER_BLOB;
+    use alloy_primitives::{address, b256, Bytes, LogData, B64, U256};
+
+    fn result() -> BlockExecutionResult<ReceiptEnvelope> {
+        let log = Log {
+            address: address!("0x000000000000000000000000000000000000c0de"),
+            data: LogData::new_unchecked(vec![B256::repeat_byte(0x11)], Bytes::new()),
+        };
+        let receipts = vec![
+            ReceiptEnvelope::Legacy(
+                Receipt { status: true.into(), cumulative_gas_used: 21_000, logs: vec![] }
+                    .with_bloom(),
+            ),
+            ReceiptEnvelope::Eip1559(
+                Receipt { status: true.into(), cumulative_gas_used: 50_000, logs: vec![log] }
+                    .with_bloom(),
+            ),
+        ];
+        BlockExecutionResult {
+            receipts,
+            requests: Requests::default(),
+            gas_used: 50_000,
+            blob_gas_used: 0,
+            state_diffs: None,
+        }
+    }
+
+    /// Hoodi block 411443, a post-Prague block with a single legacy transfer and no requests.
+    ///
+    /// Returns the header of the block and the outcome of its execution.
+    fn hoodi_block() -> (Header, BlockExecutionResult<ReceiptEnvelope>) {
+        let header = Header {
+            parent_hash: b256!(
+                "0x84eba4ac122adba9bbe79b78ccc538ec5fd7b612cd6c2cd6d4ac3a23160f6151"
+            ),
+            ommers_hash: b256!(
+                "0x1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347"
+            ),
+            beneficiary: address!("0x25941dc771bb64514fc8abbce970307fb9d477e9"),
+            state_root: b256!("0x7347d30e42da2799eb5b51d8e1a81756323afd47d68e9c7f7fe5c6cfd38572bd"),
+            transactions_root: b256!(
+                "0x7cbc552113ed936ee351981d5151a8913cc7cc2ac55d930d6a43ded6e721c21b"
+            ),
+            receipts_root: b256!(
+                "0x056b23fbba480696b65fe5a59b8f2148a1299103c4f57df839233af2cf4ca2d2"
+            ),
+            logs_bloom: Bloom::ZERO,
+            difficulty: U256::ZERO,
+            number: 0x64733,
+            gas_limit: 0x2255100,
+            gas_used: 0x5208,
+            timestamp: 0x68285874,
+            extra_data: Bytes::from_static(b"Nethermind"),
+            mix_hash: b256!("0x5aa29a261f252912f12377c312d68a616af8efef7a9f8c8911b7482bcf4a3adc"),
+            nonce: B64::ZERO,
+            base_fee_per_gas: Some(0x4227fedf),
+            withdrawals_root: Some(b256!(
+                "0x9a0aedb6a7b38b44467d87dd8c08b64589fcf729a0f60e9361ecb160f074b08c"
+            )),
+            blob_gas_used: Some(0),
+            excess_blob_gas: Some(0),
+            parent_beacon_block_root: Some(b256!(
+                "0x065c517950023785bf51c075203764504b5fa9b65b8fe3943aa9fb8a86e0391d"
+            )),
+            requests_hash: Some(b256!(
+                "0xe3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+            )),
+        };
+        let receipts = vec![ReceiptEnvelope::Legacy(
+            Receipt { status: true.into(), cumulative_gas_used: 21_000, logs: vec![] }.with_bloom(),
+        )];
+        let result = BlockExecutionResult {
+            receipts,
+            requests: Requests::default(),
+            gas_used: 21_000,
+            blob_gas_used: 0,
+            state_diffs: None,
+        };
+        (header, result)
+    }
+
+    fn header(result: &BlockExecutionResult<ReceiptEnvelope>) -> Header {
+        Header {
+            gas_used: result.gas_used,
+            receipts_root: calculate_receipts_root(&result.receipts),
+            logs_bloom: calculate_logs_bloom(&result.receipts),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn computes_header_fields() {
+        let (header, result) = hoodi_block();
+        // the header is the one of the real block
+        assert_eq!(
+            header.hash_slow(),
+            b256!("0x5e98e8e4d80928867e03eb2224f66fc8c68f687de3a5550119c365fca7abb118")
+        );
+        assert_eq!(result.receipts_root(), header.receipts_root);
+        assert_eq!(result.logs_bloom(), header.logs_bloom);
+        assert_eq!(Some(result.requests_hash()), header.requests_hash);
+        validate_block_post_execution(&header, &result).unwrap();
+
+        // mainnet block 1, without transactions
+        let result = BlockExecutionResult::<ReceiptEnvelope>::default();
+        assert_eq!(
+            result.receipts_root(),
+            b256!("0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421")
+        );
+        assert_eq!(result.logs_bloom(), Bloom::ZERO);
+
+        // post-Prague mainnet blocks without requests
+        assert_eq!(
+            result.requests_hash(),
+            b256!("0xe3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+        );
+    }
+
+    #[test]
+    fn validates_matching_header() {
+        let result = result();
+        let header = header(&result);
+        assert!(!header.logs_bloom.is_zero());
+
+        validate_block_post_execution(&header, &result).unwrap();
+    }
+
+    #[test]
+    fn rejects_mismatches() {
+        let result = result();
+
+        let header = Header { gas_used: 1, ..header(&result) };
+        assert!(matches!(
+            validate_block_post_execution(&header, &result),
+            Err(BlockValidationError::BlockGasUsedMismatch { got: 50_000, expected: 1 })
+        ));
+
+        let header = Header { receipts_root: B256::ZERO, ..header(&result) };
+        assert!(matches!(
+            validate_block_post_execution(&header, &result),
+            Err(BlockValidationError::ReceiptsRootMismatch { expected, .. }) if expected.is_zero()
+        ));
+
+        let header = Header { logs_bloom: Bloom::ZERO, ..header(&result) };
+        assert!(matches!(
+            validate_block_post_execution(&header, &result),
+            Err(BlockValidationError::LogsBloomMismatch { .. })
+        ));
+
+        let header = Header { requests_hash: Some(B256::ZERO), ..header(&result) };
+        assert!(matches!(
+            validate_block_post_execution(&header, &result),
+            Err(BlockValidationError::RequestsHashMismatch { got: Some(_), expected: Some(_) })
+        ));
+
+        let header = Header { blob_gas_used: Some(DATA_GAS_PER_BLOB), ..header(&result) };
+        assert!(matches!(
+            validate_block_post_execution(&header, &result),
+            Err(BlockValidationError::BlobGasUsedMismatch { got: 0, expected: DATA_GAS_PER_BLOB })
+        ));
+    }
+
+    #[test]
+    fn validates_requests_hash() {
+        let requests = Requests::default();
+        let header = Header { requests_hash: Some(requests.requests_hash()), ..Default::default() };
+        validate_requests_hash(&header, &requests).unwrap();
+        validate_requests_hash(&Header::default(), &requests).unwrap();
+
+        let requests = Requests::new(vec![Bytes::from_static(&[0x00, 0x01])]);
+        assert!(matches!(
+            validate_requests_hash(&Header::default(), &requests),
+            Err(BlockValidationError::RequestsHashMismatch { got: Some(_), expected: None })
+        ));
+    }
+
+    #[test]
+    fn validates_blob_gas_used() {
+        let transactions = [TxEip4844 {
+            blob_versioned_hashes: vec![B256::ZERO, B256::ZERO],
+            ..Default::default()
+        }];
+        assert_eq!(calculate_blob_gas_used(&transactions), 2 * DATA_GAS_PER_BLOB);
+
+        let header = Header { blob_gas_used: Some(2 * DATA_GAS_PER_BLOB), ..Default::default() };
+        validate_blob_gas_used(&header, calculate_blob_gas_used(&transactions)).unwrap();
+        assert!(matches!(
+            validate_blob_gas_used(&Header::default(), calculate_blob_gas_used(&transactions)),
+            Err(BlockValidationError::BlobGasUsedMismatch { expected: 0, .. })
+        ));
+    }
+}
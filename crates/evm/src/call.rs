@@ -1,7 +1,19 @@
 //! Utilities for dealing with eth_call and adjacent RPC endpoints.
 
-use alloy_primitives::U256;
-use revm::Database;
+use crate::{Evm, EvmError, TransactionEnv};
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec::Vec,
+};
+use alloy_eips::eip2930::{AccessList, AccessListItem};
+use alloy_primitives::{Address, Bytes, TxKind, B256, U256};
+use revm::{
+    context_interface::result::{
+        ExecutionResult, HaltReason, HaltReasonTr, OutOfGasError, ResultAndState,
+    },
+    state::EvmState,
+    Database,
+};
 
 /// Insufficient funds error
 #[derive(Debug, thiserror::Error)]
@@ -56,3 +68,368 @@ where
         .unwrap_or_default()
         .saturating_to())
 }
+
+/// Acceptable relative error of [`estimate_gas`], in thousandths.
+///
+/// The binary search stops once the gap between the lowest failing and the highest succeeding gas
+/// limit is below this fraction of the latter.
+pub const ESTIMATE_GAS_ERROR_RATIO_PER_MILLE: u64 = 15;
+
+/// Gas stipend forwarded with value transfers, accounted for in the optimistic estimate.
+const CALL_STIPEND: u64 = 2300;
+
+/// Result of a successful [`estimate_gas`] call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GasEstimate {
+    /// Estimated gas limit the transaction succeeds with.
+    pub gas_limit: u64,
+    /// Gas used by the transaction when executed with the upper bound of the search.
+    pub gas_used: u64,
+    /// Number of times the transaction was executed.
+    pub iterations: u64,
+}
+
+/// Error returned by [`estimate_gas`].
+#[derive(Debug, thiserror::Error)]
+pub enum EstimateGasError<DbError, EvmError, HaltReason> {
+    /// Failed to compute the caller gas allowance.
+    #[error(transparent)]
+    Call(#[from] CallError<DbError>),
+    /// The transaction couldn't be executed.
+    #[error(transparent)]
+    Evm(EvmError),
+    /// The transaction reverted even with the highest possible gas limit.
+    #[error("execution reverted")]
+    Reverted {
+        /// Revert output.
+        output: Bytes,
+        /// Gas used by the reverted execution.
+        gas_used: u64,
+    },
+    /// The transaction halted for a reason other than running out of gas.
+    #[error("execution halted: {reason:?}")]
+    Halted {
+        /// Halt reason.
+        reason: HaltReason,
+        /// Gas used by the halted execution.
+        gas_used: u64,
+    },
+    /// The transaction ran out of gas even with the highest possible gas limit.
+    #[error("gas required exceeds allowance ({gas_limit})")]
+    OutOfGas {
+        /// The highest gas limit the transaction was executed with.
+        gas_limit: u64,
+    },
+}
+
+/// [`EstimateGasError`] for the given [`Evm`].
+pub type EstimateGasErrorFor<E> = EstimateGasError<
+    <<E as Evm>::DB as Database>::Error,
+    <E as Evm>::Error,
+    <E as Evm>::HaltReason,
+>;
+
+/// Estimates the gas limit required by the transaction, like `eth_estimateGas`.
+///
+/// The search is bounded by the block gas limit, the gas limit of the transaction and, if the
+/// transaction has a non-zero gas price, the [caller gas allowance](caller_gas_allowance).
+///
+/// The transaction is first executed with the upper bound. If it succeeds, an optimistic limit
+/// derived from the used gas is tried, which accounts for the 63/64 of gas forwarded to
+/// subcalls. Remaining uncertainty is resolved with a binary search that stops once the result
+/// is within [`ESTIMATE_GAS_ERROR_RATIO_PER_MILLE`] of the lowest working limit.
+///
+/// State changes are never committed, the caller decides how the EVM is configured, e.g. whether
+/// nonce or base fee checks are disabled.
+pub fn estimate_gas<E>(evm: &mut E, tx: E::Tx) -> Result<GasEstimate, EstimateGasErrorFor<E>>
+where
+    E: Evm<DB: Database>,
+    E::Tx: TransactionEnv,
+{
+    let mut highest = evm.block().gas_limit;
+    if tx.gas_limit() != 0 {
+        highest = highest.min(tx.gas_limit());
+    }
+    if tx.gas_price() > 0 {
+        highest = highest.min(caller_gas_allowance(evm.db_mut(), &tx)?);
+    }
+
+    let mut iterations = 1;
+    let (gas_used, gas_refunded) = match transact_with_gas_limit(evm, &tx, highest)? {
+        ExecutionResult::Success { gas_used, gas_refunded, .. } => (gas_used, gas_refunded),
+        ExecutionResult::Revert { output, gas_used } => {
+            return Err(EstimateGasError::Reverted { output, gas_used })
+        }
+        ExecutionResult::Halt { reason, gas_used } => {
+            return Err(if is_out_of_gas(&reason) {
+                EstimateGasError::OutOfGas { gas_limit: highest }
+            } else {
+                EstimateGasError::Halted { reason, gas_used }
+            })
+        }
+    };
+
+    // The transaction can't succeed with less gas than it used.
+    let mut lowest = gas_used.saturating_sub(1);
+
+    // Subcalls only receive 63/64 of the available gas, so the gas used is usually not enough
+    // for the transaction to succeed. Try a limit accounting for that before searching.
+    let optimistic =
+        gas_used.saturating_add(gas_refunded).saturating_add(CALL_STIPEND).saturating_mul(64) / 63;
+    if optimistic < highest {
+        iterations += 1;
+        if succeeds_with_gas_limit(evm, &tx, optimistic)? {
+            highest = optimistic;
+        } else {
+            lowest = optimistic;
+        }
+    }
+
+    // Start close to the lower end, most transactions don't need much more than they used.
+    let mut mid = (highest + lowest) / 2;
+    mid = mid.min(gas_used.saturating_mul(3));
+    while lowest + 1 < highest {
+        if u128::from(highest - lowest) * 1000
+            < u128::from(highest) * u128::from(ESTIMATE_GAS_ERROR_RATIO_PER_MILLE)
+        {
+            break;
+        }
+
+        iterations += 1;
+        if succeeds_with_gas_limit(evm, &tx, mid)? {
+            highest = mid;
+        } else {
+            lowest = mid;
+        }
+        mid = (highest + lowest) / 2;
+    }
+
+    Ok(GasEstimate { gas_limit: highest, gas_used, iterations })
+}
+
+/// Executes the transaction with the given gas limit without committing.
+fn transact_with_gas_limit<E>(
+    evm: &mut E,
+    tx: &E::Tx,
+    gas_limit: u64,
+) -> Result<ExecutionResult<E::HaltReason>, EstimateGasErrorFor<E>>
+where
+    E: Evm<DB: Database>,
+    E::Tx: TransactionEnv,
+{
+    evm.transact_raw(tx.clone().with_gas_limit(gas_limit))
+        .map(|result| result.result)
+        .map_err(EstimateGasError::Evm)
+}
+
+/// Returns whether the transaction succeeds with the given gas limit.
+///
+/// Only used for limits below one the transaction is known to succeed with, so any failure,
+/// including validation errors like an intrinsic gas above the limit, means that the limit is too
+/// low.
+fn succeeds_with_gas_limit<E>(
+    evm: &mut E,
+    tx: &E::Tx,
+    gas_limit: u64,
+) -> Result<bool, EstimateGasErrorFor<E>>
+where
+    E: Evm<DB: Database>,
+    E::Tx: TransactionEnv,
+{
+    match transact_with_gas_limit(evm, tx, gas_limit) {
+        Ok(result) => Ok(result.is_success()),
+        Err(EstimateGasError::Evm(err)) if err.is_invalid_tx_err() => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// Returns whether the halt reason is any kind of out of gas error.
+fn is_out_of_gas<H: HaltReasonTr>(reason: &H) -> bool {
+    [
+        OutOfGasError::Basic,
+        OutOfGasError::MemoryLimit,
+        OutOfGasError::Memory,
+        OutOfGasError::Precompile,
+        OutOfGasError::InvalidOperand,
+        OutOfGasError::ReentrancySentry,
+    ]
+    .into_iter()
+    .any(|err| *reason == H::from(HaltReason::OutOfGas(err)))
+}
+
+/// Maximum number of executions performed by [`create_access_list`].
+pub const CREATE_ACCESS_LIST_MAX_ITERATIONS: usize = 16;
+
+/// Result of a successful [`create_access_list`] call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessListResult<H> {
+    /// The generated access list.
+    pub access_list: AccessList,
+    /// Gas used by the transaction when executed with the generated access list.
+    pub gas_used: u64,
+    /// Result of the execution with the generated access list.
+    pub result: ExecutionResult<H>,
+}
+
+/// Error returned by [`create_access_list`].
+#[derive(Debug, thiserror::Error)]
+pub enum AccessListError<E> {
+    /// The transaction couldn't be executed.
+    #[error(transparent)]
+    Evm(E),
+    /// The accessed state kept changing with the access list, e.g. because storage slots are
+    /// derived from the remaining gas.
+    #[error("access list didn't stabilize after {0} iterations")]
+    NotStable(usize),
+}
+
+/// Generates an access list for the transaction, like `eth_createAccessList`.
+///
+/// The transaction is executed with the access list generated from the previous execution until
+/// it accesses the same state twice in a row, which accounts for state accessed only with the
+/// lower gas costs of an access list. The access list of `tx` is replaced.
+///
+/// The sender, the recipient, the block beneficiary and `precompiles` are only included if their
+/// storage is accessed, since they are always warm. Precompile addresses can usually be obtained
+/// from the [`Evm::precompiles`], e.g. with [`PrecompilesMap::addresses`].
+///
+/// [`PrecompilesMap::addresses`]: crate::precompiles::PrecompilesMap::addresses
+pub fn create_access_list<E>(
+    evm: &mut E,
+    mut tx: E::Tx,
+    precompiles: impl IntoIterator<Item = Address>,
+) -> Result<AccessListResult<E::HaltReason>, AccessListError<E::Error>>
+where
+    E: Evm,
+    E::Tx: TransactionEnv,
+{
+    let recipient = match tx.kind() {
+        TxKind::Call(to) => to,
+        TxKind::Create => tx.caller().create(tx.nonce()),
+    };
+    let excluded: BTreeSet<Address> =
+        [tx.caller(), recipient, evm.block().beneficiary].into_iter().chain(precompiles).collect();
+
+    let mut access_list = AccessList::default();
+    for _ in 0..CREATE_ACCESS_LIST_MAX_ITERATIONS {
+        tx.set_access_list(access_list.clone());
+        let ResultAndState { result, state } =
+            evm.transact_raw(tx.clone()).map_err(AccessListError::Evm)?;
+
+        let accessed = access_list_from_state(&state, &excluded);
+        if accessed == access_list {
+            return Ok(AccessListResult { access_list, gas_used: result.gas_used(), result });
+        }
+        access_list = accessed;
+    }
+
+    Err(AccessListError::NotStable(CREATE_ACCESS_LIST_MAX_ITERATIONS))
+}
+
+/// Builds a sorted access list from all accounts and storage slots loaded during execution.
+fn access_list_from_state(state: &EvmState, excluded: &BTreeSet<Address>) -> AccessList {
+    let mut accessed = BTreeMap::<Address, BTreeSet<B256>>::new();
+    for (address, account) in state {
+        let slots: BTreeSet<_> = account.storage.keys().map(|slot| B256::from(*slot)).collect();
+        if !slots.is_empty() || !excluded.contains(address) {
+            accessed.insert(*address, slots);
+        }
+    }
+
+    accessed
+        .into_iter()
+        .map(|(address, slots)| AccessListItem {
+            address,
+            storage_keys: slots.into_iter().collect(),
+        })
+        .collect::<Vec<_>>()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{precompiles::PrecompilesMap, EthEvm, EthEvmFactory, EvmEnv, EvmFactory};
+    use alloy_primitives::{address, bytes};
+    use revm::{
+        context::{BlockEnv, CfgEnv, TxEnv},
+        database::{CacheDB, EmptyDB},
+        inspector::NoOpInspector,
+        state::{AccountInfo, Bytecode},
+    };
+
+    const CALLER: Address = address!("0x000000000000000000000000000000000000a11c");
+    const CONTRACT: Address = address!("0x000000000000000000000000000000000000c0de");
+
+    fn setup(code: Bytes) -> (EthEvm<CacheDB<EmptyDB>, NoOpInspector, PrecompilesMap>, TxEnv) {
+        let mut db = CacheDB::new(EmptyDB::new());
+        let code = Bytecode::new_raw(code);
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo { code_hash: code.hash_slow(), code: Some(code), ..Default::default() },
+        );
+        let block_env = BlockEnv { gas_limit: 30_000_000, ..Default::default() };
+        let evm =
+            EthEvmFactory::default().create_evm(db, EvmEnv::new(CfgEnv::default(), block_env));
+
+        (evm, TxEnv { caller: CALLER, kind: TxKind::Call(CONTRACT), ..Default::default() })
+    }
+
+    #[test]
+    fn estimates_gas_checking_contract() {
+        // Reverts unless more than 30000 gas is left: GAS PUSH2 30000 LT PUSH1 12 JUMPI PUSH1 0
+        // DUP1 REVERT JUMPDEST STOP
+        let (mut evm, tx) = setup(bytes!("0x5a61753010600c57600080fd5b00"));
+        let estimate = estimate_gas(&mut evm, tx.clone()).unwrap();
+
+        assert!(estimate.gas_limit > 51_000);
+        assert!(estimate.gas_used < 22_000);
+        assert!(estimate.iterations > 2);
+
+        // The estimate works and is within the error ratio of the lowest working limit.
+        let result = evm.transact(tx.clone().with_gas_limit(estimate.gas_limit)).unwrap();
+        assert!(result.result.is_success());
+        let result = evm.transact(tx.with_gas_limit(estimate.gas_limit * 98 / 100)).unwrap();
+        assert!(!result.result.is_success());
+    }
+
+    #[test]
+    fn reports_revert() {
+        // PUSH1 0 DUP1 REVERT
+        let (mut evm, tx) = setup(bytes!("0x600080fd"));
+        let err = estimate_gas(&mut evm, tx).unwrap_err();
+        assert!(matches!(err, EstimateGasError::Reverted { .. }));
+    }
+
+    #[test]
+    fn reports_out_of_gas() {
+        // JUMPDEST PUSH1 0 JUMP
+        let (mut evm, tx) = setup(bytes!("0x5b600056"));
+        let err = estimate_gas(&mut evm, tx).unwrap_err();
+        assert!(matches!(err, EstimateGasError::OutOfGas { gas_limit: 30_000_000 }));
+    }
+
+    #[test]
+    fn creates_access_list() {
+        // PUSH1 1 SLOAD POP PUSH20 0xbeef BALANCE POP STOP
+        let (mut evm, tx) =
+            setup(bytes!("0x600154507300000000000000000000000000000000000000000000beef315000"));
+        let precompiles = (1..=10).map(Address::with_last_byte);
+        let result = create_access_list(&mut evm, tx.clone(), precompiles).unwrap();
+
+        let expected = AccessList::from(vec![
+            AccessListItem {
+                address: address!("0x000000000000000000000000000000000000beef"),
+                storage_keys: vec![],
+            },
+            AccessListItem { address: CONTRACT, storage_keys: vec![B256::with_last_byte(1)] },
+        ]);
+        assert_eq!(result.access_list, expected);
+        assert!(result.result.is_success());
+
+        let mut tx = tx;
+        tx.set_access_list(expected);
+        let gas_used = evm.transact(tx).unwrap().result.gas_used();
+        assert_eq!(result.gas_used, gas_used);
+    }
+}
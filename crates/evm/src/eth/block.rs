@@ -9,21 +9,20 @@ use super::{
 use crate::{
     block::{
         state_changes::{balance_increment_state, post_block_balance_increments},
-        BlockExecutionError, BlockExecutionResult, BlockExecutor, BlockExecutorFactory,
-        BlockExecutorFor, BlockValidationError, CommitChanges, ExecutableTx, OnStateHook,
-        StateChangePostBlockSource, StateChangeSource, SystemCaller,
+        BlockExecutionError, BlockExecutionResult, BlockExecutor, BlockExecutorCheckpoint,
+        BlockExecutorFactory, BlockExecutorFor, BlockValidationError, ExecutableTx, OnStateHook,
+        StateChangePostBlockSource, StateChangeSource, StateCheckpoints, SystemCaller,
     },
     Database, Evm, EvmFactory, FromRecoveredTx, FromTxWithEncoded,
 };
 use alloc::{borrow::Cow, boxed::Box, vec::Vec};
 use alloy_consensus::{Header, Transaction, TxReceipt};
-use alloy_eips::{eip4895::Withdrawals, eip7685::Requests, Encodable2718};
+use alloy_eips::{
+    eip4844::DATA_GAS_PER_BLOB, eip4895::Withdrawals, eip7685::Requests, Encodable2718,
+};
 use alloy_hardforks::EthereumHardfork;
 use alloy_primitives::{Log, B256};
-use revm::{
-    context::result::ExecutionResult, context_interface::result::ResultAndState, database::State,
-    DatabaseCommit, Inspector,
-};
+use revm::{context_interface::result::ResultAndState, database::State, DatabaseCommit, Inspector};
 
 /// Context for Ethereum block execution.
 #[derive(Debug, Clone)]
@@ -57,6 +56,11 @@ pub struct EthBlockExecutor<'a, Evm, Spec, R: ReceiptBuilder> {
     receipts: Vec<R::Receipt>,
     /// Total gas used by transactions in this block.
     gas_used: u64,
+    /// Total blob gas used by transactions in this block.
+    blob_gas_used: u64,
+    /// Active checkpoints, holding the number of receipts, gas used and blob gas used at each
+    /// checkpoint.
+    checkpoints: StateCheckpoints<(usize, u64, u64)>,
 }
 
 impl<'a, Evm, Spec, R> EthBlockExecutor<'a, Evm, Spec, R>
@@ -71,6 +75,8 @@ where
             ctx,
             receipts: Vec::new(),
             gas_used: 0,
+            blob_gas_used: 0,
+            checkpoints: StateCheckpoints::default(),
             system_caller: SystemCaller::new(spec.clone()),
             spec,
             receipt_builder,
@@ -78,6 +84,47 @@ where
     }
 }
 
+impl<E, Spec, R> EthBlockExecutor<'_, E, Spec, R>
+where
+    E: Evm,
+    Spec: EthExecutorSpec,
+    R: ReceiptBuilder,
+{
+    /// Validates the gas limit and the blob gas of the given transaction against the gas left in
+    /// the block.
+    fn validate_transaction(&self, tx: &impl Transaction) -> Result<(), BlockValidationError> {
+        // The sum of the transaction's gas limit, Tg, and the gas utilized in this block prior,
+        // must be no greater than the block's gasLimit.
+        let block_available_gas = self.evm.block().gas_limit - self.gas_used;
+        if tx.gas_limit() > block_available_gas {
+            return Err(BlockValidationError::TransactionGasLimitMoreThanAvailableBlockGas {
+                transaction_gas_limit: tx.gas_limit(),
+                block_available_gas,
+            });
+        }
+
+        let Some(transaction_blob_gas_used) = tx.blob_gas_used() else { return Ok(()) };
+        let timestamp = self.evm.block().timestamp.saturating_to();
+        let Some(blob_params) = self.spec.blob_params_at_timestamp(timestamp) else {
+            // blob transactions before Cancun are rejected by the EVM
+            if !self.spec.is_cancun_active_at_timestamp(timestamp) {
+                return Ok(());
+            }
+            return Err(BlockValidationError::MissingBlobParams { timestamp });
+        };
+
+        let block_available_blob_gas =
+            (blob_params.max_blob_count * DATA_GAS_PER_BLOB).saturating_sub(self.blob_gas_used);
+        if transaction_blob_gas_used > block_available_blob_gas {
+            return Err(BlockValidationError::TransactionBlobGasMoreThanAvailableBlockBlobGas {
+                transaction_blob_gas_used,
+                block_available_blob_gas,
+            });
+        }
+        Ok(())
+    }
+}
+
 impl<'db, DB, E, Spec, R> BlockExecutor for EthBlockExecutor<'_, E, Spec, R>
 where
     DB: Database + 'db,
@@ -105,53 +152,84 @@ where
         Ok(())
     }
 
-    fn execute_transaction_with_commit_condition(
+    fn execute_transaction_without_commit(
         &mut self,
         tx: impl ExecutableTx<Self>,
-        f: impl FnOnce(&ExecutionResult<<Self::Evm as Evm>::HaltReason>) -> CommitChanges,
-    ) -> Result<Option<u64>, BlockExecutionError> {
-        // The sum of the transaction's gas limit, Tg, and the gas utilized in this block prior,
-        // must be no greater than the block's gasLimit.
-        let block_available_gas = self.evm.block().gas_limit - self.gas_used;
-
-        if tx.tx().gas_limit() > block_available_gas {
-            return Err(BlockValidationError::TransactionGasLimitMoreThanAvailableBlockGas {
-                transaction_gas_limit: tx.tx().gas_limit(),
-                block_available_gas,
-            }
-            .into());
-        }
+    ) -> Result<ResultAndState<<Self::Evm as Evm>::HaltReason>, BlockExecutionError> {
+        self.validate_transaction(tx.tx())?;
 
         // Execute transaction.
-        let ResultAndState { result, state } = self
-            .evm
-            .transact(&tx)
-            .map_err(|err| BlockExecutionError::evm(err, tx.tx().trie_hash()))?;
+        self.evm.transact(&tx).map_err(|err| BlockExecutionError::evm(err, tx.tx().trie_hash()))
+    }
 
-        if !f(&result).should_commit() {
-            return Ok(None);
-        }
+    fn commit_transaction(
+        &mut self,
+        output: ResultAndState<<Self::Evm as Evm>::HaltReason>,
+        tx: impl ExecutableTx<Self>,
+    ) -> Result<u64, BlockExecutionError> {
+        // The output might not come from `execute_transaction_without_commit`, e.g. when executing
+        // transactions in parallel, so the transaction is validated again.
+        self.validate_transaction(tx.tx())?;
 
-        self.system_caller.on_state(StateChangeSource::Transaction(self.receipts.len()), &state);
+        let ResultAndState { result, state } = output;
 
         let gas_used = result.gas_used();
+        let cumulative_gas_used = self.gas_used + gas_used;
 
-        // append gas used
-        self.gas_used += gas_used;
-
-        // Push transaction changeset and calculate header bloom filter for receipt.
-        self.receipts.push(self.receipt_builder.build_receipt(ReceiptBuilderCtx {
+        // Build the receipt before touching the executor, so that a failure leaves it unchanged.
+        let receipt = self.receipt_builder.build_receipt(ReceiptBuilderCtx {
             tx: tx.tx(),
             evm: &self.evm,
             result,
             state: &state,
-            cumulative_gas_used: self.gas_used,
-        }));
+            cumulative_gas_used,
+        })?;
+
+        let source = StateChangeSource::Transaction(self.receipts.len());
+        self.system_caller.record_state_diff(source, self.evm.db_mut(), &state)?;
+        self.system_caller.on_state(source, &state);
+
+        // append gas used
+        self.gas_used = cumulative_gas_used;
+        self.blob_gas_used += tx.tx().blob_gas_used().unwrap_or_default();
+
+        // Push transaction changeset and calculate header bloom filter for receipt.
+        self.receipts.push(receipt);
 
         // Commit the state changes.
+        self.checkpoints.record(self.evm.db(), &state);
         self.evm.db_mut().commit(state);
 
-        Ok(Some(gas_used))
+        Ok(gas_used)
+    }
+
+    fn checkpoint(&mut self) -> Result<BlockExecutorCheckpoint, BlockExecutionError> {
+        let data = (self.receipts.len(), self.gas_used, self.blob_gas_used);
+        Ok(self.checkpoints.checkpoint(data))
+    }
+
+    fn checkpoint_commit(
+        &mut self,
+        checkpoint: BlockExecutorCheckpoint,
+    ) -> Result<(), BlockExecutionError> {
+        self.checkpoints.commit(checkpoint)
+    }
+
+    fn checkpoint_revert(
+        &mut self,
+        checkpoint: BlockExecutorCheckpoint,
+    ) -> Result<(), BlockExecutionError> {
+        let (receipts, gas_used, blob_gas_used) =
+            self.checkpoints.revert(self.evm.db_mut(), checkpoint)?;
+        self.receipts.truncate(receipts);
+        self.gas_used = gas_used;
+        self.blob_gas_used = blob_gas_used;
+        if let Some(diffs) = self.system_caller.state_diffs_mut() {
+            diffs.retain(|(source, _)| {
+                !matches!(source, StateChangeSource::Transaction(index) if *index >= receipts)
+            });
+        }
+        Ok(())
     }
 
     fn finish(
@@ -203,6 +281,9 @@ where
             *balance_increments.entry(dao_fork::DAO_HARDFORK_BENEFICIARY).or_default() +=
                 drained_balance;
         }
+        self.system_caller
+            .record_balance_increments_diff(self.evm.db_mut(), &balance_increments)?;
+
         // increment balances
         self.evm
             .db_mut()
@@ -221,7 +302,13 @@ where
 
         Ok((
             self.evm,
-            BlockExecutionResult { receipts: self.receipts, requests, gas_used: self.gas_used },
+            BlockExecutionResult {
+                receipts: self.receipts,
+                requests,
+                gas_used: self.gas_used,
+                blob_gas_used: self.blob_gas_used,
+                state_diffs: self.system_caller.take_state_diffs(),
+            },
         ))
     }
 
@@ -229,6 +316,10 @@ where
         self.system_caller.with_state_hook(hook);
     }
 
+    fn set_state_diff_recording(&mut self, enabled: bool) {
+        self.system_caller.with_state_diff_recording(enabled);
+    }
+
     fn evm_mut(&mut self) -> &mut Self::Evm {
         &mut self.evm
     }
@@ -304,3 +395,171 @@ where
         EthBlockExecutor::new(evm, ctx, &self.spec, &self.receipt_builder)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_utils::{factory, state, EXECUTION_CTX, SENDER},
+        EvmEnv,
+    };
+    use alloy_consensus::{
+        transaction::Recovered, Signed, TxEip4844, TxEip4844Variant, TxEnvelope,
+    };
+    use alloy_hardforks::{EthereumHardfork, EthereumHardforks, ForkCondition};
+    use alloy_primitives::{address, b256, Address, Signature, U256};
+    use revm::{
+        context::{BlockEnv, CfgEnv},
+        database::{CacheDB, EmptyDB},
+        state::AccountInfo,
+    };
+
+    fn blob_tx(nonce: u64, blobs: usize) -> Recovered<TxEnvelope> {
+        let tx = TxEip4844 {
+            nonce,
+            gas_limit: 100_000,
+            to: address!("0x000000000000000000000000000000000000beef"),
+            max_fee_per_blob_gas: 1,
+            blob_versioned_hashes: vec![
+                b256!(
+                    "0x0100000000000000000000000000000000000000000000000000000000000000"
+                );
+                blobs
+            ],
+            ..Default::default()
+        };
+        let tx = Signed::new_unchecked(
+            TxEip4844Variant::TxEip4844(tx),
+            Signature::test_signature(),
+            B256::ZERO,
+        );
+        Recovered::new_unchecked(TxEnvelope::Eip4844(tx), SENDER)
+    }
+
+    type Factory<Spec> = EthBlockExecutorFactory<AlloyReceiptBuilder, Spec>;
+
+    fn executor_at<'a, Spec: EthExecutorSpec + 'static>(
+        factory: &'a Factory<Spec>,
+        state: &'a mut State<CacheDB<EmptyDB>>,
+        fork: EthereumHardfork,
+    ) -> impl BlockExecutorFor<'a, Factory<Spec>, CacheDB<EmptyDB>> {
+        let timestamp = fork.mainnet_activation_timestamp().unwrap();
+        let evm_env = EvmEnv::new(
+            CfgEnv::default(),
+            BlockEnv { timestamp: U256::from(timestamp), ..Default::default() },
+        );
+        let evm = factory.evm_factory().create_evm(state, evm_env);
+        factory.create_executor(evm, EXECUTION_CTX)
+    }
+
+    /// Mainnet with the BPO3 blob parameter only fork activated along with BPO2.
+    #[derive(Debug, Clone)]
+    struct Bpo3Spec(EthSpec);
+
+    impl EthereumHardforks for Bpo3Spec {
+        fn ethereum_fork_activation(&self, fork: EthereumHardfork) -> ForkCondition {
+            match fork {
+                EthereumHardfork::Bpo3 => self.0.ethereum_fork_activation(EthereumHardfork::Bpo2),
+                fork => self.0.ethereum_fork_activation(fork),
+            }
+        }
+    }
+
+    impl EthExecutorSpec for Bpo3Spec {
+        fn deposit_contract_address(&self) -> Option<Address> {
+            self.0.deposit_contract_address()
+        }
+    }
+
+    fn funded_state() -> State<CacheDB<EmptyDB>> {
+        state([(SENDER, AccountInfo { balance: U256::from(u64::MAX), ..Default::default() })])
+    }
+
+    #[test]
+    fn limits_blob_gas_per_block() {
+        let factory = factory();
+        let mut state = funded_state();
+        // Prague allows 9 blobs per block
+        let mut executor = executor_at(&factory, &mut state, EthereumHardfork::Prague);
+
+        executor.execute_transaction(&blob_tx(0, 6)).unwrap();
+        assert!(matches!(
+            executor.execute_transaction(&blob_tx(1, 6)),
+            Err(BlockExecutionError::Validation(
+                BlockValidationError::TransactionBlobGasMoreThanAvailableBlockBlobGas {
+                    transaction_blob_gas_used,
+                    block_available_blob_gas,
+                }
+            )) if transaction_blob_gas_used == 6 * DATA_GAS_PER_BLOB
+                && block_available_blob_gas == 3 * DATA_GAS_PER_BLOB
+        ));
+
+        executor.execute_transaction(&blob_tx(1, 3)).unwrap();
+        assert!(matches!(
+            executor.execute_transaction(&blob_tx(2, 1)),
+            Err(BlockExecutionError::Validation(
+                BlockValidationError::TransactionBlobGasMoreThanAvailableBlockBlobGas {
+                    block_available_blob_gas: 0,
+                    ..
+                }
+            ))
+        ));
+    }
+
+    #[test]
+    fn blob_limit_follows_fork_schedule() {
+        let factory = factory();
+        for (fork, max_blobs) in [
+            (EthereumHardfork::Cancun, 6),
+            (EthereumHardfork::Prague, 9),
+            (EthereumHardfork::Osaka, 9),
+            (EthereumHardfork::Bpo1, 15),
+            (EthereumHardfork::Bpo2, 21),
+        ] {
+            let mut state = funded_state();
+            let mut executor = executor_at(&factory, &mut state, fork);
+
+            // fill the block with transactions of at most 6 blobs
+            let mut nonce = 0;
+            let mut blobs = max_blobs;
+            while blobs > 0 {
+                let tx_blobs = blobs.min(6);
+                executor.execute_transaction(&blob_tx(nonce, tx_blobs)).unwrap();
+                nonce += 1;
+                blobs -= tx_blobs;
+            }
+
+            assert!(
+                matches!(
+                    executor.execute_transaction(&blob_tx(nonce, 1)),
+                    Err(BlockExecutionError::Validation(
+                        BlockValidationError::TransactionBlobGasMoreThanAvailableBlockBlobGas {
+                            block_available_blob_gas: 0,
+                            ..
+                        }
+                    ))
+                ),
+                "{fork:?} allows more than {max_blobs} blobs"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_blob_transactions_with_unknown_blob_params() {
+        let factory = EthBlockExecutorFactory::new(
+            AlloyReceiptBuilder,
+            Bpo3Spec(EthSpec::mainnet()),
+            EthEvmFactory,
+        );
+        let mut state = funded_state();
+        let mut executor = executor_at(&factory, &mut state, EthereumHardfork::Bpo2);
+
+        let timestamp = EthereumHardfork::Bpo2.mainnet_activation_timestamp().unwrap();
+        assert!(matches!(
+            executor.execute_transaction(&blob_tx(0, 1)),
+            Err(BlockExecutionError::Validation(BlockValidationError::MissingBlobParams {
+                timestamp: missing,
+            })) if missing == timestamp
+        ));
+    }
+}
@@ -12,7 +12,6 @@ use revm::{
     handler::{instructions::EthInstructions, EthFrame, EthPrecompiles, PrecompileProvider},
     inspector::NoOpInspector,
     interpreter::{interpreter::EthInterpreter, InterpreterResult},
-    precompile::{PrecompileSpecId, Precompiles},
     primitives::hardfork::SpecId,
     Context, ExecuteEvm, InspectEvm, Inspector, MainBuilder, MainContext, SystemCallEvm,
 };
@@ -96,9 +95,7 @@ impl<DB: Database, I> EthEvmBuilder<DB, I> {
     {
         let precompiles = match self.precompiles {
             Some(p) => p,
-            None => PrecompilesMap::from_static(Precompiles::new(PrecompileSpecId::from_spec_id(
-                self.cfg_env.spec,
-            ))),
+            None => PrecompilesMap::for_spec(self.cfg_env.spec),
         };
 
         let inner = Context::mainnet()
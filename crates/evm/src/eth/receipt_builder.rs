@@ -1,7 +1,7 @@
 //! Abstraction over receipt building logic to allow plugging different primitive types into
 //! [`super::EthBlockExecutor`].
 
-use crate::Evm;
+use crate::{block::BlockExecutionError, Evm};
 use alloy_consensus::{Eip658Value, ReceiptEnvelope, TxEnvelope, TxType};
 use revm::{context::result::ExecutionResult, state::EvmState};
 
@@ -30,10 +30,13 @@ pub trait ReceiptBuilder {
     type Receipt;
 
     /// Builds a receipt given a transaction and the result of the execution.
+    ///
+    /// Builders return an error for transactions they don't know how to build a receipt for,
+    /// e.g. transactions of an unsupported type.
     fn build_receipt<E: Evm>(
         &self,
         ctx: ReceiptBuilderCtx<'_, Self::Transaction, E>,
-    ) -> Self::Receipt;
+    ) -> Result<Self::Receipt, BlockExecutionError>;
 }
 
 /// Receipt builder operating on Alloy types.
@@ -45,7 +48,10 @@ impl ReceiptBuilder for AlloyReceiptBuilder {
     type Transaction = TxEnvelope;
     type Receipt = ReceiptEnvelope;
 
-    fn build_receipt<E: Evm>(&self, ctx: ReceiptBuilderCtx<'_, TxEnvelope, E>) -> Self::Receipt {
+    fn build_receipt<E: Evm>(
+        &self,
+        ctx: ReceiptBuilderCtx<'_, TxEnvelope, E>,
+    ) -> Result<Self::Receipt, BlockExecutionError> {
         let receipt = alloy_consensus::Receipt {
             status: Eip658Value::Eip658(ctx.result.is_success()),
             cumulative_gas_used: ctx.cumulative_gas_used,
@@ -53,12 +59,12 @@ impl ReceiptBuilder for AlloyReceiptBuilder {
         }
         .with_bloom();
 
-        match ctx.tx.tx_type() {
+        Ok(match ctx.tx.tx_type() {
             TxType::Legacy => ReceiptEnvelope::Legacy(receipt),
             TxType::Eip2930 => ReceiptEnvelope::Eip2930(receipt),
             TxType::Eip1559 => ReceiptEnvelope::Eip1559(receipt),
             TxType::Eip4844 => ReceiptEnvelope::Eip4844(receipt),
             TxType::Eip7702 => ReceiptEnvelope::Eip7702(receipt),
-        }
+        })
     }
 }
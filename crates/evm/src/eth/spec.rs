@@ -1,6 +1,6 @@
 //! Abstraction over configuration object for [`super::EthBlockExecutor`].
 
-use alloy_eips::eip6110::MAINNET_DEPOSIT_CONTRACT_ADDRESS;
+use alloy_eips::{eip6110::MAINNET_DEPOSIT_CONTRACT_ADDRESS, eip7840::BlobParams};
 use alloy_hardforks::{EthereumChainHardforks, EthereumHardfork, EthereumHardforks, ForkCondition};
 use alloy_primitives::{address, Address};
 
@@ -11,6 +11,37 @@ pub trait EthExecutorSpec: EthereumHardforks {
     ///
     /// Used by [`super::eip6110::parse_deposits_from_receipts`].
     fn deposit_contract_address(&self) -> Option<Address>;
+
+    /// Returns the blob parameters active at the given timestamp, used to limit the blob gas of a
+    /// block.
+    ///
+    /// Defaults to the parameters of the latest active fork changing them, up to the BPO2 blob
+    /// parameter only fork. Returns [`None`] before Cancun, and once any later blob parameter only
+    /// fork is active, as their parameters are chain specific. Chains with other schedules need to
+    /// override this: after Cancun, [`super::EthBlockExecutor`] rejects blob transactions with
+    /// [`MissingBlobParams`](crate::block::BlockValidationError::MissingBlobParams) while the
+    /// parameters are unknown.
+    fn blob_params_at_timestamp(&self, timestamp: u64) -> Option<BlobParams> {
+        let active = |fork| self.is_ethereum_fork_active_at_timestamp(fork, timestamp);
+        if [EthereumHardfork::Bpo3, EthereumHardfork::Bpo4, EthereumHardfork::Bpo5]
+            .into_iter()
+            .any(active)
+        {
+            None
+        } else if active(EthereumHardfork::Bpo2) {
+            Some(BlobParams::bpo2())
+        } else if active(EthereumHardfork::Bpo1) {
+            Some(BlobParams::bpo1())
+        } else if active(EthereumHardfork::Osaka) {
+            Some(BlobParams::osaka())
+        } else if active(EthereumHardfork::Prague) {
+            Some(BlobParams::prague())
+        } else if active(EthereumHardfork::Cancun) {
+            Some(BlobParams::cancun())
+        } else {
+            None
+        }
+    }
 }
 
 /// Basic Ethereum specification.
@@ -26,10 +26,15 @@ pub use traits::*;
 pub mod call;
 #[cfg(feature = "overrides")]
 pub mod overrides;
+#[cfg(feature = "simulate")]
+pub mod simulate;
 pub mod precompiles;
 pub mod tracing;
+pub mod witness;
 
 mod either;
+#[cfg(test)]
+mod test_utils;
 
 // re-export revm and op-revm
 #[cfg(feature = "op")]
@@ -3,7 +3,9 @@
 //! This module provides helper functions for RPC implementations, including:
 //! - Block and state overrides
 
-use alloc::collections::BTreeMap;
+use crate::{eth::EthBlockExecutionCtx, precompiles::PrecompilesMap};
+use alloc::{borrow::Cow, collections::BTreeMap, vec::Vec};
+use alloy_eips::{eip4895::Withdrawals, eip7840::BlobParams};
 use alloy_primitives::{keccak256, map::HashMap, Address, B256, U256};
 use alloy_rpc_types_eth::{
     state::{AccountOverride, StateOverride},
@@ -12,6 +14,7 @@ use alloy_rpc_types_eth::{
 use revm::{
     bytecode::BytecodeDecodeError,
     context::BlockEnv,
+    context_interface::block::BlobExcessGasAndPrice,
     database::{CacheDB, State},
     state::{Account, AccountStatus, Bytecode, EvmStorageSlot},
     Database, DatabaseCommit,
@@ -26,6 +29,15 @@ pub enum StateOverrideError<E> {
     /// Both state and state_diff were provided for an account.
     #[error("Both 'state' and 'stateDiff' fields are set for account {0}")]
     BothStateAndStateDiff(Address),
+    /// `movePrecompileToAddress` was set for an account that isn't a precompile.
+    #[error("account {0} is not a precompile")]
+    NotAPrecompile(Address),
+    /// More than one precompile was moved to the same address.
+    #[error("multiple precompiles are moved to {0}")]
+    DuplicatePrecompileDestination(Address),
+    /// A precompile was moved to an account that has overrides itself.
+    #[error("account {0} is already overridden")]
+    AlreadyOverridden(Address),
     /// Database error occurred.
     #[error(transparent)]
     Database(E),
@@ -105,7 +117,94 @@ where
     }
 }
 
+/// [`BlockOverrides`] extended with the Cancun and Prague block fields.
+///
+/// Besides the [`BlockEnv`] fields covered by [`BlockOverrides`], this allows overriding the
+/// blob gas price and the inputs of the beacon root and withdrawal processing.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExtendedBlockOverrides {
+    /// Overrides of the basic block fields.
+    pub base: BlockOverrides,
+    /// Overrides the excess blob gas, the blob base fee is derived from it unless
+    /// [`Self::blob_base_fee`] is set.
+    pub excess_blob_gas: Option<u64>,
+    /// Overrides the blob base fee.
+    pub blob_base_fee: Option<u128>,
+    /// Overrides the parent beacon block root used by the EIP-4788 system call.
+    pub parent_beacon_block_root: Option<B256>,
+    /// Overrides the withdrawals of the block.
+    pub withdrawals: Option<Withdrawals>,
+}
+
+impl From<BlockOverrides> for ExtendedBlockOverrides {
+    fn from(base: BlockOverrides) -> Self {
+        Self { base, ..Default::default() }
+    }
+}
+
+/// Overrides of the block execution context inputs, see [`ExtendedBlockOverrides`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecutionCtxOverrides {
+    /// Overridden parent beacon block root.
+    pub parent_beacon_block_root: Option<B256>,
+    /// Overridden withdrawals.
+    pub withdrawals: Option<Withdrawals>,
+}
+
+impl ExecutionCtxOverrides {
+    /// Applies the overrides to an [`EthBlockExecutionCtx`].
+    pub fn apply_to_eth_ctx(self, ctx: &mut EthBlockExecutionCtx<'_>) {
+        if let Some(root) = self.parent_beacon_block_root {
+            ctx.parent_beacon_block_root = Some(root);
+        }
+        if let Some(withdrawals) = self.withdrawals {
+            ctx.withdrawals = Some(Cow::Owned(withdrawals));
+        }
+    }
+}
+
+/// Applies the given extended block overrides to the env and updates overridden block hashes in
+/// the db.
+///
+/// `blob_params` are used to derive the blob base fee from an overridden excess blob gas. The
+/// overrides that aren't part of the [`BlockEnv`] are returned and have to be applied to the
+/// block execution context.
+pub fn apply_extended_block_overrides<DB>(
+    overrides: ExtendedBlockOverrides,
+    db: &mut DB,
+    env: &mut BlockEnv,
+    blob_params: &BlobParams,
+) -> ExecutionCtxOverrides
+where
+    DB: OverrideBlockHashes,
+{
+    let ExtendedBlockOverrides {
+        base,
+        excess_blob_gas,
+        blob_base_fee,
+        parent_beacon_block_root,
+        withdrawals,
+    } = overrides;
+
+    apply_block_overrides(base, db, env);
+
+    if excess_blob_gas.is_some() || blob_base_fee.is_some() {
+        let excess_blob_gas = excess_blob_gas
+            .or_else(|| env.blob_excess_gas_and_price.map(|blob| blob.excess_blob_gas))
+            .unwrap_or_default();
+        let blob_gasprice =
+            blob_base_fee.unwrap_or_else(|| blob_params.calc_blob_fee(excess_blob_gas));
+        env.blob_excess_gas_and_price =
+            Some(BlobExcessGasAndPrice { excess_blob_gas, blob_gasprice });
+    }
+
+    ExecutionCtxOverrides { parent_beacon_block_root, withdrawals }
+}
+
 /// Applies the given state overrides (a set of [`AccountOverride`]) to the database.
+///
+/// [`AccountOverride::move_precompile_to`] is ignored, use
+/// [`apply_state_overrides_with_precompiles`] to move precompiles.
 pub fn apply_state_overrides<DB>(
     overrides: StateOverride,
     db: &mut DB,
@@ -119,6 +218,52 @@ where
     Ok(())
 }
 
+/// Applies the given state overrides to the database and the precompiles.
+///
+/// In addition to [`apply_state_overrides`], this handles [`AccountOverride::move_precompile_to`]:
+/// all requested precompiles are removed from their addresses and installed at their
+/// destinations. The remaining fields of an override are applied to the account afterwards, which
+/// allows replacing a moved precompile with code.
+///
+/// Returns an error if a moved account isn't a precompile, if several precompiles are moved to
+/// the same address or if a precompile is moved to an account that is overridden as well, which
+/// also rules out swapping precompiles. All moves are validated before any of them is applied, so
+/// the precompiles are left untouched on error.
+pub fn apply_state_overrides_with_precompiles<DB>(
+    overrides: StateOverride,
+    db: &mut DB,
+    precompiles: &mut PrecompilesMap,
+) -> Result<(), StateOverrideError<DB::Error>>
+where
+    DB: Database + DatabaseCommit,
+{
+    let mut moves = Vec::new();
+    for (&source, account_override) in &overrides {
+        let Some(destination) = account_override.move_precompile_to else { continue };
+        if precompiles.get(&source).is_none() {
+            return Err(StateOverrideError::NotAPrecompile(source));
+        }
+        if overrides.contains_key(&destination) {
+            return Err(StateOverrideError::AlreadyOverridden(destination));
+        }
+        if moves.iter().any(|(_, existing)| *existing == destination) {
+            return Err(StateOverrideError::DuplicatePrecompileDestination(destination));
+        }
+        moves.push((source, destination));
+    }
+
+    for (source, destination) in moves {
+        let mut precompile = None;
+        precompiles.apply_precompile(&source, |existing| {
+            precompile = existing;
+            None
+        });
+        precompiles.apply_precompile(&destination, |_| precompile);
+    }
+
+    apply_state_overrides(overrides, db)
+}
+
 /// Applies a single [`AccountOverride`] to the database.
 fn apply_account_override<DB>(
     account: Address,
@@ -196,7 +341,7 @@ where
 mod tests {
     use super::*;
     use alloy_primitives::{address, bytes};
-    use revm::database::EmptyDB;
+    use revm::{database::EmptyDB, precompile::Precompiles};
 
     #[test]
     fn test_state_override_state() {
@@ -285,4 +430,125 @@ mod tests {
         assert_eq!(storage1, U256::from(100).into());
         assert_eq!(storage2, U256::from(200).into());
     }
+
+    #[test]
+    fn test_move_precompile() {
+        let ecrecover = address!("0x0000000000000000000000000000000000000001");
+        let sha256 = address!("0x0000000000000000000000000000000000000002");
+        let destination = address!("0x0000000000000000000000000000000000123456");
+        let code = bytes!("0x600160005260206000f3");
+
+        let mut db = CacheDB::new(EmptyDB::new());
+        let mut precompiles = PrecompilesMap::from_static(Precompiles::cancun());
+
+        let mut state_overrides = StateOverride::default();
+        state_overrides.insert(
+            ecrecover,
+            AccountOverride {
+                move_precompile_to: Some(destination),
+                ..AccountOverride::default().with_code(code.clone())
+            },
+        );
+        apply_state_overrides_with_precompiles(state_overrides, &mut db, &mut precompiles).unwrap();
+
+        assert!(precompiles.get(&ecrecover).is_none());
+        assert!(precompiles.get(&destination).is_some());
+        assert!(precompiles.get(&sha256).is_some());
+        assert_eq!(db.basic(ecrecover).unwrap().unwrap().code_hash, keccak256(&code));
+    }
+
+    #[test]
+    fn test_move_non_precompile() {
+        let account = address!("0x1234567890123456789012345678901234567890");
+
+        let mut db = CacheDB::new(EmptyDB::new());
+        let mut precompiles = PrecompilesMap::from_static(Precompiles::cancun());
+
+        let mut state_overrides = StateOverride::default();
+        state_overrides.insert(
+            account,
+            AccountOverride { move_precompile_to: Some(Address::ZERO), ..Default::default() },
+        );
+        let err =
+            apply_state_overrides_with_precompiles(state_overrides, &mut db, &mut precompiles)
+                .unwrap_err();
+
+        assert!(matches!(err, StateOverrideError::NotAPrecompile(address) if address == account));
+    }
+
+    #[test]
+    fn test_move_precompile_to_overridden_account() {
+        let ecrecover = address!("0x0000000000000000000000000000000000000001");
+        let sha256 = address!("0x0000000000000000000000000000000000000002");
+        let destination = address!("0x0000000000000000000000000000000000123456");
+
+        let mut db = CacheDB::new(EmptyDB::new());
+        let mut precompiles = PrecompilesMap::from_static(Precompiles::cancun());
+
+        // swapping two precompiles
+        let mut state_overrides = StateOverride::default();
+        state_overrides.insert(
+            ecrecover,
+            AccountOverride { move_precompile_to: Some(sha256), ..Default::default() },
+        );
+        state_overrides.insert(
+            sha256,
+            AccountOverride { move_precompile_to: Some(ecrecover), ..Default::default() },
+        );
+        let err =
+            apply_state_overrides_with_precompiles(state_overrides, &mut db, &mut precompiles)
+                .unwrap_err();
+        assert!(matches!(err, StateOverrideError::AlreadyOverridden(_)));
+
+        // a valid move next to an invalid one is not applied either
+        let mut state_overrides = StateOverride::default();
+        state_overrides.insert(
+            ecrecover,
+            AccountOverride { move_precompile_to: Some(destination), ..Default::default() },
+        );
+        state_overrides.insert(
+            sha256,
+            AccountOverride { move_precompile_to: Some(destination), ..Default::default() },
+        );
+        let err =
+            apply_state_overrides_with_precompiles(state_overrides, &mut db, &mut precompiles)
+                .unwrap_err();
+        assert!(matches!(
+            err,
+            StateOverrideError::DuplicatePrecompileDestination(address) if address == destination
+        ));
+
+        assert!(precompiles.get(&ecrecover).is_some());
+        assert!(precompiles.get(&sha256).is_some());
+        assert!(precompiles.get(&destination).is_none());
+    }
+
+    #[test]
+    fn test_extended_block_overrides() {
+        let mut db = CacheDB::new(EmptyDB::new());
+        let mut env = BlockEnv::default();
+        let root = B256::repeat_byte(0x42);
+
+        let overrides = ExtendedBlockOverrides {
+            base: BlockOverrides { time: Some(1_000), ..Default::default() },
+            excess_blob_gas: Some(10_000_000),
+            parent_beacon_block_root: Some(root),
+            ..Default::default()
+        };
+        let ctx_overrides =
+            apply_extended_block_overrides(overrides, &mut db, &mut env, &BlobParams::cancun());
+
+        let blob = env.blob_excess_gas_and_price.unwrap();
+        assert_eq!(env.timestamp, U256::from(1_000));
+        assert_eq!(blob.excess_blob_gas, 10_000_000);
+        assert_eq!(blob.blob_gasprice, BlobParams::cancun().calc_blob_fee(10_000_000));
+        assert_eq!(ctx_overrides.parent_beacon_block_root, Some(root));
+
+        let overrides = ExtendedBlockOverrides { blob_base_fee: Some(7), ..Default::default() };
+        apply_extended_block_overrides(overrides, &mut db, &mut env, &BlobParams::cancun());
+
+        let blob = env.blob_excess_gas_and_price.unwrap();
+        assert_eq!(blob.excess_blob_gas, 10_000_000);
+        assert_eq!(blob.blob_gasprice, 7);
+    }
 }
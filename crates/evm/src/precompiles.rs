@@ -1,7 +1,7 @@
 //! Helpers for dealing with Precompiles.
 
 use crate::{Database, EvmInternals};
-use alloc::{borrow::Cow, boxed::Box, string::String, sync::Arc};
+use alloc::{borrow::Cow, boxed::Box, string::String, sync::Arc, vec::Vec};
 use alloy_consensus::transaction::Either;
 use alloy_primitives::{
     map::{HashMap, HashSet},
@@ -12,10 +12,23 @@ use revm::{
     context::LocalContextTr,
     handler::{EthPrecompiles, PrecompileProvider},
     interpreter::{CallInput, CallInputs, CallValue, Gas, InstructionResult, InterpreterResult},
-    precompile::{PrecompileError, PrecompileFn, PrecompileId, PrecompileResult, Precompiles},
+    precompile::{
+        PrecompileError, PrecompileFn, PrecompileId, PrecompileResult, PrecompileSpecId,
+        Precompiles,
+    },
+    primitives::hardfork::SpecId,
     Context, Journal,
 };
 
+#[cfg(feature = "std")]
+pub mod cache;
+#[cfg(feature = "std")]
+pub use cache::{PrecompileCache, PrecompileCacheStats};
+#[cfg(feature = "std")]
+pub mod metrics;
+#[cfg(feature = "std")]
+pub use metrics::{PrecompileMetrics, PrecompileMetricsSnapshot, PrecompileProfiler};
+
 /// A mapping of precompile contracts that can be either static (builtin) or dynamic.
 ///
 /// This is an optimization that allows us to keep using the static precompiles
@@ -26,8 +39,21 @@ pub struct PrecompilesMap {
     precompiles: PrecompilesKind,
     /// An optional dynamic precompile loader that can lookup precompiles dynamically.
     lookup: Option<Arc<dyn PrecompileLookup>>,
+    /// An optional builder of the builtin precompiles, used to follow spec changes.
+    spec_precompiles: Option<Arc<dyn SpecPrecompiles>>,
+    /// The spec the builtin precompiles were built for, if they follow spec changes.
+    spec: Option<SpecId>,
+    /// Addresses that were overridden after construction.
+    ///
+    /// These are kept as is when the builtin precompiles are rebuilt for a new spec.
+    overridden: HashSet<Address>,
+    /// Wrappers applied to all precompiles, reapplied when the builtin precompiles are rebuilt.
+    wrappers: Vec<PrecompileWrapper>,
 }
 
+/// A wrapper applied with [`PrecompilesMap::wrap_precompiles`].
+type PrecompileWrapper = Arc<dyn Fn(&Address, DynPrecompile) -> DynPrecompile + Send + Sync>;
+
 impl PrecompilesMap {
     /// Creates the [`PrecompilesMap`] from a static reference.
     pub fn from_static(precompiles: &'static Precompiles) -> Self {
@@ -36,7 +62,86 @@ impl PrecompilesMap {
 
     /// Creates a new set of precompiles for a spec.
     pub fn new(precompiles: Cow<'static, Precompiles>) -> Self {
-        Self { precompiles: PrecompilesKind::Builtin(precompiles), lookup: None }
+        Self {
+            precompiles: PrecompilesKind::Builtin(precompiles),
+            lookup: None,
+            spec_precompiles: None,
+            spec: None,
+            overridden: HashSet::default(),
+            wrappers: Vec::new(),
+        }
+    }
+
+    /// Creates the Ethereum precompiles for the given spec, which are rebuilt when the spec of
+    /// the EVM changes.
+    ///
+    /// See [`Self::with_spec_precompiles`].
+    pub fn for_spec(spec: SpecId) -> Self {
+        Self::with_spec_precompiles(spec, |spec| {
+            Cow::Borrowed(Precompiles::new(PrecompileSpecId::from_spec_id(spec)))
+        })
+    }
+
+    /// Creates the precompiles for the given spec using `builtins`.
+    ///
+    /// Whenever the spec changes, see [`PrecompileProvider::set_spec`], the builtin precompiles
+    /// are rebuilt using `builtins` and the wrappers added with [`Self::wrap_precompiles`] are
+    /// applied to them again. Precompiles added, removed or modified at a given address with
+    /// [`Self::map_precompile`] and [`Self::apply_precompile`] are kept as is, as is the lookup
+    /// set with [`Self::set_precompile_lookup`]. Transformations of all precompiles with
+    /// [`Self::map_precompiles`] and [`Self::map_pure_precompiles`] only affect the current
+    /// builtin precompiles.
+    pub fn with_spec_precompiles<P>(spec: SpecId, builtins: P) -> Self
+    where
+        P: SpecPrecompiles + 'static,
+    {
+        let mut this = Self::new(builtins.precompiles(spec));
+        this.spec_precompiles = Some(Arc::new(builtins));
+        this.spec = Some(spec);
+        this
+    }
+
+    /// Returns the spec the builtin precompiles were built for, if they follow spec changes.
+    pub const fn spec(&self) -> Option<SpecId> {
+        self.spec
+    }
+
+    /// Rebuilds the builtin precompiles for the given spec, keeping overridden precompiles.
+    ///
+    /// Returns `true` if the precompiles were rebuilt, which is the case if they follow spec
+    /// changes and the spec differs from the current one.
+    pub fn set_builtin_spec(&mut self, spec: SpecId) -> bool {
+        let Some(builtins) = self.spec_precompiles.clone() else { return false };
+        if self.spec == Some(spec) {
+            return false;
+        }
+        self.spec = Some(spec);
+
+        let builtins = builtins.precompiles(spec);
+        let previous =
+            core::mem::replace(&mut self.precompiles, PrecompilesKind::Builtin(builtins));
+        for wrapper in self.wrappers.clone() {
+            self.map_precompiles(|address, precompile| wrapper(address, precompile));
+        }
+        if let PrecompilesKind::Dynamic(mut previous) = previous {
+            let overridden = core::mem::take(&mut self.overridden);
+            let dyn_precompiles = self.ensure_dynamic_precompiles();
+            for address in &overridden {
+                match previous.inner.remove(address) {
+                    Some(precompile) => {
+                        dyn_precompiles.inner.insert(*address, precompile);
+                        dyn_precompiles.addresses.insert(*address);
+                    }
+                    None => {
+                        dyn_precompiles.inner.remove(address);
+                        dyn_precompiles.addresses.remove(address);
+                    }
+                }
+            }
+            self.overridden = overridden;
+        }
+
+        true
     }
 
     /// Maps a precompile at the given address using the provided function.
@@ -53,10 +158,14 @@ impl PrecompilesMap {
 
             // update the precompile at the address
             dyn_precompiles.inner.insert(*address, transformed);
+            self.overridden.insert(*address);
         }
     }
 
     /// Maps all precompiles using the provided function.
+    ///
+    /// Builtin precompiles rebuilt for a new spec aren't mapped again, see
+    /// [`Self::wrap_precompiles`] for a transformation that persists.
     pub fn map_precompiles<F>(&mut self, f: F)
     where
         F: FnMut(&Address, DynPrecompile) -> DynPrecompile,
@@ -75,6 +184,19 @@ impl PrecompilesMap {
         self.map_precompiles_filtered(f, |_, precompile| precompile.is_pure());
     }
 
+    /// Wraps all precompiles using the provided function.
+    ///
+    /// Unlike [`Self::map_precompiles`], the wrapper is applied again to the builtin precompiles
+    /// whenever they are rebuilt for a new spec, see [`Self::with_spec_precompiles`]. Precompiles
+    /// overridden at a given address are only wrapped if they exist when this is called.
+    pub fn wrap_precompiles<F>(&mut self, f: F)
+    where
+        F: Fn(&Address, DynPrecompile) -> DynPrecompile + Send + Sync + 'static,
+    {
+        self.map_precompiles(&f);
+        self.wrappers.push(Arc::new(f));
+    }
+
     /// Internal helper to map precompiles with an optional filter.
     ///
     /// The `filter` decides whether to apply the mapping function `f` to a given
@@ -145,6 +267,7 @@ impl PrecompilesMap {
     where
         F: FnOnce(Option<DynPrecompile>) -> Option<DynPrecompile>,
     {
+        self.overridden.insert(*address);
         let dyn_precompiles = self.ensure_dynamic_precompiles();
         let current = dyn_precompiles.inner.get(address).cloned();
 
@@ -351,13 +474,13 @@ impl<BlockEnv, TxEnv, CfgEnv, DB, Chain>
 where
     BlockEnv: revm::context::Block,
     TxEnv: revm::context::Transaction,
-    CfgEnv: revm::context::Cfg,
+    CfgEnv: revm::context::Cfg + 'static,
     DB: Database,
 {
     type Output = InterpreterResult;
 
-    fn set_spec(&mut self, _spec: CfgEnv::Spec) -> bool {
-        false
+    fn set_spec(&mut self, spec: CfgEnv::Spec) -> bool {
+        self.set_builtin_spec(spec.into())
     }
 
     fn run(
@@ -403,7 +526,9 @@ where
                 CallValue::Apparent(v) => v,
                 CallValue::Transfer(v) => v,
             },
-            internals: EvmInternals::new(journal, &context.block),
+            internals: EvmInternals::new(journal, &context.block)
+                .with_tx_env(&context.tx)
+                .with_cfg_env(&context.cfg),
             target_address: inputs.target_address,
             bytecode_address: inputs.bytecode_address,
         });
@@ -570,6 +695,27 @@ impl<'a> PrecompileInput<'a> {
         self.target_address == self.bytecode_address
     }
 
+    /// Returns the sender of the executed transaction, i.e. `tx.origin`, if available.
+    ///
+    /// See [`EvmInternals::tx`].
+    pub fn tx_origin(&self) -> Option<Address> {
+        self.internals.tx_origin()
+    }
+
+    /// Returns the active hardfork, if available.
+    ///
+    /// See [`EvmInternals::spec_id`].
+    pub fn spec_id(&self) -> Option<SpecId> {
+        self.internals.spec_id()
+    }
+
+    /// Returns the spec of the EVM, if available.
+    ///
+    /// See [`EvmInternals::spec`].
+    pub fn spec<S: Clone + 'static>(&self) -> Option<S> {
+        self.internals.spec()
+    }
+
     /// Returns the [`EvmInternals`].
     pub fn internals(&self) -> &EvmInternals<'_> {
         &self.internals
@@ -754,6 +900,22 @@ pub trait PrecompileLookup: Send + Sync {
     fn lookup(&self, address: &Address) -> Option<DynPrecompile>;
 }
 
+/// Trait for building the builtin precompiles of a spec, see
+/// [`PrecompilesMap::with_spec_precompiles`].
+pub trait SpecPrecompiles: Send + Sync {
+    /// Returns the builtin precompiles of the given spec.
+    fn precompiles(&self, spec: SpecId) -> Cow<'static, Precompiles>;
+}
+
+impl<F> SpecPrecompiles for F
+where
+    F: Fn(SpecId) -> Cow<'static, Precompiles> + Send + Sync,
+{
+    fn precompiles(&self, spec: SpecId) -> Cow<'static, Precompiles> {
+        self(spec)
+    }
+}
+
 /// Implement PrecompileLookup for closure types
 impl<F> PrecompileLookup for F
 where
@@ -1019,4 +1181,110 @@ mod tests {
             "Identity precompile should return the input data after conversion to dynamic"
         );
     }
+
+    #[test]
+    fn test_follows_spec_changes() {
+        let identity_address = address!("0x0000000000000000000000000000000000000004");
+        let kzg_address = address!("0x000000000000000000000000000000000000000a");
+        let custom_address = address!("0x0000000000000000000000000000000000001000");
+
+        let mut precompiles = PrecompilesMap::for_spec(SpecId::SHANGHAI);
+        assert!(precompiles.get(&kzg_address).is_none());
+
+        precompiles.map_precompile(&identity_address, |_| {
+            DynPrecompile::new(PrecompileId::Custom("constant".into()), |_input| {
+                Ok(PrecompileOutput::new(10, Bytes::from_static(b"constant")))
+            })
+        });
+        precompiles.apply_precompile(&custom_address, |_| {
+            Some(DynPrecompile::new(PrecompileId::Custom("custom".into()), |_input| {
+                Ok(PrecompileOutput::new(10, Bytes::new()))
+            }))
+        });
+
+        assert!(PrecompileProvider::<EthEvmContext<EmptyDB>>::set_spec(
+            &mut precompiles,
+            SpecId::CANCUN
+        ));
+        assert_eq!(precompiles.spec(), Some(SpecId::CANCUN));
+        assert!(precompiles.get(&kzg_address).is_some());
+        assert!(precompiles.get(&custom_address).is_some());
+        assert!(precompiles.addresses().any(|address| *address == kzg_address));
+
+        let mut ctx = EthEvmContext::new(EmptyDB::default(), Default::default());
+        let result = precompiles
+            .get(&identity_address)
+            .unwrap()
+            .call(PrecompileInput {
+                data: b"input",
+                gas: 1000,
+                caller: Address::ZERO,
+                value: U256::ZERO,
+                internals: EvmInternals::new(&mut ctx.journaled_state, &ctx.block),
+                target_address: identity_address,
+                bytecode_address: identity_address,
+            })
+            .unwrap();
+        assert_eq!(result.bytes, Bytes::from_static(b"constant"));
+
+        // setting the same spec again is a no-op
+        assert!(!PrecompileProvider::<EthEvmContext<EmptyDB>>::set_spec(
+            &mut precompiles,
+            SpecId::CANCUN
+        ));
+    }
+
+    #[test]
+    fn test_wrappers_follow_spec_changes() {
+        let identity_address = address!("0x0000000000000000000000000000000000000004");
+        let shanghai = Precompiles::new(PrecompileSpecId::from_spec_id(SpecId::SHANGHAI)).len();
+        let cancun = Precompiles::new(PrecompileSpecId::from_spec_id(SpecId::CANCUN)).len();
+
+        let wrapped = Arc::new(core::sync::atomic::AtomicUsize::new(0));
+        let mut precompiles = PrecompilesMap::for_spec(SpecId::SHANGHAI);
+        let counter = wrapped.clone();
+        precompiles.wrap_precompiles(move |_, precompile| {
+            counter.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+            precompile
+        });
+        // mapping all precompiles doesn't mark them as overridden
+        precompiles.map_precompiles(|_, _| {
+            DynPrecompile::new(PrecompileId::Custom("constant".into()), |_input| {
+                Ok(PrecompileOutput::new(10, Bytes::from_static(b"constant")))
+            })
+        });
+        assert_eq!(wrapped.load(core::sync::atomic::Ordering::Relaxed), shanghai);
+
+        assert!(PrecompileProvider::<EthEvmContext<EmptyDB>>::set_spec(
+            &mut precompiles,
+            SpecId::CANCUN
+        ));
+        assert_eq!(wrapped.load(core::sync::atomic::Ordering::Relaxed), shanghai + cancun);
+
+        let mut ctx = EthEvmContext::new(EmptyDB::default(), Default::default());
+        let result = precompiles
+            .get(&identity_address)
+            .unwrap()
+            .call(PrecompileInput {
+                data: b"input",
+                gas: 1000,
+                caller: Address::ZERO,
+                value: U256::ZERO,
+                internals: EvmInternals::new(&mut ctx.journaled_state, &ctx.block),
+                target_address: identity_address,
+                bytecode_address: identity_address,
+            })
+            .unwrap();
+        assert_eq!(result.bytes, Bytes::from_static(b"input"));
+    }
+
+    #[test]
+    fn test_static_precompiles_ignore_spec_changes() {
+        let mut precompiles = PrecompilesMap::from_static(Precompiles::berlin());
+        assert!(!PrecompileProvider::<EthEvmContext<EmptyDB>>::set_spec(
+            &mut precompiles,
+            SpecId::CANCUN
+        ));
+        assert_eq!(precompiles.spec(), None);
+    }
 }
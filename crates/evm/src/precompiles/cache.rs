@@ -0,0 +1,338 @@
+//! Memoization of pure precompile calls.
+
+use super::{DynPrecompile, Precompile, PrecompileInput, PrecompilesMap};
+use alloc::{collections::VecDeque, sync::Arc};
+use alloy_primitives::{map::HashMap, Bytes};
+use core::sync::atomic::{AtomicU64, Ordering};
+use revm::{
+    precompile::{PrecompileError, PrecompileId, PrecompileResult},
+    primitives::hardfork::SpecId,
+};
+use std::sync::{Mutex, MutexGuard};
+
+/// Key of a cached precompile call.
+type CacheKey = (PrecompileId, Option<SpecId>, Bytes, u64);
+
+/// Statistics of a [`PrecompileCache`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PrecompileCacheStats {
+    /// Number of calls answered from the cache.
+    pub hits: u64,
+    /// Number of calls that were executed.
+    pub misses: u64,
+    /// Number of entries evicted to stay within the capacity.
+    pub evictions: u64,
+    /// Number of cached entries.
+    pub entries: usize,
+}
+
+impl PrecompileCacheStats {
+    /// Returns the share of calls answered from the cache, or `0.0` if there were no calls.
+    pub fn hit_rate(&self) -> f64 {
+        let calls = self.hits + self.misses;
+        if calls == 0 {
+            0.0
+        } else {
+            self.hits as f64 / calls as f64
+        }
+    }
+}
+
+/// A bounded cache of pure precompile results.
+///
+/// Results are keyed by [`PrecompileId`], the active hardfork, input and gas limit, so the cache
+/// can be shared by all precompiles of a [`PrecompilesMap`] and across EVM instances running on
+/// different hardforks. Cloning the cache is cheap and the clones share their entries and
+/// statistics.
+///
+/// The hardfork is the Ethereum equivalent of the EVM spec, see [`PrecompileInput::spec_id`].
+/// Chains whose precompiles change between hardforks that map to the same Ethereum spec should
+/// use a separate cache per hardfork.
+///
+/// Only precompiles called through a [`PrecompilesMap`] can be cached. Providers wrapping a
+/// [`PrecompilesMap`], like the seismic precompiles, are cached through the wrapped map.
+///
+/// Once the capacity is reached, the oldest entries are evicted. Fatal precompile errors are
+/// never cached.
+#[derive(Debug, Clone)]
+pub struct PrecompileCache {
+    inner: Arc<CacheInner>,
+}
+
+#[derive(Debug)]
+struct CacheInner {
+    capacity: usize,
+    entries: Mutex<Entries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    results: HashMap<CacheKey, PrecompileResult>,
+    /// Insertion order of the keys, used for eviction.
+    order: VecDeque<CacheKey>,
+}
+
+impl PrecompileCache {
+    /// Creates a new cache holding at most `capacity` results.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(CacheInner {
+                capacity,
+                entries: Mutex::default(),
+                hits: AtomicU64::new(0),
+                misses: AtomicU64::new(0),
+                evictions: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Returns the maximum number of cached results.
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity
+    }
+
+    /// Returns the current statistics of the cache.
+    pub fn stats(&self) -> PrecompileCacheStats {
+        PrecompileCacheStats {
+            hits: self.inner.hits.load(Ordering::Relaxed),
+            misses: self.inner.misses.load(Ordering::Relaxed),
+            evictions: self.inner.evictions.load(Ordering::Relaxed),
+            entries: self.entries().results.len(),
+        }
+    }
+
+    /// Removes all cached results, statistics are kept.
+    pub fn clear(&self) {
+        let mut entries = self.entries();
+        entries.results.clear();
+        entries.order.clear();
+    }
+
+    /// Wraps the given precompile to cache its results, if it is pure.
+    ///
+    /// Non-pure precompiles are returned unchanged, see [`Precompile::is_pure`].
+    pub fn wrap(&self, precompile: DynPrecompile) -> DynPrecompile {
+        if !precompile.is_pure() {
+            return precompile;
+        }
+        DynPrecompile(Arc::new(CachedPrecompile { precompile, cache: self.clone() }))
+    }
+
+    fn entries(&self) -> MutexGuard<'_, Entries> {
+        self.inner.entries.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn get(&self, key: &CacheKey) -> Option<PrecompileResult> {
+        let result = self.entries().results.get(key).cloned();
+        let counter = if result.is_some() { &self.inner.hits } else { &self.inner.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        result
+    }
+
+    fn insert(&self, key: CacheKey, result: PrecompileResult) {
+        if self.inner.capacity == 0 {
+            return;
+        }
+
+        let mut entries = self.entries();
+        if entries.results.insert(key.clone(), result).is_some() {
+            // another instance raced us, the key is already tracked
+            return;
+        }
+        entries.order.push_back(key);
+
+        while entries.order.len() > self.inner.capacity {
+            if let Some(oldest) = entries.order.pop_front() {
+                entries.results.remove(&oldest);
+                self.inner.evictions.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// A pure precompile whose results are memoized in a [`PrecompileCache`].
+#[derive(Debug)]
+struct CachedPrecompile {
+    precompile: DynPrecompile,
+    cache: PrecompileCache,
+}
+
+impl Precompile for CachedPrecompile {
+    fn precompile_id(&self) -> &PrecompileId {
+        self.precompile.precompile_id()
+    }
+
+    fn call(&self, input: PrecompileInput<'_>) -> PrecompileResult {
+        let key = (
+            self.precompile_id().clone(),
+            input.spec_id(),
+            Bytes::copy_from_slice(input.data),
+            input.gas,
+        );
+        if let Some(result) = self.cache.get(&key) {
+            return result;
+        }
+
+        let result = self.precompile.call(input);
+        if !matches!(result, Err(PrecompileError::Fatal(_))) {
+            self.cache.insert(key, result.clone());
+        }
+        result
+    }
+}
+
+impl PrecompilesMap {
+    /// Caches the results of all pure precompiles in the given cache.
+    ///
+    /// See [`PrecompileCache`]. Builtin precompiles rebuilt for a new spec are cached as well,
+    /// see [`PrecompilesMap::wrap_precompiles`].
+    pub fn cache_pure_precompiles(&mut self, cache: &PrecompileCache) {
+        let cache = cache.clone();
+        self.wrap_precompiles(move |_, precompile| cache.wrap(precompile));
+    }
+
+    /// Builder-style method that caches the results of all pure precompiles in the given cache.
+    ///
+    /// This is a consuming version of [`cache_pure_precompiles`](Self::cache_pure_precompiles)
+    /// that returns `Self`.
+    pub fn with_precompile_cache(mut self, cache: &PrecompileCache) -> Self {
+        self.cache_pure_precompiles(cache);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{eth::EthEvmContext, EvmInternals};
+    use alloy_primitives::{address, Address, U256};
+    use core::sync::atomic::AtomicUsize;
+    use revm::{context::CfgEnv, database::EmptyDB, precompile::PrecompileOutput};
+
+    fn counting_precompile(calls: Arc<AtomicUsize>) -> DynPrecompile {
+        DynPrecompile::new(PrecompileId::Custom("counting".into()), move |input| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            Ok(PrecompileOutput::new(input.gas / 2, Bytes::copy_from_slice(input.data)))
+        })
+    }
+
+    fn call(precompile: &DynPrecompile, data: &[u8], gas: u64) -> PrecompileResult {
+        let address = address!("0x0000000000000000000000000000000000001000");
+        let mut ctx = EthEvmContext::new(EmptyDB::default(), Default::default());
+        precompile.call(PrecompileInput {
+            data,
+            gas,
+            caller: Address::ZERO,
+            value: U256::ZERO,
+            internals: EvmInternals::new(&mut ctx.journaled_state, &ctx.block),
+            target_address: address,
+            bytecode_address: address,
+        })
+    }
+
+    #[test]
+    fn caches_pure_precompiles() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let cache = PrecompileCache::new(16);
+        let precompile = cache.wrap(counting_precompile(calls.clone()));
+
+        let first = call(&precompile, b"input", 100).unwrap();
+        let second = call(&precompile, b"input", 100).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        // a different gas limit is a different entry
+        call(&precompile, b"input", 200).unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+
+        let stats = cache.stats();
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.misses, 2);
+        assert_eq!(stats.entries, 2);
+    }
+
+    #[test]
+    fn separates_hardforks() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let cache = PrecompileCache::new(16);
+        let precompile = cache.wrap(counting_precompile(calls.clone()));
+        let address = address!("0x0000000000000000000000000000000000001000");
+
+        let mut ctx = EthEvmContext::new(EmptyDB::default(), Default::default());
+        for spec in [SpecId::CANCUN, SpecId::PRAGUE, SpecId::PRAGUE] {
+            let cfg = CfgEnv::new_with_spec(spec);
+            precompile
+                .call(PrecompileInput {
+                    data: b"input",
+                    gas: 100,
+                    caller: Address::ZERO,
+                    value: U256::ZERO,
+                    internals: EvmInternals::new(&mut ctx.journaled_state, &ctx.block)
+                        .with_cfg_env(&cfg),
+                    target_address: address,
+                    bytecode_address: address,
+                })
+                .unwrap();
+        }
+
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+        assert_eq!(cache.stats().entries, 2);
+    }
+
+    #[test]
+    fn evicts_oldest_entries() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let cache = PrecompileCache::new(1);
+        let precompile = cache.wrap(counting_precompile(calls.clone()));
+
+        call(&precompile, b"a", 100).unwrap();
+        call(&precompile, b"b", 100).unwrap();
+        call(&precompile, b"a", 100).unwrap();
+
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+        assert_eq!(cache.stats().evictions, 2);
+        assert_eq!(cache.stats().entries, 1);
+    }
+
+    #[test]
+    fn skips_stateful_precompiles() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let cache = PrecompileCache::new(16);
+        let precompile = cache.wrap(counting_precompile(calls.clone()).stateful());
+
+        call(&precompile, b"input", 100).unwrap();
+        call(&precompile, b"input", 100).unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+        assert_eq!(cache.stats(), PrecompileCacheStats::default());
+    }
+
+    #[test]
+    fn shares_entries_between_maps() {
+        let cache = PrecompileCache::new(16);
+        let identity = address!("0x0000000000000000000000000000000000000004");
+        let first = PrecompilesMap::for_spec(SpecId::CANCUN).with_precompile_cache(&cache);
+        let second = first.clone();
+
+        let mut ctx = EthEvmContext::new(EmptyDB::default(), Default::default());
+        for precompiles in [&first, &second] {
+            precompiles
+                .get(&identity)
+                .unwrap()
+                .call(PrecompileInput {
+                    data: b"input",
+                    gas: 1000,
+                    caller: Address::ZERO,
+                    value: U256::ZERO,
+                    internals: EvmInternals::new(&mut ctx.journaled_state, &ctx.block),
+                    target_address: identity,
+                    bytecode_address: identity,
+                })
+                .unwrap();
+        }
+
+        assert_eq!(cache.stats().hits, 1);
+    }
+}
@@ -0,0 +1,286 @@
+//! Call metrics and gas profiling of precompiles.
+
+use super::{DynPrecompile, Precompile, PrecompileInput, PrecompilesMap};
+use alloc::{sync::Arc, vec::Vec};
+use alloy_primitives::map::HashMap;
+use core::time::Duration;
+use revm::precompile::{PrecompileId, PrecompileResult};
+use std::{
+    sync::{Mutex, MutexGuard},
+    time::Instant,
+};
+
+/// Metrics of the calls to a single precompile.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PrecompileMetrics {
+    /// Number of calls.
+    pub calls: u64,
+    /// Total gas used by all calls.
+    ///
+    /// Failed calls consume their entire gas limit.
+    pub gas_used: u64,
+    /// Total size of the inputs of all calls, in bytes.
+    pub input_bytes: u64,
+    /// Number of calls that reverted or failed for a reason other than running out of gas.
+    pub failures: u64,
+    /// Number of calls that ran out of gas.
+    pub out_of_gas: u64,
+    /// Total time spent executing the precompile.
+    pub elapsed: Duration,
+}
+
+impl PrecompileMetrics {
+    /// Returns the average gas used per call, or `0` if there were no calls.
+    pub fn average_gas_used(&self) -> u64 {
+        self.gas_used.checked_div(self.calls).unwrap_or_default()
+    }
+
+    /// Returns the average time per call, or [`Duration::ZERO`] if there were no calls.
+    pub fn average_elapsed(&self) -> Duration {
+        u32::try_from(self.calls)
+            .ok()
+            .and_then(|calls| self.elapsed.checked_div(calls))
+            .unwrap_or_default()
+    }
+
+    fn record(
+        &mut self,
+        input_len: usize,
+        gas_limit: u64,
+        result: &PrecompileResult,
+        elapsed: Duration,
+    ) {
+        self.calls += 1;
+        self.input_bytes = self.input_bytes.saturating_add(input_len as u64);
+        self.elapsed += elapsed;
+
+        let gas_used = match result {
+            Ok(output) => {
+                if output.reverted {
+                    self.failures += 1;
+                }
+                output.gas_used
+            }
+            Err(err) => {
+                if err.is_oog() {
+                    self.out_of_gas += 1;
+                } else {
+                    self.failures += 1;
+                }
+                gas_limit
+            }
+        };
+        self.gas_used = self.gas_used.saturating_add(gas_used);
+    }
+}
+
+/// Point-in-time copy of the metrics recorded by a [`PrecompileProfiler`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PrecompileMetricsSnapshot {
+    /// Metrics per precompile.
+    pub precompiles: HashMap<PrecompileId, PrecompileMetrics>,
+}
+
+impl PrecompileMetricsSnapshot {
+    /// Returns the metrics of the given precompile, if it was called.
+    pub fn get(&self, id: &PrecompileId) -> Option<&PrecompileMetrics> {
+        self.precompiles.get(id)
+    }
+
+    /// Returns the metrics summed over all precompiles.
+    pub fn total(&self) -> PrecompileMetrics {
+        self.precompiles.values().fold(PrecompileMetrics::default(), |mut total, metrics| {
+            total.calls += metrics.calls;
+            total.gas_used = total.gas_used.saturating_add(metrics.gas_used);
+            total.input_bytes = total.input_bytes.saturating_add(metrics.input_bytes);
+            total.failures += metrics.failures;
+            total.out_of_gas += metrics.out_of_gas;
+            total.elapsed += metrics.elapsed;
+            total
+        })
+    }
+
+    /// Returns the precompiles sorted by total gas used, highest first.
+    pub fn by_gas_used(&self) -> Vec<(&PrecompileId, &PrecompileMetrics)> {
+        let mut precompiles = self.precompiles.iter().collect::<Vec<_>>();
+        precompiles.sort_by(|(_, a), (_, b)| b.gas_used.cmp(&a.gas_used));
+        precompiles
+    }
+
+    /// Returns the precompiles sorted by total time spent, highest first.
+    pub fn by_elapsed(&self) -> Vec<(&PrecompileId, &PrecompileMetrics)> {
+        let mut precompiles = self.precompiles.iter().collect::<Vec<_>>();
+        precompiles.sort_by(|(_, a), (_, b)| b.elapsed.cmp(&a.elapsed));
+        precompiles
+    }
+}
+
+/// Records [`PrecompileMetrics`] per [`PrecompileId`].
+///
+/// Precompiles are instrumented with [`Self::wrap`] or
+/// [`PrecompilesMap::profile_precompiles`]. Since metrics are keyed by [`PrecompileId`], this
+/// works the same for any [`PrecompilesMap`], and a profiler can be shared across EVM instances.
+/// Cloning the profiler is cheap and the clones share their metrics.
+///
+/// Only precompiles called through a [`PrecompilesMap`] can be profiled. Providers wrapping a
+/// [`PrecompilesMap`], like the seismic precompiles, are profiled through the wrapped map.
+#[derive(Debug, Clone, Default)]
+pub struct PrecompileProfiler {
+    metrics: Arc<Mutex<HashMap<PrecompileId, PrecompileMetrics>>>,
+}
+
+impl PrecompileProfiler {
+    /// Creates a new profiler without recorded metrics.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wraps the given precompile to record metrics of its calls.
+    pub fn wrap(&self, precompile: DynPrecompile) -> DynPrecompile {
+        DynPrecompile(Arc::new(ProfiledPrecompile { precompile, profiler: self.clone() }))
+    }
+
+    /// Returns a snapshot of the recorded metrics.
+    pub fn snapshot(&self) -> PrecompileMetricsSnapshot {
+        PrecompileMetricsSnapshot { precompiles: self.metrics().clone() }
+    }
+
+    /// Clears the recorded metrics.
+    pub fn reset(&self) {
+        self.metrics().clear();
+    }
+
+    /// Returns a snapshot of the recorded metrics and clears them.
+    pub fn take_snapshot(&self) -> PrecompileMetricsSnapshot {
+        PrecompileMetricsSnapshot { precompiles: core::mem::take(&mut *self.metrics()) }
+    }
+
+    fn metrics(&self) -> MutexGuard<'_, HashMap<PrecompileId, PrecompileMetrics>> {
+        self.metrics.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// A precompile whose calls are recorded by a [`PrecompileProfiler`].
+#[derive(Debug)]
+struct ProfiledPrecompile {
+    precompile: DynPrecompile,
+    profiler: PrecompileProfiler,
+}
+
+impl Precompile for ProfiledPrecompile {
+    fn precompile_id(&self) -> &PrecompileId {
+        self.precompile.precompile_id()
+    }
+
+    fn call(&self, input: PrecompileInput<'_>) -> PrecompileResult {
+        let (input_len, gas_limit) = (input.data.len(), input.gas);
+
+        let start = Instant::now();
+        let result = self.precompile.call(input);
+        let elapsed = start.elapsed();
+
+        self.profiler
+            .metrics()
+            .entry(self.precompile_id().clone())
+            .or_default()
+            .record(input_len, gas_limit, &result, elapsed);
+        result
+    }
+
+    fn is_pure(&self) -> bool {
+        self.precompile.is_pure()
+    }
+}
+
+impl PrecompilesMap {
+    /// Records metrics of all precompile calls in the given profiler.
+    ///
+    /// See [`PrecompileProfiler`]. Builtin precompiles rebuilt for a new spec are profiled as
+    /// well, see [`PrecompilesMap::wrap_precompiles`].
+    pub fn profile_precompiles(&mut self, profiler: &PrecompileProfiler) {
+        let profiler = profiler.clone();
+        self.wrap_precompiles(move |_, precompile| profiler.wrap(precompile));
+    }
+
+    /// Builder-style method that records metrics of all precompile calls in the given profiler.
+    ///
+    /// This is a consuming version of [`profile_precompiles`](Self::profile_precompiles) that
+    /// returns `Self`.
+    pub fn with_precompile_profiler(mut self, profiler: &PrecompileProfiler) -> Self {
+        self.profile_precompiles(profiler);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{eth::EthEvmContext, EvmInternals};
+    use alloy_primitives::{address, Address, U256};
+    use revm::{database::EmptyDB, precompile::PrecompileError, primitives::hardfork::SpecId};
+
+    fn call(precompiles: &PrecompilesMap, address: Address, data: &[u8], gas: u64) {
+        let mut ctx = EthEvmContext::new(EmptyDB::default(), Default::default());
+        let _ = precompiles.get(&address).unwrap().call(PrecompileInput {
+            data,
+            gas,
+            caller: Address::ZERO,
+            value: U256::ZERO,
+            internals: EvmInternals::new(&mut ctx.journaled_state, &ctx.block),
+            target_address: address,
+            bytecode_address: address,
+        });
+    }
+
+    #[test]
+    fn records_metrics_per_precompile() {
+        let identity = address!("0x0000000000000000000000000000000000000004");
+        let sha256 = address!("0x0000000000000000000000000000000000000002");
+        let profiler = PrecompileProfiler::new();
+        let precompiles =
+            PrecompilesMap::for_spec(SpecId::CANCUN).with_precompile_profiler(&profiler);
+
+        call(&precompiles, identity, &[0; 32], 1_000);
+        call(&precompiles, identity, &[0; 64], 1_000);
+        call(&precompiles, sha256, &[0; 32], 10);
+
+        let snapshot = profiler.snapshot();
+        let identity_metrics = snapshot.get(&PrecompileId::Identity).unwrap();
+        assert_eq!(identity_metrics.calls, 2);
+        assert_eq!(identity_metrics.input_bytes, 96);
+        // 15 + 3 per word
+        assert_eq!(identity_metrics.gas_used, 18 + 21);
+        assert_eq!(identity_metrics.out_of_gas, 0);
+
+        let sha256_metrics = snapshot.get(&PrecompileId::Sha256).unwrap();
+        assert_eq!(sha256_metrics.calls, 1);
+        assert_eq!(sha256_metrics.out_of_gas, 1);
+        assert_eq!(sha256_metrics.gas_used, 10);
+
+        assert_eq!(snapshot.by_gas_used()[0].0, &PrecompileId::Identity);
+        assert_eq!(snapshot.total().calls, 3);
+
+        assert_eq!(profiler.take_snapshot(), snapshot);
+        assert!(profiler.snapshot().precompiles.is_empty());
+    }
+
+    #[test]
+    fn records_failures() {
+        let address = address!("0x0000000000000000000000000000000000001000");
+        let profiler = PrecompileProfiler::new();
+        let mut precompiles = PrecompilesMap::for_spec(SpecId::CANCUN);
+        precompiles.apply_precompile(&address, |_| {
+            Some(DynPrecompile::new(PrecompileId::Custom("failing".into()), |_input| {
+                Err(PrecompileError::Other("failure".into()))
+            }))
+        });
+        precompiles.profile_precompiles(&profiler);
+
+        call(&precompiles, address, &[], 100);
+
+        let snapshot = profiler.snapshot();
+        let metrics = snapshot.get(&PrecompileId::Custom("failing".into())).unwrap();
+        assert_eq!(metrics.failures, 1);
+        assert_eq!(metrics.gas_used, 100);
+    }
+}
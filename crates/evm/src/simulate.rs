@@ -0,0 +1,552 @@
+//! Multi-block simulation, as used by `eth_simulateV1`.
+//!
+//! [`BlockSimulator`] executes a sequence of [`SimulatedBlock`]s on top of a parent block. Every
+//! block inherits its environment from the previous one, can be adjusted with
+//! [`ExtendedBlockOverrides`] and [`StateOverride`]s, and is executed with a [`BlockExecutorFactory`].
+//! Hashes of simulated blocks are made available to `BLOCKHASH` in subsequent blocks.
+
+use crate::{
+    block::{BlockExecutionError, BlockExecutionResult, BlockExecutor, BlockExecutorFactory},
+    eth::EthBlockExecutionCtx,
+    overrides::{
+        apply_extended_block_overrides, apply_state_overrides_with_precompiles,
+        ExtendedBlockOverrides, OverrideBlockHashes, StateOverrideError,
+    },
+    precompiles::PrecompilesMap,
+    Database, Evm, EvmEnv, EvmFactory, FromRecoveredTx,
+};
+use alloc::{borrow::Cow, collections::BTreeMap, vec::Vec};
+use alloy_consensus::{transaction::Recovered, Header, TxReceipt};
+use alloy_eips::{eip1559::BaseFeeParams, eip4895::Withdrawals, eip7840::BlobParams};
+use alloy_primitives::{Log, B256, U256};
+use alloy_rpc_types_eth::state::StateOverride;
+use core::borrow::BorrowMut;
+use revm::{
+    context::{BlockEnv, CfgEnv},
+    context_interface::block::BlobExcessGasAndPrice,
+    database::{states::bundle_state::BundleRetention, BundleState, State},
+};
+
+/// A block to simulate.
+#[derive(Debug, Clone)]
+pub struct SimulatedBlock<T> {
+    /// Overrides of the block environment inherited from the previous block.
+    pub block_overrides: Option<ExtendedBlockOverrides>,
+    /// State overrides applied before executing the block.
+    pub state_overrides: Option<StateOverride>,
+    /// Transactions to execute.
+    pub calls: Vec<Recovered<T>>,
+}
+
+impl<T> SimulatedBlock<T> {
+    /// Creates a new [`SimulatedBlock`] executing the given transactions without overrides.
+    pub const fn new(calls: Vec<Recovered<T>>) -> Self {
+        Self { block_overrides: None, state_overrides: None, calls }
+    }
+
+    /// Sets the block overrides.
+    pub fn with_block_overrides(mut self, overrides: impl Into<ExtendedBlockOverrides>) -> Self {
+        self.block_overrides = Some(overrides.into());
+        self
+    }
+
+    /// Sets the state overrides.
+    pub fn with_state_overrides(mut self, overrides: StateOverride) -> Self {
+        self.state_overrides = Some(overrides);
+        self
+    }
+}
+
+/// The block simulated blocks are built on top of.
+#[derive(Debug, Clone)]
+pub struct SimulationParent<Spec> {
+    /// Hash of the parent block.
+    pub hash: B256,
+    /// Gas used by the parent block, used to derive the base fee of the first simulated block.
+    pub gas_used: u64,
+    /// Blob gas used by the parent block, used to derive the excess blob gas of the first
+    /// simulated block.
+    pub blob_gas_used: u64,
+    /// Environment of the parent block.
+    ///
+    /// The [`CfgEnv`] is used for all simulated blocks.
+    pub evm_env: EvmEnv<Spec>,
+}
+
+/// Information about a simulated block passed to the execution context builder of
+/// [`BlockSimulator::simulate`].
+#[derive(Debug, Clone)]
+pub struct SimulatedBlockInfo {
+    /// Hash of the parent block.
+    pub parent_hash: B256,
+    /// Environment of the block.
+    pub block_env: BlockEnv,
+    /// Overridden parent beacon block root.
+    pub parent_beacon_block_root: Option<B256>,
+    /// Overridden withdrawals.
+    pub withdrawals: Option<Withdrawals>,
+}
+
+impl SimulatedBlockInfo {
+    /// Returns the [`EthBlockExecutionCtx`] of the block.
+    pub fn eth_execution_ctx(&self) -> EthBlockExecutionCtx<'_> {
+        EthBlockExecutionCtx {
+            parent_hash: self.parent_hash,
+            parent_beacon_block_root: self.parent_beacon_block_root,
+            ommers: &[],
+            withdrawals: self.withdrawals.as_ref().map(Cow::Borrowed),
+        }
+    }
+}
+
+/// Outcome of a simulated block.
+#[derive(Debug, Clone)]
+pub struct SimulatedBlockResult<R> {
+    /// Hash of the simulated block, see [`BlockSimulator`].
+    pub hash: B256,
+    /// Hash of the parent block.
+    pub parent_hash: B256,
+    /// Environment the block was executed with.
+    pub block_env: BlockEnv,
+    /// Receipts, requests and gas used of the block.
+    pub result: BlockExecutionResult<R>,
+    /// State changes of the block.
+    pub state: BundleState,
+}
+
+impl<R: TxReceipt<Log = Log>> SimulatedBlockResult<R> {
+    /// Returns all logs emitted in the block.
+    pub fn logs(&self) -> impl Iterator<Item = &Log> {
+        self.result.receipts.iter().flat_map(|receipt| receipt.logs())
+    }
+}
+
+/// Errors that can occur during simulation.
+#[derive(Debug, thiserror::Error)]
+pub enum SimulationError<E> {
+    /// Block numbers of simulated blocks must be increasing.
+    #[error("block number {number} is not greater than the previous block number {previous}")]
+    BlockNumberNotIncreasing {
+        /// Number of the simulated block.
+        number: u64,
+        /// Number of the previous block.
+        previous: u64,
+    },
+    /// Timestamps of simulated blocks must be increasing.
+    #[error("block timestamp {timestamp} is not greater than the previous timestamp {previous}")]
+    TimestampNotIncreasing {
+        /// Timestamp of the simulated block.
+        timestamp: u64,
+        /// Timestamp of the previous block.
+        previous: u64,
+    },
+    /// Applying state overrides failed.
+    #[error(transparent)]
+    StateOverride(#[from] StateOverrideError<E>),
+    /// Block execution failed.
+    #[error(transparent)]
+    Execution(#[from] BlockExecutionError),
+}
+
+/// Simulates chains of blocks with a [`BlockExecutorFactory`].
+///
+/// Simulated blocks aren't assembled, so their hashes are computed from a [`Header`] containing
+/// only the fields known after execution: parent hash, beneficiary, difficulty, number, gas
+/// limit, gas used, timestamp, mix hash and base fee. The hashes are consistent within a
+/// simulation and are returned by `BLOCKHASH` in subsequent simulated blocks.
+#[derive(Debug, Clone)]
+pub struct BlockSimulator<'a, F> {
+    factory: &'a F,
+    validation: bool,
+    timestamp_increment: u64,
+    base_fee_params: BaseFeeParams,
+    blob_params: BlobParams,
+}
+
+impl<'a, F: BlockExecutorFactory> BlockSimulator<'a, F> {
+    /// Creates a new [`BlockSimulator`] with validation disabled.
+    ///
+    /// Timestamps increase by 12 seconds per block, the base fee follows Ethereum mainnet
+    /// parameters and the blob base fee follows Prague parameters.
+    pub fn new(factory: &'a F) -> Self {
+        let timestamp_increment = if cfg!(feature = "timestamp-in-seconds") { 12 } else { 12_000 };
+        Self {
+            factory,
+            validation: false,
+            timestamp_increment,
+            base_fee_params: BaseFeeParams::ethereum(),
+            blob_params: BlobParams::prague(),
+        }
+    }
+
+    /// Enables or disables validation.
+    ///
+    /// Without validation, the base fee defaults to zero and base fee and nonce checks are
+    /// disabled. With validation, the base fee is derived from the parent block.
+    pub const fn with_validation(mut self, validation: bool) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    /// Sets the timestamp increment between blocks without timestamp overrides.
+    ///
+    /// The increment uses the unit of [`BlockEnv::timestamp`].
+    pub const fn with_timestamp_increment(mut self, increment: u64) -> Self {
+        self.timestamp_increment = increment;
+        self
+    }
+
+    /// Sets the parameters used to derive the base fee when validation is enabled.
+    pub const fn with_base_fee_params(mut self, base_fee_params: BaseFeeParams) -> Self {
+        self.base_fee_params = base_fee_params;
+        self
+    }
+
+    /// Sets the parameters used to derive the excess blob gas and the blob base fee of simulated
+    /// blocks.
+    pub const fn with_blob_params(mut self, blob_params: BlobParams) -> Self {
+        self.blob_params = blob_params;
+        self
+    }
+
+    /// Simulates the given blocks on top of `parent`.
+    ///
+    /// The state of every block is committed to `db` before the next one is executed, the
+    /// [`State`] needs bundle updates enabled for [`SimulatedBlockResult::state`] to be
+    /// populated.
+    ///
+    /// `execution_ctx` builds the [`BlockExecutorFactory::ExecutionCtx`] of every block, see
+    /// [`SimulatedBlockInfo::eth_execution_ctx`].
+    ///
+    /// Precompiles moved by the state overrides of a block are only moved for that block, the
+    /// state overrides themselves persist in `db`.
+    pub fn simulate<DB, C>(
+        &self,
+        db: &mut State<DB>,
+        parent: SimulationParent<<F::EvmFactory as EvmFactory>::Spec>,
+        blocks: impl IntoIterator<Item = SimulatedBlock<F::Transaction>>,
+        mut execution_ctx: C,
+    ) -> Result<Vec<SimulatedBlockResult<F::Receipt>>, SimulationError<DB::Error>>
+    where
+        DB: Database,
+        C: for<'b> FnMut(&'b SimulatedBlockInfo) -> F::ExecutionCtx<'b>,
+        <F::EvmFactory as EvmFactory>::Tx: FromRecoveredTx<F::Transaction>,
+        for<'b> <F::EvmFactory as EvmFactory>::Precompiles<&'b mut State<DB>>:
+            BorrowMut<PrecompilesMap>,
+    {
+        let SimulationParent { hash, gas_used, blob_gas_used, evm_env } = parent;
+        let EvmEnv { cfg_env, block_env: mut previous } = evm_env;
+        let cfg_env = self.cfg_env(cfg_env);
+
+        let mut parent_hash = hash;
+        let mut parent_gas_used = gas_used;
+        let mut parent_blob_gas_used = blob_gas_used;
+        db.override_block_hashes(BTreeMap::from([(previous.number.saturating_to(), hash)]));
+
+        let mut results = Vec::new();
+        for block in blocks {
+            let mut block_env =
+                self.next_block_env(&previous, parent_gas_used, parent_blob_gas_used);
+            let ctx_overrides = block
+                .block_overrides
+                .map(|overrides| {
+                    apply_extended_block_overrides(overrides, db, &mut block_env, &self.blob_params)
+                })
+                .unwrap_or_default();
+            ensure_increasing(&previous, &block_env)?;
+
+            let info = SimulatedBlockInfo {
+                parent_hash,
+                block_env: block_env.clone(),
+                parent_beacon_block_root: ctx_overrides.parent_beacon_block_root,
+                withdrawals: ctx_overrides.withdrawals,
+            };
+            let mut evm = self.factory.evm_factory().create_evm(
+                &mut *db,
+                EvmEnv { cfg_env: cfg_env.clone(), block_env: block_env.clone() },
+            );
+            if let Some(overrides) = block.state_overrides {
+                let (db, _, precompiles) = evm.components_mut();
+                let precompiles = BorrowMut::<PrecompilesMap>::borrow_mut(precompiles);
+                apply_state_overrides_with_precompiles(overrides, &mut **db, precompiles)?;
+            }
+            let result = self
+                .factory
+                .create_executor(evm, execution_ctx(&info))
+                .execute_block(block.calls.iter())?;
+
+            db.merge_transitions(BundleRetention::Reverts);
+            let state = db.take_bundle();
+
+            let hash = simulated_block_hash(parent_hash, &block_env, result.gas_used);
+            db.override_block_hashes(BTreeMap::from([(block_env.number.saturating_to(), hash)]));
+
+            parent_gas_used = result.gas_used;
+            parent_blob_gas_used = result.blob_gas_used;
+            results.push(SimulatedBlockResult {
+                hash,
+                parent_hash,
+                block_env: block_env.clone(),
+                result,
+                state,
+            });
+            parent_hash = hash;
+            previous = block_env;
+        }
+
+        Ok(results)
+    }
+
+    /// Adjusts the configuration of the parent block for simulation.
+    fn cfg_env<Spec>(&self, mut cfg_env: CfgEnv<Spec>) -> CfgEnv<Spec> {
+        // Simulated calls may be sent from accounts with code.
+        cfg_env.disable_eip3607 = true;
+        if !self.validation {
+            cfg_env.disable_base_fee = true;
+            cfg_env.disable_nonce_check = true;
+        }
+        cfg_env
+    }
+
+    /// Derives the default environment of the block following `previous`.
+    ///
+    /// The excess blob gas is only derived if `previous` has one, i.e. if blobs are enabled.
+    fn next_block_env(
+        &self,
+        previous: &BlockEnv,
+        previous_gas_used: u64,
+        previous_blob_gas_used: u64,
+    ) -> BlockEnv {
+        let basefee = if self.validation {
+            self.base_fee_params.next_block_base_fee(
+                previous_gas_used,
+                previous.gas_limit,
+                previous.basefee,
+            )
+        } else {
+            0
+        };
+        let blob_excess_gas_and_price = previous.blob_excess_gas_and_price.map(|previous| {
+            let excess_blob_gas = previous
+                .excess_blob_gas
+                .saturating_add(previous_blob_gas_used)
+                .saturating_sub(self.blob_params.target_blob_gas_per_block());
+            BlobExcessGasAndPrice {
+                excess_blob_gas,
+                blob_gasprice: self.blob_params.calc_blob_fee(excess_blob_gas),
+            }
+        });
+
+        BlockEnv {
+            number: previous.number.saturating_add(U256::from(1)),
+            timestamp: previous.timestamp.saturating_add(U256::from(self.timestamp_increment)),
+            basefee,
+            blob_excess_gas_and_price,
+            ..previous.clone()
+        }
+    }
+}
+
+/// Ensures that block number and timestamp increase.
+fn ensure_increasing<E>(
+    previous: &BlockEnv,
+    block_env: &BlockEnv,
+) -> Result<(), SimulationError<E>> {
+    if block_env.number <= previous.number {
+        return Err(SimulationError::BlockNumberNotIncreasing {
+            number: block_env.number.saturating_to(),
+            previous: previous.number.saturating_to(),
+        });
+    }
+    if block_env.timestamp <= previous.timestamp {
+        return Err(SimulationError::TimestampNotIncreasing {
+            timestamp: block_env.timestamp.saturating_to(),
+            previous: previous.timestamp.saturating_to(),
+        });
+    }
+    Ok(())
+}
+
+/// Computes the hash identifying a simulated block, see [`BlockSimulator`].
+fn simulated_block_hash(parent_hash: B256, block_env: &BlockEnv, gas_used: u64) -> B256 {
+    Header {
+        parent_hash,
+        beneficiary: block_env.beneficiary,
+        difficulty: block_env.difficulty,
+        number: block_env.number.saturating_to(),
+        gas_limit: block_env.gas_limit,
+        gas_used,
+        timestamp: block_env.timestamp.saturating_to(),
+        mix_hash: block_env.prevrandao.unwrap_or_default(),
+        base_fee_per_gas: Some(block_env.basefee),
+        ..Default::default()
+    }
+    .hash_slow()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{factory, tx, SENDER};
+    use alloy_consensus::TxEnvelope;
+    use alloy_eips::eip4844::DATA_GAS_PER_BLOB;
+    use alloy_primitives::{address, bytes, Address};
+    use alloy_rpc_types_eth::{state::AccountOverride, BlockOverrides};
+    use revm::database::{CacheDB, EmptyDB};
+
+    const CONTRACT: Address = address!("0x000000000000000000000000000000000000c0de");
+
+    #[test]
+    fn links_simulated_blocks() {
+        let factory = factory();
+        let mut db = State::builder()
+            .with_database(CacheDB::new(EmptyDB::new()))
+            .with_bundle_update()
+            .build();
+
+        let parent = SimulationParent {
+            hash: B256::repeat_byte(0x11),
+            gas_used: 0,
+            blob_gas_used: 0,
+            evm_env: EvmEnv::new(
+                CfgEnv::default(),
+                BlockEnv {
+                    number: U256::from(20_000_000),
+                    timestamp: U256::from(1_000_000),
+                    gas_limit: 30_000_000,
+                    ..Default::default()
+                },
+            ),
+        };
+
+        // Stores BLOCKHASH(NUMBER - 1) in slot 0 and TIMESTAMP in slot 1:
+        // PUSH1 1 NUMBER SUB BLOCKHASH PUSH1 0 SSTORE TIMESTAMP PUSH1 1 SSTORE STOP
+        let code = bytes!("0x60014303406000554260015500");
+        let blocks = vec![
+            SimulatedBlock::new(vec![tx(SENDER, 0, CONTRACT)]).with_state_overrides(
+                StateOverride::from_iter([(CONTRACT, AccountOverride::default().with_code(code))]),
+            ),
+            SimulatedBlock::new(vec![tx(SENDER, 1, CONTRACT)]).with_block_overrides(
+                BlockOverrides { time: Some(5_000_000), ..Default::default() },
+            ),
+        ];
+
+        let simulator = BlockSimulator::new(&factory);
+        let results =
+            simulator.simulate(&mut db, parent, blocks, |info| info.eth_execution_ctx()).unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].parent_hash, B256::repeat_byte(0x11));
+        assert_eq!(results[1].parent_hash, results[0].hash);
+        assert_eq!(
+            results[0].block_env.timestamp,
+            U256::from(1_000_000 + simulator.timestamp_increment)
+        );
+        assert_eq!(results[1].block_env.number, U256::from(20_000_002));
+
+        let storage = |block: usize, slot: u64| {
+            results[block].state.account(&CONTRACT).unwrap().storage_slot(U256::from(slot))
+        };
+        assert_eq!(storage(0, 0), Some(U256::from_be_bytes(B256::repeat_byte(0x11).0)));
+        assert_eq!(storage(1, 0), Some(U256::from_be_bytes(results[0].hash.0)));
+        assert_eq!(storage(1, 1), Some(U256::from(5_000_000)));
+    }
+
+    #[test]
+    fn advances_excess_blob_gas() {
+        let factory = factory();
+        let mut db = State::builder().with_database(CacheDB::new(EmptyDB::new())).build();
+
+        let blob_params = BlobParams::prague();
+        let parent = SimulationParent {
+            hash: B256::ZERO,
+            gas_used: 0,
+            // 9 blobs, 3 above the Prague target
+            blob_gas_used: 9 * DATA_GAS_PER_BLOB,
+            evm_env: EvmEnv::new(
+                CfgEnv::default(),
+                BlockEnv {
+                    timestamp: U256::from(1_000_000),
+                    blob_excess_gas_and_price: Some(BlobExcessGasAndPrice {
+                        excess_blob_gas: 0,
+                        blob_gasprice: 1,
+                    }),
+                    ..Default::default()
+                },
+            ),
+        };
+        let blocks = vec![SimulatedBlock::<TxEnvelope>::new(vec![]); 2];
+
+        let results = BlockSimulator::new(&factory)
+            .with_blob_params(blob_params)
+            .simulate(&mut db, parent, blocks, |info| info.eth_execution_ctx())
+            .unwrap();
+
+        let blob = |block: usize| results[block].block_env.blob_excess_gas_and_price.unwrap();
+        assert_eq!(blob(0).excess_blob_gas, 3 * DATA_GAS_PER_BLOB);
+        assert_eq!(blob(0).blob_gasprice, blob_params.calc_blob_fee(3 * DATA_GAS_PER_BLOB));
+        // no blobs in the first simulated block
+        assert_eq!(blob(1).excess_blob_gas, 0);
+        assert_eq!(blob(1).blob_gasprice, 1);
+    }
+
+    #[test]
+    fn moves_precompiles() {
+        let factory = factory();
+        let mut db = State::builder()
+            .with_database(CacheDB::new(EmptyDB::new()))
+            .with_bundle_update()
+            .build();
+
+        let parent = SimulationParent {
+            hash: B256::ZERO,
+            gas_used: 0,
+            blob_gas_used: 0,
+            evm_env: EvmEnv::new(
+                CfgEnv::default(),
+                BlockEnv {
+                    timestamp: U256::from(1_000_000),
+                    gas_limit: 30_000_000,
+                    ..Default::default()
+                },
+            ),
+        };
+        let identity = address!("0x0000000000000000000000000000000000000004");
+        let blocks = vec![SimulatedBlock::new(vec![tx(SENDER, 0, CONTRACT)]).with_state_overrides(
+            StateOverride::from_iter([(
+                identity,
+                AccountOverride { move_precompile_to: Some(CONTRACT), ..Default::default() },
+            )]),
+        )];
+
+        let results = BlockSimulator::new(&factory)
+            .simulate(&mut db, parent, blocks, |info| info.eth_execution_ctx())
+            .unwrap();
+
+        // the call to `CONTRACT` runs the identity precompile, which costs 15 gas for no input
+        assert_eq!(results[0].result.gas_used, 21_015);
+    }
+
+    #[test]
+    fn rejects_decreasing_timestamps() {
+        let factory = factory();
+        let mut db = State::builder().with_database(CacheDB::new(EmptyDB::new())).build();
+
+        let parent = SimulationParent {
+            hash: B256::ZERO,
+            gas_used: 0,
+            blob_gas_used: 0,
+            evm_env: EvmEnv::new(
+                CfgEnv::default(),
+                BlockEnv { timestamp: U256::from(1_000_000), ..Default::default() },
+            ),
+        };
+        let blocks = vec![SimulatedBlock::<TxEnvelope>::new(vec![])
+            .with_block_overrides(BlockOverrides { time: Some(1_000), ..Default::default() })];
+
+        let err = BlockSimulator::new(&factory)
+            .simulate(&mut db, parent, blocks, |info| info.eth_execution_ctx())
+            .unwrap_err();
+        assert!(matches!(err, SimulationError::TimestampNotIncreasing { .. }));
+    }
+}
@@ -0,0 +1,79 @@
+//! Fixtures shared by the tests of the crate.
+
+use crate::{
+    eth::{
+        receipt_builder::AlloyReceiptBuilder, spec::EthSpec, EthBlockExecutionCtx,
+        EthBlockExecutorFactory,
+    },
+    EthEvmFactory, EvmEnv,
+};
+use alloy_consensus::{transaction::Recovered, Signed, TxEnvelope, TxLegacy};
+use alloy_primitives::{address, hex, Address, Bytes, Signature, TxKind, B256, U256};
+use revm::{
+    context::{BlockEnv, CfgEnv},
+    database::{CacheDB, EmptyDB, State},
+    state::{AccountInfo, Bytecode},
+};
+
+/// Sender of the test transactions.
+pub(crate) const SENDER: Address = address!("0x000000000000000000000000000000000000a11c");
+
+/// Address of the [`COUNTER_CODE`] contract in [`state`].
+pub(crate) const COUNTER: Address = address!("0x000000000000000000000000000000000000c0de");
+
+/// Code incrementing storage slot 0.
+pub(crate) const COUNTER_CODE: &[u8] = &hex!("0x60005460010160005500");
+
+/// Executor factory for Ethereum mainnet.
+pub(crate) fn factory() -> EthBlockExecutorFactory {
+    EthBlockExecutorFactory::new(AlloyReceiptBuilder, EthSpec::mainnet(), EthEvmFactory)
+}
+
+/// Environment of block 20 000 000 of mainnet, with all other fields set to their defaults.
+pub(crate) fn evm_env() -> EvmEnv {
+    EvmEnv::new(
+        CfgEnv::default(),
+        BlockEnv { number: U256::from(20_000_000), ..Default::default() },
+    )
+}
+
+/// Execution context of a block without ommers and withdrawals.
+pub(crate) const EXECUTION_CTX: EthBlockExecutionCtx<'static> = EthBlockExecutionCtx {
+    parent_hash: B256::ZERO,
+    parent_beacon_block_root: None,
+    ommers: &[],
+    withdrawals: None,
+};
+
+/// Returns [`EXECUTION_CTX`] for any environment.
+pub(crate) const fn execution_ctx(_: &EvmEnv) -> EthBlockExecutionCtx<'_> {
+    EXECUTION_CTX
+}
+
+/// Signs the given transaction as `sender`, with a test signature.
+pub(crate) fn sign(tx: TxLegacy, sender: Address) -> Recovered<TxEnvelope> {
+    let tx = Signed::new_unchecked(tx, Signature::test_signature(), B256::ZERO);
+    Recovered::new_unchecked(TxEnvelope::Legacy(tx), sender)
+}
+
+/// A call from `sender` to `to` with a gas limit of 100 000 and no fees.
+pub(crate) fn tx(sender: Address, nonce: u64, to: Address) -> Recovered<TxEnvelope> {
+    sign(TxLegacy { nonce, gas_limit: 100_000, to: TxKind::Call(to), ..Default::default() }, sender)
+}
+
+/// A contract account with the given code.
+pub(crate) fn contract(code: Bytes) -> AccountInfo {
+    AccountInfo { code: Some(Bytecode::new_raw(code)), ..Default::default() }
+}
+
+/// State with the [`COUNTER`] contract and the given accounts, with bundle updates enabled.
+pub(crate) fn state(
+    accounts: impl IntoIterator<Item = (Address, AccountInfo)>,
+) -> State<CacheDB<EmptyDB>> {
+    let mut cache_db = CacheDB::new(EmptyDB::new());
+    cache_db.insert_account_info(COUNTER, contract(Bytes::from_static(COUNTER_CODE)));
+    for (address, info) in accounts {
+        cache_db.insert_account_info(address, info);
+    }
+    State::builder().with_database(cache_db).with_bundle_update().build()
+}
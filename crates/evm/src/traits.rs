@@ -3,11 +3,12 @@
 use crate::Database;
 use alloc::boxed::Box;
 use alloy_primitives::{Address, Log, B256, U256};
-use core::{error::Error, fmt, fmt::Debug};
+use core::{any::Any, error::Error, fmt, fmt::Debug};
 use revm::{
-    context::{Block, DBErrorMarker, JournalTr},
+    context::{Block, Cfg, CfgEnv, DBErrorMarker, JournalTr, Transaction},
+    context_interface::journaled_state::{JournalCheckpoint, TransferError},
     interpreter::{SStoreResult, StateLoad},
-    primitives::{StorageKey, StorageValue},
+    primitives::{hardfork::SpecId, StorageKey, StorageValue},
     state::{Account, AccountInfo, Bytecode},
 };
 
@@ -73,6 +74,35 @@ trait EvmInternalsTr: Database<Error = ErasedError> + Debug {
     ) -> Result<StateLoad<SStoreResult>, EvmInternalsError>;
 
     fn log(&mut self, log: Log);
+
+    fn transfer(
+        &mut self,
+        from: Address,
+        to: Address,
+        value: U256,
+    ) -> Result<Option<TransferError>, EvmInternalsError>;
+
+    fn balance_incr(&mut self, address: Address, value: U256) -> Result<(), EvmInternalsError>;
+
+    fn bump_nonce(&mut self, address: Address) -> Result<Option<u64>, EvmInternalsError>;
+
+    fn create_account_checkpoint(
+        &mut self,
+        caller: Address,
+        address: Address,
+        value: U256,
+        spec_id: SpecId,
+    ) -> Result<JournalCheckpoint, TransferError>;
+
+    fn tload(&mut self, address: Address, key: StorageKey) -> StorageValue;
+
+    fn tstore(&mut self, address: Address, key: StorageKey, value: StorageValue);
+
+    fn checkpoint(&mut self) -> JournalCheckpoint;
+
+    fn checkpoint_commit(&mut self);
+
+    fn checkpoint_revert(&mut self, checkpoint: JournalCheckpoint);
 }
 
 /// Helper internal struct for implementing [`EvmInternals`].
@@ -152,21 +182,191 @@ where
     fn log(&mut self, log: Log) {
         self.0.log(log);
     }
+
+    fn transfer(
+        &mut self,
+        from: Address,
+        to: Address,
+        value: U256,
+    ) -> Result<Option<TransferError>, EvmInternalsError> {
+        self.0.transfer(from, to, value).map_err(EvmInternalsError::database)
+    }
+
+    fn balance_incr(&mut self, address: Address, value: U256) -> Result<(), EvmInternalsError> {
+        self.0.balance_incr(address, value).map_err(EvmInternalsError::database)
+    }
+
+    fn bump_nonce(&mut self, address: Address) -> Result<Option<u64>, EvmInternalsError> {
+        let account = self.0.load_account(address).map_err(EvmInternalsError::database)?.data;
+        let Some(nonce) = account.info.nonce.checked_add(1) else { return Ok(None) };
+        account.info.nonce = nonce;
+        self.0.nonce_bump_journal_entry(address);
+        self.0.touch_account(address);
+        Ok(Some(nonce))
+    }
+
+    fn create_account_checkpoint(
+        &mut self,
+        caller: Address,
+        address: Address,
+        value: U256,
+        spec_id: SpecId,
+    ) -> Result<JournalCheckpoint, TransferError> {
+        self.0.create_account_checkpoint(caller, address, value, spec_id)
+    }
+
+    fn tload(&mut self, address: Address, key: StorageKey) -> StorageValue {
+        self.0.tload(address, key)
+    }
+
+    fn tstore(&mut self, address: Address, key: StorageKey, value: StorageValue) {
+        self.0.tstore(address, key, value);
+    }
+
+    fn checkpoint(&mut self) -> JournalCheckpoint {
+        self.0.checkpoint()
+    }
+
+    fn checkpoint_commit(&mut self) {
+        self.0.checkpoint_commit();
+    }
+
+    fn checkpoint_revert(&mut self, checkpoint: JournalCheckpoint) {
+        self.0.checkpoint_revert(checkpoint);
+    }
+}
+
+/// Read-only information about the executed transaction, see [`EvmInternals::tx`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxInfo {
+    /// Sender of the transaction, i.e. `tx.origin`.
+    pub origin: Address,
+    /// Effective gas price of the transaction.
+    pub gas_price: u128,
+    /// Gas limit of the transaction.
+    pub gas_limit: u64,
+    /// Type of the transaction.
+    pub tx_type: u8,
+}
+
+impl TxInfo {
+    /// Creates the [`TxInfo`] of a transaction executed in a block with the given base fee.
+    pub fn new(tx: &impl Transaction, basefee: u64) -> Self {
+        Self {
+            origin: tx.caller(),
+            gas_price: tx.effective_gas_price(basefee as u128),
+            gas_limit: tx.gas_limit(),
+            tx_type: tx.tx_type(),
+        }
+    }
+}
+
+/// Read-only information about the EVM configuration, see [`EvmInternals::cfg`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CfgInfo {
+    /// Chain id.
+    pub chain_id: u64,
+    /// Ethereum hardfork the EVM behaves like.
+    ///
+    /// Chain specific specs are converted to their Ethereum equivalent, so different hardforks
+    /// of a chain may have the same [`SpecId`]. See [`EvmInternals::spec`] for the spec of the
+    /// EVM itself.
+    pub spec_id: SpecId,
+}
+
+impl CfgInfo {
+    /// Creates the [`CfgInfo`] of the given configuration.
+    pub fn new(cfg: &impl Cfg) -> Self {
+        Self { chain_id: cfg.chain_id(), spec_id: cfg.spec().into() }
+    }
 }
 
 /// Helper type exposing hooks into EVM and access to evm internal settings.
 pub struct EvmInternals<'a> {
     internals: Box<dyn EvmInternalsTr + 'a>,
     block_env: &'a (dyn Block + 'a),
+    tx: Option<TxInfo>,
+    cfg: Option<CfgInfo>,
+    cfg_env: Option<&'a dyn Any>,
 }
 
 impl<'a> EvmInternals<'a> {
     /// Creates a new [`EvmInternals`] instance.
+    ///
+    /// Transaction and configuration information can be provided with [`Self::with_tx_env`] and
+    /// [`Self::with_cfg_env`]. [`PrecompilesMap`](crate::precompiles::PrecompilesMap) provides
+    /// both, so they are available to the precompiles of all EVMs calling their precompiles
+    /// through it, including Ethereum, OP and seismic EVMs.
     pub fn new<T>(journal: &'a mut T, block_env: &'a dyn Block) -> Self
     where
         T: JournalTr<Database: Database> + Debug,
     {
-        Self { internals: Box::new(EvmInternalsImpl(journal)), block_env }
+        Self {
+            internals: Box::new(EvmInternalsImpl(journal)),
+            block_env,
+            tx: None,
+            cfg: None,
+            cfg_env: None,
+        }
+    }
+
+    /// Sets the transaction information from the given transaction environment.
+    pub fn with_tx_env(mut self, tx_env: &impl Transaction) -> Self {
+        self.tx = Some(TxInfo::new(tx_env, self.block_env.basefee()));
+        self
+    }
+
+    /// Sets the configuration information from the given configuration environment.
+    pub fn with_cfg_env<C: Cfg + 'static>(mut self, cfg_env: &'a C) -> Self {
+        self.cfg = Some(CfgInfo::new(cfg_env));
+        self.cfg_env = Some(cfg_env);
+        self
+    }
+
+    /// Returns information about the executed transaction, if provided.
+    pub const fn tx(&self) -> Option<&TxInfo> {
+        self.tx.as_ref()
+    }
+
+    /// Returns information about the EVM configuration, if provided.
+    pub const fn cfg(&self) -> Option<&CfgInfo> {
+        self.cfg.as_ref()
+    }
+
+    /// Returns the sender of the executed transaction, i.e. `tx.origin`, if provided.
+    pub fn tx_origin(&self) -> Option<Address> {
+        self.tx.map(|tx| tx.origin)
+    }
+
+    /// Returns the effective gas price of the executed transaction, if provided.
+    pub fn gas_price(&self) -> Option<u128> {
+        self.tx.map(|tx| tx.gas_price)
+    }
+
+    /// Returns the chain id, if provided.
+    pub fn chain_id(&self) -> Option<u64> {
+        self.cfg.map(|cfg| cfg.chain_id)
+    }
+
+    /// Returns the active hardfork, if provided.
+    ///
+    /// This is the Ethereum equivalent of the spec of the EVM, see [`Self::spec`].
+    pub fn spec_id(&self) -> Option<SpecId> {
+        self.cfg.map(|cfg| cfg.spec_id)
+    }
+
+    /// Returns the configuration environment, if provided and of type `C`.
+    pub fn cfg_env<C: 'static>(&self) -> Option<&'a C> {
+        self.cfg_env?.downcast_ref()
+    }
+
+    /// Returns the spec of the EVM, if provided and the configuration environment is a
+    /// [`CfgEnv<S>`].
+    ///
+    /// Unlike [`Self::spec_id`], this keeps chain specific hardforks apart, e.g. `OpSpecId` for
+    /// OP EVMs.
+    pub fn spec<S: Clone + 'static>(&self) -> Option<S> {
+        self.cfg_env::<CfgEnv<S>>().map(|cfg| cfg.spec.clone())
     }
 
     /// Returns the  evm's block information.
@@ -241,6 +441,73 @@ impl<'a> EvmInternals<'a> {
     pub fn log(&mut self, log: Log) {
         self.internals.log(log);
     }
+
+    /// Transfers value between two accounts.
+    ///
+    /// Returns `Some` if the transfer failed, e.g. because of insufficient balance.
+    pub fn transfer(
+        &mut self,
+        from: Address,
+        to: Address,
+        value: U256,
+    ) -> Result<Option<TransferError>, EvmInternalsError> {
+        self.internals.transfer(from, to, value)
+    }
+
+    /// Increases the balance of the account, minting the value.
+    pub fn balance_incr(&mut self, address: Address, value: U256) -> Result<(), EvmInternalsError> {
+        self.internals.balance_incr(address, value)
+    }
+
+    /// Increments the nonce of the account.
+    ///
+    /// Returns the new nonce, or `None` if the nonce would overflow.
+    pub fn bump_nonce(&mut self, address: Address) -> Result<Option<u64>, EvmInternalsError> {
+        self.internals.bump_nonce(address)
+    }
+
+    /// Creates an account at `address`, transferring `value` from `caller` to it.
+    ///
+    /// This creates a checkpoint which has to be committed with [`Self::checkpoint_commit`] or
+    /// reverted with [`Self::checkpoint_revert`]. Fails if the account already exists or the
+    /// caller has insufficient balance.
+    pub fn create_account_checkpoint(
+        &mut self,
+        caller: Address,
+        address: Address,
+        value: U256,
+        spec_id: SpecId,
+    ) -> Result<JournalCheckpoint, TransferError> {
+        self.internals.create_account_checkpoint(caller, address, value, spec_id)
+    }
+
+    /// Loads a transient storage slot.
+    pub fn tload(&mut self, address: Address, key: StorageKey) -> StorageValue {
+        self.internals.tload(address, key)
+    }
+
+    /// Stores a transient storage slot.
+    pub fn tstore(&mut self, address: Address, key: StorageKey, value: StorageValue) {
+        self.internals.tstore(address, key, value);
+    }
+
+    /// Creates a checkpoint of the journal.
+    ///
+    /// Every checkpoint has to be either committed with [`Self::checkpoint_commit`] or reverted
+    /// with [`Self::checkpoint_revert`].
+    pub fn checkpoint(&mut self) -> JournalCheckpoint {
+        self.internals.checkpoint()
+    }
+
+    /// Commits the latest checkpoint, keeping the changes made since it was created.
+    pub fn checkpoint_commit(&mut self) {
+        self.internals.checkpoint_commit();
+    }
+
+    /// Reverts all changes made since the given checkpoint was created.
+    pub fn checkpoint_revert(&mut self, checkpoint: JournalCheckpoint) {
+        self.internals.checkpoint_revert(checkpoint);
+    }
 }
 
 impl<'a> fmt::Debug for EvmInternals<'a> {
@@ -248,6 +515,87 @@ impl<'a> fmt::Debug for EvmInternals<'a> {
         f.debug_struct("EvmInternals")
             .field("internals", &self.internals)
             .field("block_env", &"{{}}")
+            .field("tx", &self.tx)
+            .field("cfg", &self.cfg)
             .finish_non_exhaustive()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eth::EthEvmContext;
+    use alloy_primitives::address;
+    use revm::database::{CacheDB, EmptyDB};
+
+    const ALICE: Address = address!("0x000000000000000000000000000000000000a11c");
+    const BOB: Address = address!("0x0000000000000000000000000000000000000b0b");
+
+    fn context() -> EthEvmContext<CacheDB<EmptyDB>> {
+        let mut db = CacheDB::new(EmptyDB::new());
+        db.insert_account_info(
+            ALICE,
+            AccountInfo { balance: U256::from(1_000), ..Default::default() },
+        );
+        EthEvmContext::new(db, SpecId::CANCUN)
+    }
+
+    #[test]
+    fn transfers_and_bumps_nonce() {
+        let mut ctx = context();
+        let mut internals = EvmInternals::new(&mut ctx.journaled_state, &ctx.block);
+
+        assert!(internals.transfer(ALICE, BOB, U256::from(400)).unwrap().is_none());
+        assert_eq!(
+            internals.transfer(ALICE, BOB, U256::from(1_000)).unwrap(),
+            Some(TransferError::OutOfFunds)
+        );
+        assert_eq!(internals.bump_nonce(ALICE).unwrap(), Some(1));
+
+        assert_eq!(internals.load_account(ALICE).unwrap().info.balance, U256::from(600));
+        assert_eq!(internals.load_account(ALICE).unwrap().info.nonce, 1);
+        assert_eq!(internals.load_account(BOB).unwrap().info.balance, U256::from(400));
+    }
+
+    #[test]
+    fn reverts_to_checkpoint() {
+        let mut ctx = context();
+        let mut internals = EvmInternals::new(&mut ctx.journaled_state, &ctx.block);
+        let key = U256::from(1);
+
+        internals.tstore(ALICE, key, U256::from(7).into());
+        let checkpoint = internals.checkpoint();
+        internals.transfer(ALICE, BOB, U256::from(400)).unwrap();
+        internals.bump_nonce(ALICE).unwrap();
+        internals.balance_incr(BOB, U256::from(1)).unwrap();
+        internals.tstore(ALICE, key, U256::from(8).into());
+        internals.checkpoint_revert(checkpoint);
+
+        assert_eq!(internals.load_account(ALICE).unwrap().info.balance, U256::from(1_000));
+        assert_eq!(internals.load_account(ALICE).unwrap().info.nonce, 0);
+        assert_eq!(internals.load_account(BOB).unwrap().info.balance, U256::ZERO);
+        assert_eq!(internals.tload(ALICE, key), U256::from(7).into());
+    }
+
+    #[test]
+    fn exposes_tx_and_cfg() {
+        let mut ctx = context();
+        ctx.tx.caller = ALICE;
+        ctx.tx.gas_price = 10;
+        ctx.cfg.chain_id = 5;
+
+        let internals = EvmInternals::new(&mut ctx.journaled_state, &ctx.block);
+        assert_eq!(internals.tx_origin(), None);
+        assert_eq!(internals.spec_id(), None);
+
+        let internals = EvmInternals::new(&mut ctx.journaled_state, &ctx.block)
+            .with_tx_env(&ctx.tx)
+            .with_cfg_env(&ctx.cfg);
+        assert_eq!(internals.tx_origin(), Some(ALICE));
+        assert_eq!(internals.gas_price(), Some(10));
+        assert_eq!(internals.chain_id(), Some(5));
+        assert_eq!(internals.spec_id(), Some(SpecId::CANCUN));
+        assert_eq!(internals.spec::<SpecId>(), Some(SpecId::CANCUN));
+        assert_eq!(internals.spec::<u8>(), None);
+    }
+}
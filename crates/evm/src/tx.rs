@@ -9,10 +9,7 @@ use alloy_consensus::{
     TxEip2930, TxEip4844, TxEip7702, TxLegacy,
 };
 use alloy_eips::{
-    eip2718::{
-        WithEncoded, EIP1559_TX_TYPE_ID, EIP2930_TX_TYPE_ID, EIP4844_TX_TYPE_ID,
-        EIP7702_TX_TYPE_ID, LEGACY_TX_TYPE_ID,
-    },
+    eip2718::WithEncoded,
     eip2930::AccessList,
     eip7702::{RecoveredAuthority, RecoveredAuthorization},
     Typed2718,
@@ -106,6 +103,55 @@ impl IntoTxEnv<TxEnv> for seismic_revm::SeismicTransaction<TxEnv> {
     }
 }
 
+/// Abstraction over transaction environments that can be modified before execution.
+///
+/// This is used by utilities that need to execute the same transaction multiple times with
+/// different parameters, e.g. gas estimation.
+pub trait TransactionEnv: revm::context_interface::Transaction + Clone {
+    /// Sets the gas limit of the transaction.
+    fn set_gas_limit(&mut self, gas_limit: u64);
+
+    /// Returns the transaction with the given gas limit.
+    fn with_gas_limit(mut self, gas_limit: u64) -> Self {
+        self.set_gas_limit(gas_limit);
+        self
+    }
+
+    /// Sets the access list of the transaction.
+    fn set_access_list(&mut self, access_list: AccessList);
+}
+
+impl TransactionEnv for TxEnv {
+    fn set_gas_limit(&mut self, gas_limit: u64) {
+        self.gas_limit = gas_limit;
+    }
+
+    fn set_access_list(&mut self, access_list: AccessList) {
+        self.access_list = access_list;
+    }
+}
+
+#[cfg(feature = "op")]
+impl<T: TransactionEnv> TransactionEnv for op_revm::OpTransaction<T> {
+    fn set_gas_limit(&mut self, gas_limit: u64) {
+        self.base.set_gas_limit(gas_limit);
+    }
+
+    fn set_access_list(&mut self, access_list: AccessList) {
+        self.base.set_access_list(access_list);
+    }
+}
+
+impl<T: TransactionEnv> TransactionEnv for seismic_revm::SeismicTransaction<T> {
+    fn set_gas_limit(&mut self, gas_limit: u64) {
+        self.base.set_gas_limit(gas_limit);
+    }
+
+    fn set_access_list(&mut self, access_list: AccessList) {
+        self.base.set_access_list(access_list);
+    }
+}
+
 /// Helper trait for building a transaction environment from a recovered transaction.
 ///
 /// This trait enables the conversion of consensus transaction types (which have been recovered
@@ -557,9 +603,9 @@ impl FromTxWithEncoded<SeismicTxEnvelope> for SeismicTransaction<TxEnv> {
     }
 }
 
-// seismic upstream merge: this is still needed?
-/// Necessary to run a test case that uses the SeismicAlloyReceiptBuilder for the SeismicEvm
 /// Necessary to include in this crate due to the orphan rule.
+///
+/// Ethereum transaction types are converted like [`EthereumTxEnvelope`] transactions.
 impl FromRecoveredTx<SeismicTxEnvelope> for SeismicTransaction<TxEnv> {
     fn from_recovered_tx(tx: &SeismicTxEnvelope, sender: Address) -> Self {
         // TODO: this should not be hardcoded
@@ -568,86 +614,13 @@ impl FromRecoveredTx<SeismicTxEnvelope> for SeismicTransaction<TxEnv> {
 
         let tx_hash = tx.tx_hash().clone();
         let base = match tx {
-            SeismicTxEnvelope::Legacy(tx) => TxEnv {
-                tx_type: LEGACY_TX_TYPE_ID,
-                caller: sender,
-                gas_limit: tx.tx().gas_limit,
-                gas_price: tx.tx().gas_price,
-                kind: tx.tx().kind(),
-                value: tx.tx().value.into(),
-                data: tx.tx().input.clone(),
-                nonce: tx.tx().nonce,
-                chain_id: tx.tx().chain_id,
-                access_list: AccessList::default(),
-                gas_priority_fee: None,
-                blob_hashes: vec![],
-                max_fee_per_blob_gas: 0,
-                authorization_list: vec![],
-            },
-            SeismicTxEnvelope::Eip2930(tx) => TxEnv {
-                tx_type: EIP2930_TX_TYPE_ID,
-                caller: sender,
-                gas_limit: tx.tx().gas_limit,
-                gas_price: tx.tx().gas_price,
-                kind: tx.tx().kind(),
-                value: tx.tx().value.into(),
-                data: tx.tx().input.clone(),
-                nonce: tx.tx().nonce,
-                chain_id: tx.tx().chain_id(),
-                access_list: AccessList::default(),
-                gas_priority_fee: None,
-                blob_hashes: vec![],
-                max_fee_per_blob_gas: 0,
-                authorization_list: vec![],
-            },
-            SeismicTxEnvelope::Eip1559(tx) => TxEnv {
-                tx_type: EIP1559_TX_TYPE_ID,
-                caller: sender,
-                gas_limit: tx.tx().gas_limit,
-                gas_price: tx.tx().gas_price().unwrap_or_default(),
-                kind: tx.tx().kind(),
-                value: tx.tx().value.into(),
-                data: tx.tx().input.clone(),
-                nonce: tx.tx().nonce,
-                chain_id: tx.tx().chain_id(),
-                access_list: AccessList::default(),
-                gas_priority_fee: None,
-                blob_hashes: vec![],
-                max_fee_per_blob_gas: 0,
-                authorization_list: vec![],
-            },
-            SeismicTxEnvelope::Eip4844(tx) => TxEnv {
-                tx_type: EIP4844_TX_TYPE_ID,
-                caller: sender,
-                gas_limit: tx.tx().gas_limit(),
-                gas_price: tx.tx().gas_price().unwrap_or_default(),
-                kind: tx.tx().kind(),
-                value: tx.tx().value().into(),
-                data: tx.tx().input().clone(),
-                nonce: tx.tx().nonce(),
-                chain_id: tx.tx().chain_id(),
-                access_list: AccessList::default(),
-                gas_priority_fee: None,
-                blob_hashes: vec![],
-                max_fee_per_blob_gas: 0,
-                authorization_list: vec![],
-            },
-            SeismicTxEnvelope::Eip7702(tx) => TxEnv {
-                tx_type: EIP7702_TX_TYPE_ID,
-                caller: sender,
-                gas_limit: tx.tx().gas_limit,
-                gas_price: tx.tx().gas_price().unwrap_or_default(),
-                kind: tx.tx().kind(),
-                value: tx.tx().value.into(),
-                data: tx.tx().input.clone(),
-                nonce: tx.tx().nonce,
-                chain_id: tx.tx().chain_id(),
-                access_list: AccessList::default(),
-                gas_priority_fee: None,
-                blob_hashes: vec![],
-                max_fee_per_blob_gas: 0,
-                authorization_list: vec![],
-            },
+            SeismicTxEnvelope::Legacy(tx) => TxEnv::from_recovered_tx(tx.tx(), sender),
+            SeismicTxEnvelope::Eip2930(tx) => TxEnv::from_recovered_tx(tx.tx(), sender),
+            SeismicTxEnvelope::Eip1559(tx) => TxEnv::from_recovered_tx(tx.tx(), sender),
+            SeismicTxEnvelope::Eip4844(tx) => {
+                TxEnv::from_recovered_tx(AsRef::<TxEip4844>::as_ref(tx.tx()), sender)
+            }
+            SeismicTxEnvelope::Eip7702(tx) => TxEnv::from_recovered_tx(tx.tx(), sender),
             SeismicTxEnvelope::Seismic(tx) => TxEnv {
                 tx_type: SEISMIC_TX_TYPE_ID,
                 caller: sender,
@@ -0,0 +1,388 @@
+//! Execution witnesses: the state read while executing a block.
+//!
+//! [`RecordingDatabase`] wraps a database and records everything read from it into an
+//! [`ExecutionWitness`]. Used under a [`State`], it records the pre-state of every account,
+//! storage slot, bytecode and block hash accessed by the block, see
+//! [`execute_block_with_witness`].
+//!
+//! [`WitnessDatabase`] is the stateless counterpart: it serves reads from an [`ExecutionWitness`]
+//! only, so a block can be re-executed without access to the full state.
+
+use crate::{
+    block::{BlockExecutionError, BlockExecutionResult, BlockExecutor, BlockExecutorFactory},
+    Database, EvmEnv, EvmFactory, RecoveredTx, ToTxEnv,
+};
+use alloc::collections::BTreeMap;
+use alloy_primitives::{
+    map::{AddressMap, B256Map, HashMap},
+    Address, FlaggedStorage, B256,
+};
+use revm::{
+    context::DBErrorMarker,
+    database::State,
+    primitives::{StorageKey, KECCAK_EMPTY},
+    state::{AccountInfo, Bytecode},
+    DatabaseRef,
+};
+
+/// State read during execution.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecutionWitness {
+    /// Accounts, [`None`] for accounts that don't exist.
+    ///
+    /// Bytecode is not included in the account info, see [`Self::codes`].
+    pub accounts: AddressMap<Option<AccountInfo>>,
+    /// Storage slots per account.
+    pub storage: AddressMap<HashMap<StorageKey, FlaggedStorage>>,
+    /// Bytecodes by code hash.
+    pub codes: B256Map<Bytecode>,
+    /// Block hashes by block number.
+    pub block_hashes: BTreeMap<u64, B256>,
+}
+
+impl ExecutionWitness {
+    /// Returns `true` if nothing was read.
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+            && self.storage.is_empty()
+            && self.codes.is_empty()
+            && self.block_hashes.is_empty()
+    }
+
+    /// Records an account, storing its bytecode separately.
+    fn record_account(&mut self, address: Address, info: &Option<AccountInfo>) {
+        if let Some(AccountInfo { code_hash, code: Some(code), .. }) = info {
+            if *code_hash != KECCAK_EMPTY {
+                self.codes.entry(*code_hash).or_insert_with(|| code.clone());
+            }
+        }
+        self.accounts
+            .entry(address)
+            .or_insert_with(|| info.as_ref().map(|info| info.clone().without_code()));
+    }
+}
+
+/// A database recording all reads into an [`ExecutionWitness`].
+///
+/// Only the first read of every item is recorded. When wrapped in a [`State`], which caches all
+/// reads, this is the value before execution. Note that a [`State`] only reads from the database
+/// on cache misses, so the witness of a block is only complete if the [`State`] was created for
+/// that block.
+///
+/// If the wrapped database returns bytecode along with the account info, the bytecode of every
+/// loaded account is recorded, as it can't be told whether it was executed.
+#[derive(Debug, Clone, Default)]
+pub struct RecordingDatabase<DB> {
+    inner: DB,
+    witness: ExecutionWitness,
+}
+
+impl<DB> RecordingDatabase<DB> {
+    /// Creates a new [`RecordingDatabase`] wrapping the given database.
+    pub fn new(inner: DB) -> Self {
+        Self { inner, witness: ExecutionWitness::default() }
+    }
+
+    /// Returns the wrapped database.
+    pub const fn inner(&self) -> &DB {
+        &self.inner
+    }
+
+    /// Returns the witness recorded so far.
+    pub const fn witness(&self) -> &ExecutionWitness {
+        &self.witness
+    }
+
+    /// Takes the witness recorded so far, leaving an empty one.
+    pub fn take_witness(&mut self) -> ExecutionWitness {
+        core::mem::take(&mut self.witness)
+    }
+
+    /// Consumes the type and returns the wrapped database and the recorded witness.
+    pub fn into_parts(self) -> (DB, ExecutionWitness) {
+        (self.inner, self.witness)
+    }
+}
+
+impl<DB: revm::Database> revm::Database for RecordingDatabase<DB> {
+    type Error = DB::Error;
+
+    fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        let info = self.inner.basic(address)?;
+        self.witness.record_account(address, &info);
+        Ok(info)
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        let code = self.inner.code_by_hash(code_hash)?;
+        self.witness.codes.entry(code_hash).or_insert_with(|| code.clone());
+        Ok(code)
+    }
+
+    fn storage(
+        &mut self,
+        address: Address,
+        index: StorageKey,
+    ) -> Result<FlaggedStorage, Self::Error> {
+        let value = self.inner.storage(address, index)?;
+        self.witness.storage.entry(address).or_default().entry(index).or_insert(value);
+        Ok(value)
+    }
+
+    fn block_hash(&mut self, number: u64) -> Result<B256, Self::Error> {
+        let hash = self.inner.block_hash(number)?;
+        self.witness.block_hashes.entry(number).or_insert(hash);
+        Ok(hash)
+    }
+}
+
+/// Error returned by [`WitnessDatabase`] when execution accesses state missing from the witness.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum MissingWitnessError {
+    /// Account missing from the witness.
+    #[error("account {0} is missing from the witness")]
+    Account(Address),
+    /// Storage slot missing from the witness.
+    #[error("storage slot {1} of account {0} is missing from the witness")]
+    Storage(Address, StorageKey),
+    /// Bytecode missing from the witness.
+    #[error("bytecode {0} is missing from the witness")]
+    Code(B256),
+    /// Block hash missing from the witness.
+    #[error("hash of block {0} is missing from the witness")]
+    BlockHash(u64),
+}
+
+impl DBErrorMarker for MissingWitnessError {}
+
+/// A database serving reads from an [`ExecutionWitness`] only.
+///
+/// Any access to state that is not part of the witness fails with a [`MissingWitnessError`].
+/// Storage of accounts that don't exist according to the witness is empty.
+///
+/// Wrapped in a [`State`], it can be used with any [`BlockExecutorFactory`] to re-execute a block
+/// recorded with [`RecordingDatabase`].
+#[derive(Debug, Clone, Default)]
+pub struct WitnessDatabase {
+    witness: ExecutionWitness,
+}
+
+impl WitnessDatabase {
+    /// Creates a new [`WitnessDatabase`] backed by the given witness.
+    pub const fn new(witness: ExecutionWitness) -> Self {
+        Self { witness }
+    }
+
+    /// Returns the witness backing the database.
+    pub const fn witness(&self) -> &ExecutionWitness {
+        &self.witness
+    }
+
+    /// Consumes the type and returns the witness backing the database.
+    pub fn into_witness(self) -> ExecutionWitness {
+        self.witness
+    }
+}
+
+impl From<ExecutionWitness> for WitnessDatabase {
+    fn from(witness: ExecutionWitness) -> Self {
+        Self::new(witness)
+    }
+}
+
+impl DatabaseRef for WitnessDatabase {
+    type Error = MissingWitnessError;
+
+    fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        let info =
+            self.witness.accounts.get(&address).ok_or(MissingWitnessError::Account(address))?;
+
+        Ok(info.clone().map(|mut info| {
+            // missing code is only an error once it is executed, see `code_by_hash_ref`
+            info.code = self.witness.codes.get(&info.code_hash).cloned();
+            info
+        }))
+    }
+
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        if code_hash == KECCAK_EMPTY {
+            return Ok(Bytecode::default());
+        }
+        self.witness.codes.get(&code_hash).cloned().ok_or(MissingWitnessError::Code(code_hash))
+    }
+
+    fn storage_ref(
+        &self,
+        address: Address,
+        index: StorageKey,
+    ) -> Result<FlaggedStorage, Self::Error> {
+        if let Some(value) =
+            self.witness.storage.get(&address).and_then(|storage| storage.get(&index))
+        {
+            return Ok(*value);
+        }
+
+        match self.witness.accounts.get(&address) {
+            Some(None) => Ok(FlaggedStorage::default()),
+            _ => Err(MissingWitnessError::Storage(address, index)),
+        }
+    }
+
+    fn block_hash_ref(&self, number: u64) -> Result<B256, Self::Error> {
+        self.witness
+            .block_hashes
+            .get(&number)
+            .copied()
+            .ok_or(MissingWitnessError::BlockHash(number))
+    }
+}
+
+impl revm::Database for WitnessDatabase {
+    type Error = MissingWitnessError;
+
+    fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        self.basic_ref(address)
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        self.code_by_hash_ref(code_hash)
+    }
+
+    fn storage(
+        &mut self,
+        address: Address,
+        index: StorageKey,
+    ) -> Result<FlaggedStorage, Self::Error> {
+        self.storage_ref(address, index)
+    }
+
+    fn block_hash(&mut self, number: u64) -> Result<B256, Self::Error> {
+        self.block_hash_ref(number)
+    }
+}
+
+/// Executes a block on top of `db` and returns its result along with the witness recorded while
+/// executing it.
+///
+/// `execution_ctx` builds the execution context of the executor, which borrows `db` only for the
+/// duration of the call. The witness is taken from `db`, so for a complete witness `db` should be
+/// a fresh [`State`], see [`RecordingDatabase`].
+pub fn execute_block_with_witness<F, DB, T>(
+    factory: &F,
+    db: &mut State<RecordingDatabase<DB>>,
+    evm_env: EvmEnv<<F::EvmFactory as EvmFactory>::Spec>,
+    execution_ctx: impl for<'b> FnOnce(
+        &'b EvmEnv<<F::EvmFactory as EvmFactory>::Spec>,
+    ) -> F::ExecutionCtx<'b>,
+    transactions: impl IntoIterator<Item = T>,
+) -> Result<(BlockExecutionResult<F::Receipt>, ExecutionWitness), BlockExecutionError>
+where
+    F: BlockExecutorFactory,
+    DB: Database,
+    T: ToTxEnv<<F::EvmFactory as EvmFactory>::Tx> + RecoveredTx<F::Transaction>,
+{
+    let evm = factory.evm_factory().create_evm(&mut *db, evm_env.clone());
+    let result =
+        factory.create_executor(evm, execution_ctx(&evm_env)).execute_block(transactions)?;
+
+    Ok((result, db.database.take_witness()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_utils::{self, evm_env, execution_ctx, factory, SENDER},
+        EthEvmFactory,
+    };
+    use alloy_consensus::{transaction::Recovered, TxEnvelope};
+    use alloy_primitives::{address, bytes, keccak256, U256};
+    use revm::database::{CacheDB, EmptyDB};
+
+    const CONTRACT: Address = address!("0x000000000000000000000000000000000000c0de");
+
+    fn tx() -> Recovered<TxEnvelope> {
+        test_utils::tx(SENDER, 0, CONTRACT)
+    }
+
+    // increments slot 0 and reads the hash of the previous block
+    fn code() -> Bytecode {
+        Bytecode::new_raw(bytes!("0x60005460010160005560014303405000"))
+    }
+
+    fn recording_state() -> State<RecordingDatabase<CacheDB<EmptyDB>>> {
+        let mut cache_db = CacheDB::new(EmptyDB::new());
+        cache_db.insert_account_info(
+            CONTRACT,
+            AccountInfo { code: Some(code()), ..Default::default() },
+        );
+        State::builder()
+            .with_database(RecordingDatabase::new(cache_db))
+            .with_bundle_update()
+            .build()
+    }
+
+    #[test]
+    fn records_block_reads() {
+        let mut db = recording_state();
+        let (result, witness) =
+            execute_block_with_witness(&factory(), &mut db, evm_env(), execution_ctx, [&tx()])
+                .unwrap();
+        assert_eq!(result.receipts.len(), 1);
+
+        assert_eq!(witness.accounts[&SENDER], None);
+        let contract = witness.accounts[&CONTRACT].as_ref().unwrap();
+        assert_eq!(contract.code, None);
+        assert_eq!(witness.codes[&contract.code_hash], code());
+        assert_eq!(witness.storage[&CONTRACT][&StorageKey::ZERO], U256::ZERO.into());
+        assert_eq!(
+            witness.block_hashes[&19_999_999],
+            keccak256(U256::from(19_999_999).to_string().as_bytes())
+        );
+
+        // the witness was taken from the database
+        assert!(db.database.witness().is_empty());
+    }
+
+    #[test]
+    fn executes_block_from_witness() {
+        let factory = factory();
+        let (expected, witness) = execute_block_with_witness(
+            &factory,
+            &mut recording_state(),
+            evm_env(),
+            execution_ctx,
+            [&tx()],
+        )
+        .unwrap();
+
+        let mut db = State::builder().with_database(WitnessDatabase::new(witness)).build();
+        let evm_env = evm_env();
+        let evm = factory.evm_factory().create_evm(&mut db, evm_env.clone());
+        let result =
+            factory.create_executor(evm, execution_ctx(&evm_env)).execute_block([&tx()]).unwrap();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn errors_on_missing_data() {
+        let mut witness = ExecutionWitness::default();
+        witness.accounts.insert(SENDER, None);
+        let db = WitnessDatabase::new(witness);
+
+        assert_eq!(db.basic_ref(CONTRACT), Err(MissingWitnessError::Account(CONTRACT)));
+        assert_eq!(
+            db.storage_ref(CONTRACT, StorageKey::ZERO),
+            Err(MissingWitnessError::Storage(CONTRACT, StorageKey::ZERO))
+        );
+        // storage of accounts that don't exist is empty
+        assert_eq!(db.storage_ref(SENDER, StorageKey::ZERO), Ok(FlaggedStorage::default()));
+        assert_eq!(db.block_hash_ref(1), Err(MissingWitnessError::BlockHash(1)));
+
+        let mut db = State::builder().with_database(db).build();
+        let evm_env = evm_env();
+        let evm = EthEvmFactory.create_evm(&mut db, evm_env.clone());
+        let result = factory().create_executor(evm, execution_ctx(&evm_env)).execute_block([&tx()]);
+        assert!(result.is_err());
+    }
+}
@@ -7,9 +7,9 @@ use alloy_eips::{Encodable2718, Typed2718};
 use alloy_evm::{
     block::{
         state_changes::{balance_increment_state, post_block_balance_increments},
-        BlockExecutionError, BlockExecutionResult, BlockExecutor, BlockExecutorFactory,
-        BlockExecutorFor, BlockValidationError, CommitChanges, ExecutableTx, OnStateHook,
-        StateChangePostBlockSource, StateChangeSource, SystemCaller,
+        BlockExecutionError, BlockExecutionResult, BlockExecutor, BlockExecutorCheckpoint,
+        BlockExecutorFactory, BlockExecutorFor, BlockValidationError, ExecutableTx, OnStateHook,
+        StateChangePostBlockSource, StateChangeSource, StateCheckpoints, SystemCaller,
     },
     eth::receipt_builder::ReceiptBuilderCtx,
     Database, Evm, EvmFactory, FromRecoveredTx, FromTxWithEncoded,
@@ -21,11 +21,7 @@ use op_alloy_consensus::OpDepositReceipt;
 use op_revm::transaction::deposit::DEPOSIT_TRANSACTION_TYPE;
 pub use receipt_builder::OpAlloyReceiptBuilder;
 use receipt_builder::OpReceiptBuilder;
-use revm::{
-    context::result::{ExecutionResult, ResultAndState},
-    database::State,
-    DatabaseCommit, Inspector,
-};
+use revm::{context::result::ResultAndState, database::State, DatabaseCommit, Inspector};
 
 mod canyon;
 pub mod receipt_builder;
@@ -61,6 +57,8 @@ pub struct OpBlockExecutor<Evm, R: OpReceiptBuilder, Spec> {
     is_regolith: bool,
     /// Utility to call system smart contracts.
     system_caller: SystemCaller<Spec>,
+    /// Active checkpoints, holding the number of receipts and gas used at each checkpoint.
+    checkpoints: StateCheckpoints<(usize, u64)>,
 }
 
 impl<E, R, Spec> OpBlockExecutor<E, R, Spec>
@@ -80,9 +78,28 @@ where
             receipt_builder,
             receipts: Vec::new(),
             gas_used: 0,
+            checkpoints: StateCheckpoints::default(),
             ctx,
         }
     }
+
+    /// Validates the gas limit of the given transaction against the gas left in the block.
+    ///
+    /// Pre-Regolith deposit transactions are exempt from this check.
+    fn validate_transaction(&self, tx: &impl Transaction) -> Result<(), BlockValidationError> {
+        let is_deposit = tx.ty() == DEPOSIT_TRANSACTION_TYPE;
+
+        // The sum of the transaction’s gas limit, Tg, and the gas utilized in this block prior,
+        // must be no greater than the block’s gasLimit.
+        let block_available_gas = self.evm.block().gas_limit - self.gas_used;
+        if tx.gas_limit() > block_available_gas && (self.is_regolith || !is_deposit) {
+            return Err(BlockValidationError::TransactionGasLimitMoreThanAvailableBlockGas {
+                transaction_gas_limit: tx.gas_limit(),
+                block_available_gas,
+            });
+        }
+        Ok(())
+    }
 }
 
 impl<'db, DB, E, R, Spec> BlockExecutor for OpBlockExecutor<E, R, Spec>
@@ -123,23 +140,29 @@ where
         Ok(())
     }
 
-    fn execute_transaction_with_commit_condition(
+    fn execute_transaction_without_commit(
         &mut self,
         tx: impl ExecutableTx<Self>,
-        f: impl FnOnce(&ExecutionResult<<Self::Evm as Evm>::HaltReason>) -> CommitChanges,
-    ) -> Result<Option<u64>, BlockExecutionError> {
-        let is_deposit = tx.tx().ty() == DEPOSIT_TRANSACTION_TYPE;
+    ) -> Result<ResultAndState<<Self::Evm as Evm>::HaltReason>, BlockExecutionError> {
+        self.validate_transaction(tx.tx())?;
 
-        // The sum of the transaction’s gas limit, Tg, and the gas utilized in this block prior,
-        // must be no greater than the block’s gasLimit.
-        let block_available_gas = self.evm.block().gas_limit - self.gas_used;
-        if tx.tx().gas_limit() > block_available_gas && (self.is_regolith || !is_deposit) {
-            return Err(BlockValidationError::TransactionGasLimitMoreThanAvailableBlockGas {
-                transaction_gas_limit: tx.tx().gas_limit(),
-                block_available_gas,
-            }
-            .into());
-        }
+        let hash = tx.tx().trie_hash();
+
+        // Execute transaction.
+        self.evm.transact(&tx).map_err(move |err| BlockExecutionError::evm(err, hash))
+    }
+
+    fn commit_transaction(
+        &mut self,
+        output: ResultAndState<<Self::Evm as Evm>::HaltReason>,
+        tx: impl ExecutableTx<Self>,
+    ) -> Result<u64, BlockExecutionError> {
+        // The output might not come from `execute_transaction_without_commit`, e.g. when executing
+        // transactions in parallel, so the transaction is validated again.
+        self.validate_transaction(tx.tx())?;
+
+        let ResultAndState { result, state } = output;
+        let is_deposit = tx.tx().ty() == DEPOSIT_TRANSACTION_TYPE;
 
         // Cache the depositor account prior to the state transition for the deposit nonce.
         //
@@ -156,17 +179,9 @@ where
             .transpose()
             .map_err(BlockExecutionError::other)?;
 
-        let hash = tx.tx().trie_hash();
-
-        // Execute transaction.
-        let ResultAndState { result, state } =
-            self.evm.transact(&tx).map_err(move |err| BlockExecutionError::evm(err, hash))?;
-
-        if !f(&result).should_commit() {
-            return Ok(None);
-        }
-
-        self.system_caller.on_state(StateChangeSource::Transaction(self.receipts.len()), &state);
+        let source = StateChangeSource::Transaction(self.receipts.len());
+        self.system_caller.record_state_diff(source, self.evm.db_mut(), &state)?;
+        self.system_caller.on_state(source, &state);
 
         let gas_used = result.gas_used();
 
@@ -209,9 +224,36 @@ where
             },
         );
 
+        self.checkpoints.record(self.evm.db(), &state);
         self.evm.db_mut().commit(state);
 
-        Ok(Some(gas_used))
+        Ok(gas_used)
+    }
+
+    fn checkpoint(&mut self) -> Result<BlockExecutorCheckpoint, BlockExecutionError> {
+        Ok(self.checkpoints.checkpoint((self.receipts.len(), self.gas_used)))
+    }
+
+    fn checkpoint_commit(
+        &mut self,
+        checkpoint: BlockExecutorCheckpoint,
+    ) -> Result<(), BlockExecutionError> {
+        self.checkpoints.commit(checkpoint)
+    }
+
+    fn checkpoint_revert(
+        &mut self,
+        checkpoint: BlockExecutorCheckpoint,
+    ) -> Result<(), BlockExecutionError> {
+        let (receipts, gas_used) = self.checkpoints.revert(self.evm.db_mut(), checkpoint)?;
+        self.receipts.truncate(receipts);
+        self.gas_used = gas_used;
+        if let Some(diffs) = self.system_caller.state_diffs_mut() {
+            diffs.retain(|(source, _)| {
+                !matches!(source, StateChangeSource::Transaction(index) if *index >= receipts)
+            });
+        }
+        Ok(())
     }
 
     fn finish(
@@ -219,6 +261,8 @@ where
     ) -> Result<(Self::Evm, BlockExecutionResult<R::Receipt>), BlockExecutionError> {
         let balance_increments =
             post_block_balance_increments::<Header>(&self.spec, self.evm.block(), &[], None);
+        self.system_caller
+            .record_balance_increments_diff(self.evm.db_mut(), &balance_increments)?;
         // increment balances
         self.evm
             .db_mut()
@@ -241,6 +285,8 @@ where
                 receipts: self.receipts,
                 requests: Default::default(),
                 gas_used,
+                blob_gas_used: 0,
+                state_diffs: self.system_caller.take_state_diffs(),
             },
         ))
     }
@@ -249,6 +295,10 @@ where
         self.system_caller.with_state_hook(hook);
     }
 
+    fn set_state_diff_recording(&mut self, enabled: bool) {
+        self.system_caller.with_state_diff_recording(enabled);
+    }
+
     fn evm_mut(&mut self) -> &mut Self::Evm {
         &mut self.evm
     }
@@ -4,31 +4,63 @@ use crate::{
     hardfork::{SeismicChainHardforks, SeismicHardforks},
     SeismicEvmFactory,
 };
-use alloy_consensus::{Transaction, TxReceipt};
+use alloc::boxed::Box;
+use alloy_consensus::{transaction::Recovered, Transaction, TxReceipt};
 use alloy_eips::Encodable2718;
 use alloy_evm::{
     block::{
-        BlockExecutionError, BlockExecutionResult, BlockExecutor, BlockExecutorFactory,
-        BlockExecutorFor, OnStateHook,
+        BlockExecutionError, BlockExecutionResult, BlockExecutor, BlockExecutorCheckpoint,
+        BlockExecutorFactory, BlockExecutorFor, ExecutableTx, OnStateHook,
     },
     eth::{
         receipt_builder::ReceiptBuilder, spec::EthExecutorSpec, EthBlockExecutionCtx,
         EthBlockExecutor,
     },
-    Database, Evm, EvmFactory, FromRecoveredTx,
+    Database, Evm, EvmFactory, FromRecoveredTx, FromTxWithEncoded, RecoveredTx,
 };
 use alloy_primitives::Log;
-pub use receipt_builder::SeismicAlloyReceiptBuilder;
-use revm::{database::State, Inspector};
-pub mod receipt_builder;
-use alloy_consensus::transaction::Recovered;
-use alloy_evm::{
-    block::{CommitChanges, ExecutableTx, InternalBlockExecutionError},
-    FromTxWithEncoded, RecoveredTx,
-};
-use revm::context::result::ExecutionResult;
+use revm::{context::result::ResultAndState, database::State, Inspector};
 use seismic_alloy_consensus::InputDecryptionElements;
 
+pub mod receipt_builder;
+pub use receipt_builder::{SeismicAlloyReceiptBuilder, SeismicReceiptBuilder};
+
+/// Decrypts the input of seismic transactions before they are executed.
+#[auto_impl::auto_impl(&, Arc)]
+pub trait SeismicTxDecryptor {
+    /// Returns a copy of `tx` with its input decrypted.
+    fn decrypt<T: InputDecryptionElements>(&self, tx: &T) -> Result<T, BlockExecutionError>;
+}
+
+/// Decrypts transactions with the `tx_io` secret key of the enclave purpose keys.
+#[cfg(feature = "enclave")]
+impl SeismicTxDecryptor for seismic_enclave::keys::GetPurposeKeysResponse {
+    fn decrypt<T: InputDecryptionElements>(&self, tx: &T) -> Result<T, BlockExecutionError> {
+        use alloy_evm::block::InternalBlockExecutionError;
+
+        tx.plaintext_copy(&self.tx_io_sk)
+            .map_err(|e| InternalBlockExecutionError::FailedToDecryptSeismicTx(e).into())
+    }
+}
+
+/// See [`DefaultSeismicTxDecryptor`].
+impl SeismicTxDecryptor for core::convert::Infallible {
+    fn decrypt<T: InputDecryptionElements>(&self, _tx: &T) -> Result<T, BlockExecutionError> {
+        match *self {}
+    }
+}
+
+/// Default [`SeismicTxDecryptor`] of [`SeismicBlockExecutorFactory`], the enclave purpose keys.
+#[cfg(feature = "enclave")]
+pub type DefaultSeismicTxDecryptor = &'static seismic_enclave::keys::GetPurposeKeysResponse;
+
+/// Placeholder for the default [`SeismicTxDecryptor`] without the `enclave` feature.
+///
+/// It can't be constructed, so the decryptor of a [`SeismicBlockExecutorFactory`] has to be
+/// provided explicitly.
+#[cfg(not(feature = "enclave"))]
+pub type DefaultSeismicTxDecryptor = core::convert::Infallible;
+
 type SeismicBlockExecutionCtx<'a> = EthBlockExecutionCtx<'a>;
 
 /// Block executor for Seismic.
@@ -37,21 +69,22 @@ type SeismicBlockExecutionCtx<'a> = EthBlockExecutionCtx<'a>;
 /// Note that only execute endpoints (e.g. eth_sendRawTransaction) will route through
 /// the block executor, not simulate endpoints (e.g. eth_call, eth_estimateGas).
 #[derive(Debug)]
-pub struct SeismicBlockExecutor<'a, Evm, Spec, R>
+pub struct SeismicBlockExecutor<'a, Evm, Spec, R, D>
 where
     R: ReceiptBuilder,
-    R::Receipt: std::fmt::Debug,
+    R::Receipt: core::fmt::Debug,
 {
     inner: EthBlockExecutor<'a, Evm, Spec, R>,
-    purpose_keys: &'static seismic_enclave::keys::GetPurposeKeysResponse,
+    decryptor: D,
 }
 
-impl<'a, E, Spec, R> SeismicBlockExecutor<'a, E, Spec, R>
+impl<'a, E, Spec, R, D> SeismicBlockExecutor<'a, E, Spec, R, D>
 where
     E: Evm,
     R: ReceiptBuilder,
-    R::Receipt: std::fmt::Debug,
+    R::Receipt: core::fmt::Debug,
     Spec: SeismicHardforks + Clone,
+    D: SeismicTxDecryptor,
 {
     /// Creates a new [`SeismicBlockExecutor`].
     pub fn new(
@@ -59,13 +92,22 @@ where
         ctx: SeismicBlockExecutionCtx<'a>,
         spec: Spec,
         receipt_builder: R,
-        purpose_keys: &'static seismic_enclave::keys::GetPurposeKeysResponse,
+        decryptor: D,
     ) -> Self {
-        Self { inner: EthBlockExecutor::new(evm, ctx, spec, receipt_builder), purpose_keys }
+        Self { inner: EthBlockExecutor::new(evm, ctx, spec, receipt_builder), decryptor }
+    }
+
+    /// Returns a copy of the transaction with its input decrypted.
+    fn decrypt<T>(&self, tx: impl RecoveredTx<T>) -> Result<Recovered<T>, BlockExecutionError>
+    where
+        T: InputDecryptionElements,
+    {
+        let plaintext_base = self.decryptor.decrypt(tx.tx())?;
+        Ok(Recovered::new_unchecked(plaintext_base, *tx.signer()))
     }
 }
 
-impl<'db, DB, E, Spec, R> BlockExecutor for SeismicBlockExecutor<'_, E, Spec, R>
+impl<'db, DB, E, Spec, R, D> BlockExecutor for SeismicBlockExecutor<'_, E, Spec, R, D>
 where
     DB: Database + 'db,
     E: Evm<
@@ -77,6 +119,7 @@ where
         Transaction: Transaction + Encodable2718 + InputDecryptionElements,
         Receipt: TxReceipt<Log = Log>,
     >,
+    D: SeismicTxDecryptor,
 {
     type Transaction = R::Transaction;
     type Receipt = R::Receipt;
@@ -86,44 +129,40 @@ where
         self.inner.apply_pre_execution_changes()
     }
 
-    fn execute_transaction_with_commit_condition(
+    fn execute_transaction_without_commit(
         &mut self,
         tx: impl ExecutableTx<Self>,
-        f: impl FnOnce(&ExecutionResult<<Self::Evm as Evm>::HaltReason>) -> CommitChanges,
-    ) -> Result<Option<u64>, BlockExecutionError> {
-        // Convert from ExecutableTx<Self> to R::Transaction,
-        // which has the InputDecryptionElements bound
-        let receipt_tx: &<R as ReceiptBuilder>::Transaction = RecoveredTx::tx(&tx);
-
-        // decrypt
-        let plaintext_base = receipt_tx
-            .plaintext_copy(&self.purpose_keys.tx_io_sk)
-            .map_err(|e| InternalBlockExecutionError::FailedToDecryptSeismicTx(e))?;
-
-        // call inner
-        let signer = RecoveredTx::signer(&tx);
-        let recovered = Recovered::new_unchecked(plaintext_base, *signer);
-        self.inner.execute_transaction_with_commit_condition(&recovered, f)
+    ) -> Result<ResultAndState<<Self::Evm as Evm>::HaltReason>, BlockExecutionError> {
+        let recovered = self.decrypt(&tx)?;
+        self.inner.execute_transaction_without_commit(&recovered)
     }
 
-    fn execute_transaction_with_result_closure(
+    fn commit_transaction(
         &mut self,
+        output: ResultAndState<<Self::Evm as Evm>::HaltReason>,
         tx: impl ExecutableTx<Self>,
-        f: impl FnOnce(&ExecutionResult<<Self::Evm as Evm>::HaltReason>),
     ) -> Result<u64, BlockExecutionError> {
-        // Convert from ExecutableTx<Self> to R::Transaction,
-        // which has the InputDecryptionElements bound
-        let receipt_tx: &<R as ReceiptBuilder>::Transaction = RecoveredTx::tx(&tx);
-
-        // decrypt
-        let plaintext_base = receipt_tx
-            .plaintext_copy(&self.purpose_keys.tx_io_sk)
-            .map_err(|e| InternalBlockExecutionError::FailedToDecryptSeismicTx(e))?;
-
-        // call inner
-        let signer = RecoveredTx::signer(&tx);
-        let recovered = Recovered::new_unchecked(plaintext_base, *signer);
-        self.inner.execute_transaction_with_result_closure(&recovered, f)
+        // The input was decrypted when executing. Receipts only depend on the transaction type and
+        // the execution result, so they are built from the transaction as included in the block.
+        self.inner.commit_transaction(output, tx)
+    }
+
+    fn checkpoint(&mut self) -> Result<BlockExecutorCheckpoint, BlockExecutionError> {
+        self.inner.checkpoint()
+    }
+
+    fn checkpoint_commit(
+        &mut self,
+        checkpoint: BlockExecutorCheckpoint,
+    ) -> Result<(), BlockExecutionError> {
+        self.inner.checkpoint_commit(checkpoint)
+    }
+
+    fn checkpoint_revert(
+        &mut self,
+        checkpoint: BlockExecutorCheckpoint,
+    ) -> Result<(), BlockExecutionError> {
+        self.inner.checkpoint_revert(checkpoint)
     }
 
     fn finish(self) -> Result<(Self::Evm, BlockExecutionResult<R::Receipt>), BlockExecutionError> {
@@ -134,6 +173,10 @@ where
         self.inner.set_state_hook(hook)
     }
 
+    fn set_state_diff_recording(&mut self, enabled: bool) {
+        self.inner.set_state_diff_recording(enabled)
+    }
+
     fn evm_mut(&mut self) -> &mut Self::Evm {
         self.inner.evm_mut()
     }
@@ -149,6 +192,7 @@ pub struct SeismicBlockExecutorFactory<
     R = SeismicAlloyReceiptBuilder,
     Spec = SeismicChainHardforks,
     EvmFactory = SeismicEvmFactory,
+    D = DefaultSeismicTxDecryptor,
 > {
     /// Receipt builder.
     receipt_builder: R,
@@ -156,20 +200,20 @@ pub struct SeismicBlockExecutorFactory<
     spec: Spec,
     /// EVM factory.
     evm_factory: EvmFactory,
-    /// Purpose keys for decryption.
-    pub purpose_keys: &'static seismic_enclave::keys::GetPurposeKeysResponse,
+    /// Decryptor of seismic transactions.
+    decryptor: D,
 }
 
-impl<R, Spec, EvmFactory> SeismicBlockExecutorFactory<R, Spec, EvmFactory> {
-    /// Creates a new [`SeismicBlockExecutorFactory`] with the given spec, [`EvmFactory`], and
-    /// [`SeismicReceiptBuilder`].
+impl<R, Spec, EvmFactory, D> SeismicBlockExecutorFactory<R, Spec, EvmFactory, D> {
+    /// Creates a new [`SeismicBlockExecutorFactory`] with the given spec, [`EvmFactory`],
+    /// [`SeismicReceiptBuilder`] and [`SeismicTxDecryptor`].
     pub const fn new(
         receipt_builder: R,
         spec: Spec,
         evm_factory: EvmFactory,
-        purpose_keys: &'static seismic_enclave::keys::GetPurposeKeysResponse,
+        decryptor: D,
     ) -> Self {
-        Self { receipt_builder, spec, evm_factory, purpose_keys }
+        Self { receipt_builder, spec, evm_factory, decryptor }
     }
 
     /// Exposes the receipt builder.
@@ -186,9 +230,14 @@ impl<R, Spec, EvmFactory> SeismicBlockExecutorFactory<R, Spec, EvmFactory> {
     pub const fn evm_factory(&self) -> &EvmFactory {
         &self.evm_factory
     }
+
+    /// Exposes the decryptor of seismic transactions.
+    pub const fn decryptor(&self) -> &D {
+        &self.decryptor
+    }
 }
 
-impl<R, Spec, EvmF> BlockExecutorFactory for SeismicBlockExecutorFactory<R, Spec, EvmF>
+impl<R, Spec, EvmF, D> BlockExecutorFactory for SeismicBlockExecutorFactory<R, Spec, EvmF, D>
 where
     R: ReceiptBuilder<
         Transaction: Transaction + Encodable2718 + InputDecryptionElements + Clone,
@@ -196,6 +245,7 @@ where
     >,
     Spec: SeismicHardforks + EthExecutorSpec,
     EvmF: EvmFactory<Tx: FromRecoveredTx<R::Transaction> + FromTxWithEncoded<R::Transaction>>,
+    D: SeismicTxDecryptor,
     Self: 'static,
 {
     type EvmFactory = EvmF;
@@ -207,8 +257,6 @@ where
         &self.evm_factory
     }
 
-    // <EvmF as EvmFactory>::Tx: RecoveredTx<<R as ReceiptBuilder>::Transaction>`
-
     fn create_executor<'a, DB, I>(
         &'a self,
         evm: EvmF::Evm<&'a mut State<DB>, I>,
@@ -218,11 +266,11 @@ where
         DB: Database + 'a,
         I: Inspector<EvmF::Context<&'a mut State<DB>>> + 'a,
     {
-        SeismicBlockExecutor::new(evm, ctx, &self.spec, &self.receipt_builder, self.purpose_keys)
+        SeismicBlockExecutor::new(evm, ctx, &self.spec, &self.receipt_builder, &self.decryptor)
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "enclave"))]
 mod tests {
     use super::*;
     use alloy_consensus::SignableTransaction;
@@ -1,26 +1,240 @@
 //! Custom receipt builder for Seismic.
 
-use alloy_consensus::Eip658Value;
+use crate::error::SeismicErrorCode;
+use alloy_consensus::{Eip2718EncodableReceipt, Eip658Value, ReceiptWithBloom, TxReceipt};
+use alloy_eips::{
+    eip2718::{
+        Encodable2718, EIP1559_TX_TYPE_ID, EIP2930_TX_TYPE_ID, EIP7702_TX_TYPE_ID,
+        LEGACY_TX_TYPE_ID,
+    },
+    Typed2718,
+};
 use alloy_evm::{
+    block::BlockExecutionError,
     eth::receipt_builder::{ReceiptBuilder, ReceiptBuilderCtx},
     Evm,
 };
-use seismic_alloy_consensus::{SeismicReceiptEnvelope, SeismicTxEnvelope, SeismicTxType};
+use alloy_primitives::{bytes::BufMut, Bloom, Log};
+use core::{fmt::Debug, marker::PhantomData};
+use seismic_alloy_consensus::{SeismicReceiptEnvelope, SeismicTxEnvelope, SEISMIC_TX_TYPE_ID};
+
+/// Receipt builder operating on seismic alloy types.
+///
+/// This is a [`SeismicReceiptBuilder`] over [`SeismicTxEnvelope`] that doesn't attach any
+/// metadata to the produced receipts.
+pub type SeismicAlloyReceiptBuilder = SeismicReceiptBuilder<SeismicTxEnvelope>;
+
+/// Errors that can occur when building a seismic receipt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum SeismicReceiptBuilderError {
+    /// The transaction type is not supported by seismic receipts.
+    #[error("unsupported transaction type for seismic receipt: {0}")]
+    UnsupportedTxType(u8),
+}
+
+/// Seismic-specific metadata that can be attached to a receipt.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SeismicReceiptMetadata {
+    /// Whether the output of the transaction was encrypted, i.e. whether this is a seismic
+    /// transaction.
+    pub encrypted_output: bool,
+    /// Epoch of the enclave keys used to process the transaction, if known.
+    pub key_epoch: Option<u64>,
+    /// Code of the error the transaction failed with, if it failed.
+    ///
+    /// This is the code calls and gas estimations of the transaction report, see
+    /// [`SeismicExecutionError`](crate::error::SeismicExecutionError).
+    pub error: Option<SeismicErrorCode>,
+}
 
-/// Receipt builder operating on seismic alloy types. Useful for testing,
-/// but reth uses SeismicRethReceiptBuilder instead with T = SeismicTransactionSigned
+/// A receipt together with optional [`SeismicReceiptMetadata`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReceiptWithSeismicMetadata<R = SeismicReceiptEnvelope> {
+    /// The inner receipt.
+    pub receipt: R,
+    /// Seismic metadata of the receipt.
+    pub metadata: Option<SeismicReceiptMetadata>,
+}
+
+impl<R: TxReceipt> TxReceipt for ReceiptWithSeismicMetadata<R> {
+    type Log = R::Log;
+
+    fn status_or_post_state(&self) -> Eip658Value {
+        self.receipt.status_or_post_state()
+    }
+
+    fn status(&self) -> bool {
+        self.receipt.status()
+    }
+
+    fn bloom(&self) -> Bloom {
+        self.receipt.bloom()
+    }
+
+    fn bloom_cheap(&self) -> Option<Bloom> {
+        self.receipt.bloom_cheap()
+    }
+
+    fn cumulative_gas_used(&self) -> u64 {
+        self.receipt.cumulative_gas_used()
+    }
+
+    fn logs(&self) -> &[Self::Log] {
+        self.receipt.logs()
+    }
+}
+
+impl<R: Typed2718> Typed2718 for ReceiptWithSeismicMetadata<R> {
+    fn ty(&self) -> u8 {
+        self.receipt.ty()
+    }
+}
+
+/// Encodes the inner receipt, the metadata isn't part of the encoding.
+impl<R: Encodable2718> Encodable2718 for ReceiptWithSeismicMetadata<R> {
+    fn encode_2718_len(&self) -> usize {
+        self.receipt.encode_2718_len()
+    }
+
+    fn encode_2718(&self, out: &mut dyn BufMut) {
+        self.receipt.encode_2718(out)
+    }
+}
+
+/// Encodes the inner receipt, the metadata isn't part of the encoding.
+impl<R: Eip2718EncodableReceipt> Eip2718EncodableReceipt for ReceiptWithSeismicMetadata<R> {
+    fn eip2718_encoded_length_with_bloom(&self, bloom: &Bloom) -> usize {
+        self.receipt.eip2718_encoded_length_with_bloom(bloom)
+    }
+
+    fn eip2718_encode_with_bloom(&self, bloom: &Bloom, out: &mut dyn BufMut) {
+        self.receipt.eip2718_encode_with_bloom(bloom, out)
+    }
+}
+
+/// Decides which metadata, if any, is attached to receipts produced by [`SeismicReceiptBuilder`].
+pub trait SeismicReceiptMetadataProvider<T>: Debug {
+    /// Receipt type produced by the builder.
+    type Receipt;
+
+    /// Attaches metadata for the given transaction to the receipt.
+    ///
+    /// `error` is the code of the error the transaction failed with, if it failed.
+    fn attach(
+        &self,
+        tx: &T,
+        error: Option<SeismicErrorCode>,
+        receipt: SeismicReceiptEnvelope,
+    ) -> Self::Receipt;
+}
+
+/// A [`SeismicReceiptMetadataProvider`] that doesn't attach any metadata.
 #[derive(Debug, Default, Clone, Copy)]
 #[non_exhaustive]
-pub struct SeismicAlloyReceiptBuilder;
+pub struct NoSeismicMetadata;
 
-impl ReceiptBuilder for SeismicAlloyReceiptBuilder {
-    type Transaction = SeismicTxEnvelope;
+impl<T> SeismicReceiptMetadataProvider<T> for NoSeismicMetadata {
     type Receipt = SeismicReceiptEnvelope;
 
-    fn build_receipt<'a, E: Evm>(
+    fn attach(
         &self,
-        ctx: ReceiptBuilderCtx<'a, Self::Transaction, E>,
+        _tx: &T,
+        _error: Option<SeismicErrorCode>,
+        receipt: SeismicReceiptEnvelope,
     ) -> Self::Receipt {
+        receipt
+    }
+}
+
+/// A [`SeismicReceiptMetadataProvider`] attaching the encrypted output flag, the key epoch and the
+/// error code of failed transactions.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WithSeismicMetadata {
+    /// Epoch of the enclave keys used by the executor.
+    pub key_epoch: Option<u64>,
+}
+
+impl<T: Typed2718> SeismicReceiptMetadataProvider<T> for WithSeismicMetadata {
+    type Receipt = ReceiptWithSeismicMetadata;
+
+    fn attach(
+        &self,
+        tx: &T,
+        error: Option<SeismicErrorCode>,
+        receipt: SeismicReceiptEnvelope,
+    ) -> Self::Receipt {
+        ReceiptWithSeismicMetadata {
+            receipt,
+            metadata: Some(SeismicReceiptMetadata {
+                encrypted_output: tx.ty() == SEISMIC_TX_TYPE_ID,
+                key_epoch: self.key_epoch,
+                error,
+            }),
+        }
+    }
+}
+
+/// Receipt builder for seismic chains, generic over the transaction type.
+///
+/// Works with any transaction implementing [`Typed2718`]. Transaction types that don't have a
+/// seismic receipt representation are rejected with a [`SeismicReceiptBuilderError`].
+pub struct SeismicReceiptBuilder<T = SeismicTxEnvelope, M = NoSeismicMetadata> {
+    metadata: M,
+    _tx: PhantomData<fn() -> T>,
+}
+
+impl<T> SeismicReceiptBuilder<T> {
+    /// Creates a new [`SeismicReceiptBuilder`] that doesn't attach any metadata.
+    pub const fn new() -> Self {
+        Self { metadata: NoSeismicMetadata, _tx: PhantomData }
+    }
+}
+
+impl<T, M> SeismicReceiptBuilder<T, M> {
+    /// Configures the [`SeismicReceiptMetadataProvider`] used to attach metadata to receipts.
+    pub fn with_metadata<N>(self, metadata: N) -> SeismicReceiptBuilder<T, N> {
+        SeismicReceiptBuilder { metadata, _tx: PhantomData }
+    }
+
+    /// Attaches [`SeismicReceiptMetadata`] with the given key epoch to all receipts.
+    pub fn with_key_epoch(self, key_epoch: u64) -> SeismicReceiptBuilder<T, WithSeismicMetadata> {
+        self.with_metadata(WithSeismicMetadata { key_epoch: Some(key_epoch) })
+    }
+
+    /// Returns the configured metadata provider.
+    pub const fn metadata(&self) -> &M {
+        &self.metadata
+    }
+
+    /// Builds the receipt envelope for the given transaction type.
+    pub fn build_envelope(
+        ty: u8,
+        receipt: ReceiptWithBloom<alloy_consensus::Receipt<Log>>,
+    ) -> Result<SeismicReceiptEnvelope, SeismicReceiptBuilderError> {
+        Ok(match ty {
+            LEGACY_TX_TYPE_ID => SeismicReceiptEnvelope::Legacy(receipt),
+            EIP2930_TX_TYPE_ID => SeismicReceiptEnvelope::Eip2930(receipt),
+            EIP1559_TX_TYPE_ID => SeismicReceiptEnvelope::Eip1559(receipt),
+            EIP7702_TX_TYPE_ID => SeismicReceiptEnvelope::Eip7702(receipt),
+            SEISMIC_TX_TYPE_ID => SeismicReceiptEnvelope::Seismic(receipt),
+            ty => return Err(SeismicReceiptBuilderError::UnsupportedTxType(ty)),
+        })
+    }
+}
+
+impl<T, M> ReceiptBuilder for SeismicReceiptBuilder<T, M>
+where
+    T: Typed2718,
+    M: SeismicReceiptMetadataProvider<T>,
+{
+    type Transaction = T;
+    type Receipt = M::Receipt;
+
+    fn build_receipt<E: Evm>(
+        &self,
+        ctx: ReceiptBuilderCtx<'_, T, E>,
+    ) -> Result<Self::Receipt, BlockExecutionError> {
+        let error = SeismicErrorCode::from_result(&ctx.result);
         let receipt_with_bloom = alloy_consensus::Receipt {
             status: Eip658Value::Eip658(ctx.result.is_success()),
             cumulative_gas_used: ctx.cumulative_gas_used,
@@ -28,16 +242,116 @@ impl ReceiptBuilder for SeismicAlloyReceiptBuilder {
         }
         .with_bloom();
 
-        let ty = ctx.tx.tx_type();
+        let envelope = Self::build_envelope(ctx.tx.ty(), receipt_with_bloom)
+            .map_err(BlockExecutionError::other)?;
 
-        match ty {
-            SeismicTxType::Legacy => SeismicReceiptEnvelope::Legacy(receipt_with_bloom),
-            SeismicTxType::Eip2930 => SeismicReceiptEnvelope::Eip2930(receipt_with_bloom),
-            SeismicTxType::Eip1559 => SeismicReceiptEnvelope::Eip1559(receipt_with_bloom),
-            SeismicTxType::Eip7702 => SeismicReceiptEnvelope::Eip7702(receipt_with_bloom),
-            SeismicTxType::Seismic => SeismicReceiptEnvelope::Seismic(receipt_with_bloom),
-            #[allow(unreachable_patterns)]
-            _ => unreachable!(),
-        }
+        Ok(self.metadata.attach(ctx.tx, error, envelope))
+    }
+}
+
+impl<T, M: Debug> Debug for SeismicReceiptBuilder<T, M> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SeismicReceiptBuilder").field("metadata", &self.metadata).finish()
+    }
+}
+
+impl<T, M: Clone> Clone for SeismicReceiptBuilder<T, M> {
+    fn clone(&self) -> Self {
+        Self { metadata: self.metadata.clone(), _tx: PhantomData }
+    }
+}
+
+impl<T, M: Copy> Copy for SeismicReceiptBuilder<T, M> {}
+
+impl<T, M: Default> Default for SeismicReceiptBuilder<T, M> {
+    fn default() -> Self {
+        Self { metadata: M::default(), _tx: PhantomData }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{vec, vec::Vec};
+    use alloy_evm::block::validation::calculate_receipts_root;
+    use alloy_primitives::EMPTY_ROOT_HASH;
+
+    #[test]
+    fn rejects_unsupported_tx_type() {
+        let receipt = alloy_consensus::Receipt::<Log>::default().with_bloom();
+        assert_eq!(
+            SeismicAlloyReceiptBuilder::build_envelope(0x7e, receipt.clone()),
+            Err(SeismicReceiptBuilderError::UnsupportedTxType(0x7e))
+        );
+        assert!(matches!(
+            SeismicAlloyReceiptBuilder::build_envelope(SEISMIC_TX_TYPE_ID, receipt),
+            Ok(SeismicReceiptEnvelope::Seismic(_))
+        ));
+    }
+
+    #[test]
+    fn attaches_error_code() {
+        let receipt = SeismicReceiptEnvelope::Legacy(
+            alloy_consensus::Receipt::<Log> {
+                status: Eip658Value::Eip658(false),
+                cumulative_gas_used: 21_000,
+                logs: vec![],
+            }
+            .with_bloom(),
+        );
+        let with_metadata = WithSeismicMetadata { key_epoch: Some(1) }.attach(
+            &alloy_consensus::TxLegacy::default(),
+            Some(SeismicErrorCode::Reverted),
+            receipt.clone(),
+        );
+
+        assert_eq!(
+            with_metadata.metadata,
+            Some(SeismicReceiptMetadata {
+                encrypted_output: false,
+                key_epoch: Some(1),
+                error: Some(SeismicErrorCode::Reverted),
+            })
+        );
+        assert_eq!(with_metadata.receipt, receipt);
+    }
+
+    #[test]
+    fn metadata_is_not_encoded() {
+        let log =
+            Log::new_unchecked(Default::default(), vec![Default::default()], Default::default());
+        let receipts = [
+            SeismicReceiptEnvelope::Legacy(
+                alloy_consensus::Receipt {
+                    status: Eip658Value::Eip658(true),
+                    cumulative_gas_used: 21_000,
+                    logs: vec![],
+                }
+                .with_bloom(),
+            ),
+            SeismicReceiptEnvelope::Seismic(
+                alloy_consensus::Receipt {
+                    status: Eip658Value::Eip658(false),
+                    cumulative_gas_used: 60_000,
+                    logs: vec![log],
+                }
+                .with_bloom(),
+            ),
+        ];
+        let with_metadata = receipts
+            .iter()
+            .map(|receipt| ReceiptWithSeismicMetadata {
+                receipt: receipt.clone(),
+                metadata: Some(SeismicReceiptMetadata {
+                    encrypted_output: true,
+                    key_epoch: Some(1),
+                    error: Some(SeismicErrorCode::Reverted),
+                }),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(with_metadata[1].encoded_2718(), receipts[1].encoded_2718());
+        assert_eq!(calculate_receipts_root(&with_metadata), calculate_receipts_root(&receipts));
+        assert_ne!(calculate_receipts_root(&with_metadata), EMPTY_ROOT_HASH);
     }
 }
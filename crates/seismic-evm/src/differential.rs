@@ -0,0 +1,473 @@
+//! Differential testing of [`SeismicEvm`](crate::SeismicEvm) against [`EthEvm`].
+//!
+//! For non-seismic transaction types the seismic EVM must behave exactly like the Ethereum EVM
+//! on the matching [`SpecId`], and seismic transactions must behave like the equivalent legacy
+//! transaction. [`DifferentialHarness`] executes the same signed transactions on both EVMs over
+//! clones of the same [`InMemoryDB`] and reports the first [`Divergence`], which makes it
+//! suitable as the body of a proptest or fuzz target.
+
+use crate::{block::SeismicAlloyReceiptBuilder, SeismicEvmFactory};
+use alloy_consensus::{
+    transaction::Recovered, Eip658Value, Receipt, ReceiptEnvelope, Signed, TxEip4844,
+    TxEip4844Variant, TxEnvelope, TxLegacy, TxReceipt,
+};
+use alloy_eips::Typed2718;
+use alloy_evm::{eth::EthEvmFactory, Evm, EvmEnv, EvmFactory, FromRecoveredTx};
+use alloy_primitives::{Address, Log};
+use revm::{
+    context::{
+        result::{ExecutionResult, ResultAndState},
+        TxEnv,
+    },
+    database::InMemoryDB,
+    primitives::hardfork::SpecId,
+    state::Account,
+    DatabaseCommit,
+};
+use seismic_alloy_consensus::{SeismicReceiptEnvelope, SeismicTxEnvelope};
+use seismic_revm::{
+    transaction::abstraction::SeismicTransaction, SeismicHaltReason, SeismicSpecId,
+};
+
+/// The first observed difference between [`EthEvm`] and
+/// [`SeismicEvm`](crate::SeismicEvm) executions.
+///
+/// [`EthEvm`]: alloy_evm::EthEvm
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("divergence at transaction {index}: {kind}")]
+pub struct Divergence {
+    /// Index of the diverging transaction.
+    pub index: usize,
+    /// What diverged.
+    pub kind: DivergenceKind,
+}
+
+/// Kind of a [`Divergence`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DivergenceKind {
+    /// Execution outcomes, including validation errors, differ.
+    #[error("execution outcome differs: eth {eth}, seismic {seismic}")]
+    Outcome {
+        /// Debug representation of the Ethereum outcome.
+        eth: String,
+        /// Debug representation of the seismic outcome.
+        seismic: String,
+    },
+    /// The post-transaction state of an account differs.
+    #[error("state of account {address} differs")]
+    State {
+        /// Address of the account.
+        address: Address,
+        /// Account as changed by the Ethereum EVM.
+        eth: Option<Box<Account>>,
+        /// Account as changed by the seismic EVM.
+        seismic: Option<Box<Account>>,
+    },
+    /// Receipts built from the execution results differ.
+    #[error("receipt differs: eth {eth}, seismic {seismic}")]
+    Receipt {
+        /// Debug representation of the Ethereum receipt.
+        eth: String,
+        /// Debug representation of the seismic receipt.
+        seismic: String,
+    },
+}
+
+/// Runs transactions through [`EthEvmFactory`] and [`SeismicEvmFactory`] and compares
+/// [`ResultAndState`]s and receipts.
+#[derive(Debug, Clone)]
+pub struct DifferentialHarness {
+    seismic: SeismicEvmFactory,
+    eth: EthEvmFactory,
+    env: EvmEnv<SeismicSpecId>,
+    eth_spec: Option<SpecId>,
+}
+
+impl DifferentialHarness {
+    /// Creates a new harness executing with the given environment.
+    ///
+    /// The Ethereum side runs on the [`SpecId`] matching the seismic spec of the environment,
+    /// see [`Self::eth_spec`].
+    pub fn new(seismic: SeismicEvmFactory, env: EvmEnv<SeismicSpecId>) -> Self {
+        Self { seismic, eth: EthEvmFactory::default(), env, eth_spec: None }
+    }
+
+    /// Overrides the [`SpecId`] the Ethereum EVM is compared on.
+    pub const fn with_eth_spec(mut self, eth_spec: SpecId) -> Self {
+        self.eth_spec = Some(eth_spec);
+        self
+    }
+
+    /// Returns the [`SpecId`] the Ethereum EVM is compared on.
+    ///
+    /// This is the Ethereum spec matching the seismic spec of the environment, unless overridden
+    /// with [`Self::with_eth_spec`].
+    pub fn eth_spec(&self) -> SpecId {
+        self.eth_spec.unwrap_or_else(|| self.env.cfg_env.spec.into())
+    }
+
+    /// Executes `txs` in order on clones of `db`, committing the state of every transaction on
+    /// both sides.
+    ///
+    /// The seismic EVM receives the transactions through their
+    /// [`FromRecoveredTx<SeismicTxEnvelope>`] conversion, the Ethereum EVM through the conversion
+    /// of the matching [`TxEnvelope`]. Seismic transactions run as the legacy transaction with
+    /// the same fields on the Ethereum side and are expected to be decrypted already, as the
+    /// block executor does before executing them.
+    ///
+    /// Returns the Ethereum receipts if both EVMs agreed on every transaction, or the first
+    /// [`Divergence`] otherwise. Transactions that are invalid on both EVMs with the same error
+    /// are skipped.
+    pub fn run(
+        &self,
+        db: &InMemoryDB,
+        txs: impl IntoIterator<Item = Recovered<SeismicTxEnvelope>>,
+    ) -> Result<Vec<ReceiptEnvelope>, Divergence> {
+        let eth_env = EvmEnv {
+            cfg_env: self.env.cfg_env.clone().with_spec(self.eth_spec()),
+            block_env: self.env.block_env.clone(),
+        };
+        let mut eth_evm = self.eth.create_evm(db.clone(), eth_env);
+        let mut seismic_evm = self.seismic.create_evm(db.clone(), self.env.clone());
+
+        let mut receipts = Vec::new();
+        let mut cumulative_gas_used = 0;
+
+        for (index, tx) in txs.into_iter().enumerate() {
+            let divergence = |kind| Divergence { index, kind };
+
+            let eth_tx = eth_envelope(tx.inner());
+            let eth_outcome = eth_evm.transact(TxEnv::from_recovered_tx(&eth_tx, tx.signer()));
+            let seismic_outcome = seismic_evm
+                .transact(SeismicTransaction::<TxEnv>::from_recovered_tx(tx.inner(), tx.signer()));
+
+            let (eth, seismic) = match (eth_outcome, seismic_outcome) {
+                (Ok(eth), Ok(seismic)) => (eth, seismic),
+                (Err(eth), Err(seismic)) if eth == seismic => continue,
+                (eth, seismic) => {
+                    return Err(divergence(DivergenceKind::Outcome {
+                        eth: format!("{eth:?}"),
+                        seismic: format!("{seismic:?}"),
+                    }))
+                }
+            };
+
+            compare_result_and_state(&eth, &seismic).map_err(divergence)?;
+
+            cumulative_gas_used += eth.result.gas_used();
+            let eth_receipt = eth_receipt(&eth_tx, &eth.result, cumulative_gas_used);
+            let seismic_receipt =
+                seismic_receipt(tx.inner().ty(), &seismic.result, cumulative_gas_used);
+            if !receipts_match(tx.inner().ty(), &eth_receipt, seismic_receipt.as_ref()) {
+                return Err(divergence(DivergenceKind::Receipt {
+                    eth: format!("{eth_receipt:?}"),
+                    seismic: format!("{seismic_receipt:?}"),
+                }));
+            }
+
+            eth_evm.db_mut().commit(eth.state);
+            seismic_evm.db_mut().commit(seismic.state);
+            receipts.push(eth_receipt);
+        }
+
+        Ok(receipts)
+    }
+}
+
+/// Returns the Ethereum transaction executed in place of `tx`.
+///
+/// Seismic transactions are mapped to the legacy transaction with the same fields.
+fn eth_envelope(tx: &SeismicTxEnvelope) -> TxEnvelope {
+    match tx {
+        SeismicTxEnvelope::Legacy(tx) => TxEnvelope::Legacy(tx.clone()),
+        SeismicTxEnvelope::Eip2930(tx) => TxEnvelope::Eip2930(tx.clone()),
+        SeismicTxEnvelope::Eip1559(tx) => TxEnvelope::Eip1559(tx.clone()),
+        SeismicTxEnvelope::Eip4844(tx) => TxEnvelope::Eip4844(Signed::new_unchecked(
+            TxEip4844Variant::from(AsRef::<TxEip4844>::as_ref(tx.tx()).clone()),
+            *tx.signature(),
+            *tx.hash(),
+        )),
+        SeismicTxEnvelope::Eip7702(tx) => TxEnvelope::Eip7702(tx.clone()),
+        SeismicTxEnvelope::Seismic(tx) => {
+            let seismic = tx.tx();
+            let legacy = TxLegacy {
+                chain_id: Some(seismic.chain_id),
+                nonce: seismic.nonce,
+                gas_price: seismic.gas_price,
+                gas_limit: seismic.gas_limit,
+                to: seismic.to,
+                value: seismic.value,
+                input: seismic.input.clone(),
+            };
+            TxEnvelope::Legacy(Signed::new_unchecked(legacy, *tx.signature(), *tx.hash()))
+        }
+    }
+}
+
+/// Compares the results and the changed accounts of both executions.
+fn compare_result_and_state(
+    eth: &ResultAndState,
+    seismic: &ResultAndState<SeismicHaltReason>,
+) -> Result<(), DivergenceKind> {
+    let eth_result = eth.result.clone().map_haltreason(SeismicHaltReason::from);
+    if eth_result != seismic.result {
+        return Err(DivergenceKind::Outcome {
+            eth: format!("{eth_result:?}"),
+            seismic: format!("{:?}", seismic.result),
+        });
+    }
+
+    for address in eth.state.keys().chain(seismic.state.keys()) {
+        let (eth, seismic) = (eth.state.get(address), seismic.state.get(address));
+        if eth != seismic {
+            return Err(DivergenceKind::State {
+                address: *address,
+                eth: eth.cloned().map(Box::new),
+                seismic: seismic.cloned().map(Box::new),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Builds the receipt the Ethereum block executor would produce.
+fn eth_receipt<H>(
+    tx: &TxEnvelope,
+    result: &ExecutionResult<H>,
+    cumulative_gas_used: u64,
+) -> ReceiptEnvelope {
+    ReceiptEnvelope::from_typed(tx.tx_type(), receipt(result, cumulative_gas_used).with_bloom())
+}
+
+/// Builds the receipt the seismic block executor would produce, if the type is supported.
+fn seismic_receipt<H>(
+    tx_type: u8,
+    result: &ExecutionResult<H>,
+    cumulative_gas_used: u64,
+) -> Option<SeismicReceiptEnvelope> {
+    SeismicAlloyReceiptBuilder::build_envelope(
+        tx_type,
+        receipt(result, cumulative_gas_used).with_bloom(),
+    )
+    .ok()
+}
+
+fn receipt<H>(result: &ExecutionResult<H>, cumulative_gas_used: u64) -> Receipt<Log> {
+    Receipt {
+        status: Eip658Value::Eip658(result.is_success()),
+        cumulative_gas_used,
+        logs: result.logs().to_vec(),
+    }
+}
+
+/// Returns whether both receipts have the same contents and the seismic receipt has the type of
+/// the executed transaction.
+///
+/// Blob transactions don't have a seismic receipt, which is a known difference and is ignored.
+fn receipts_match(
+    tx_type: u8,
+    eth: &ReceiptEnvelope,
+    seismic: Option<&SeismicReceiptEnvelope>,
+) -> bool {
+    match seismic {
+        Some(seismic) => {
+            seismic.ty() == tx_type
+                && eth.status() == seismic.status()
+                && eth.cumulative_gas_used() == seismic.cumulative_gas_used()
+                && eth.logs() == seismic.logs()
+                && eth.bloom() == seismic.bloom()
+        }
+        None => eth.is_eip4844(),
+    }
+}
+
+#[cfg(all(test, feature = "enclave"))]
+mod tests {
+    use super::*;
+    use alloy_consensus::TxEip1559;
+    use alloy_eips::eip2718::{EIP1559_TX_TYPE_ID, LEGACY_TX_TYPE_ID};
+    use alloy_primitives::{address, aliases::U96, Bytes, Signature, TxKind, B256, U256};
+    use proptest::prelude::*;
+    use revm::{
+        bytecode::Bytecode,
+        context::{BlockEnv, CfgEnv},
+        state::AccountInfo,
+    };
+    use seismic_alloy_consensus::{TxSeismic, TxSeismicElements, SEISMIC_TX_TYPE_ID};
+    use seismic_enclave::{keys::GetPurposeKeysResponse, PublicKey, Secp256k1, SecretKey};
+    use std::sync::OnceLock;
+
+    const CHAIN_ID: u64 = 5124;
+    const SENDER: Address = address!("0x000000000000000000000000000000000000a11c");
+    /// Stores calldata word 0 into slot 0, logs it and reverts if it is zero.
+    const CONTRACT: Address = address!("0x000000000000000000000000000000000000c0de");
+
+    fn harness() -> DifferentialHarness {
+        static KEYS: OnceLock<GetPurposeKeysResponse> = OnceLock::new();
+        let keys = KEYS.get_or_init(|| {
+            seismic_enclave::MockEnclaveServer::get_purpose_keys(
+                seismic_enclave::keys::GetPurposeKeysRequest { epoch: 0 },
+            )
+        });
+
+        let mut cfg_env = CfgEnv::new_with_spec(SeismicSpecId::MERCURY);
+        cfg_env.chain_id = CHAIN_ID;
+        let block_env = BlockEnv { gas_limit: 30_000_000, basefee: 7, ..Default::default() };
+
+        DifferentialHarness::new(
+            SeismicEvmFactory::new_with_purpose_keys(keys),
+            EvmEnv { cfg_env, block_env },
+        )
+    }
+
+    fn db() -> InMemoryDB {
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            SENDER,
+            AccountInfo { balance: U256::from(10u128.pow(20)), ..Default::default() },
+        );
+        // CALLDATALOAD(0) DUP1 SSTORE(0) DUP1 MSTORE(0) LOG0(0, 32) PUSH1 JUMPI(ok) REVERT
+        let code = Bytecode::new_raw(Bytes::from_static(&[
+            0x60, 0x00, 0x35, 0x80, 0x60, 0x00, 0x55, 0x80, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60,
+            0x00, 0xa0, 0x60, 0x18, 0x57, 0x60, 0x00, 0x60, 0x00, 0xfd, 0x5b, 0x00,
+        ]));
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo { code_hash: code.hash_slow(), code: Some(code), ..Default::default() },
+        );
+        db
+    }
+
+    fn signed<T>(tx: T) -> Signed<T> {
+        Signed::new_unchecked(tx, Signature::test_signature(), B256::ZERO)
+    }
+
+    /// Builds a signed transaction of type `ty` from [`SENDER`].
+    fn tx(
+        ty: u8,
+        nonce: u64,
+        to: Address,
+        value: u64,
+        data: Vec<u8>,
+        gas_limit: u64,
+    ) -> Recovered<SeismicTxEnvelope> {
+        let (to, value, input) = (TxKind::Call(to), U256::from(value), Bytes::from(data));
+        let tx = match ty {
+            LEGACY_TX_TYPE_ID => SeismicTxEnvelope::Legacy(signed(TxLegacy {
+                chain_id: Some(CHAIN_ID),
+                nonce,
+                gas_price: 10,
+                gas_limit,
+                to,
+                value,
+                input,
+            })),
+            EIP1559_TX_TYPE_ID => SeismicTxEnvelope::Eip1559(signed(TxEip1559 {
+                chain_id: CHAIN_ID,
+                nonce,
+                gas_limit,
+                max_fee_per_gas: 10,
+                max_priority_fee_per_gas: 2,
+                to,
+                value,
+                input,
+                ..Default::default()
+            })),
+            SEISMIC_TX_TYPE_ID => {
+                let encryption_sk = SecretKey::from_slice(&[1; 32]).unwrap();
+                SeismicTxEnvelope::Seismic(signed(TxSeismic {
+                    chain_id: CHAIN_ID,
+                    nonce,
+                    gas_price: 10,
+                    gas_limit,
+                    to,
+                    value,
+                    input,
+                    seismic_elements: TxSeismicElements {
+                        encryption_pubkey: PublicKey::from_secret_key(
+                            &Secp256k1::new(),
+                            &encryption_sk,
+                        ),
+                        encryption_nonce: U96::from(1),
+                        message_version: 0,
+                    },
+                }))
+            }
+            _ => unreachable!("unsupported transaction type {ty}"),
+        };
+        Recovered::new_unchecked(tx, SENDER)
+    }
+
+    #[test]
+    fn compares_on_matching_spec() {
+        let harness = harness();
+        assert_eq!(harness.eth_spec(), SpecId::from(SeismicSpecId::MERCURY));
+        assert_eq!(harness.with_eth_spec(SpecId::SHANGHAI).eth_spec(), SpecId::SHANGHAI);
+    }
+
+    #[test]
+    fn matches_on_simple_transactions() {
+        let beef = address!("0x000000000000000000000000000000000000beef");
+        let mut calldata = vec![0; 32];
+        calldata[31] = 0x2a;
+        let txs = [
+            tx(LEGACY_TX_TYPE_ID, 0, beef, 1000, vec![], 21_000),
+            tx(LEGACY_TX_TYPE_ID, 1, CONTRACT, 0, calldata, 100_000),
+            tx(LEGACY_TX_TYPE_ID, 2, CONTRACT, 0, vec![0; 32], 100_000),
+            // out of gas
+            tx(LEGACY_TX_TYPE_ID, 3, CONTRACT, 0, vec![1; 32], 22_000),
+            // invalid nonce on both sides
+            tx(LEGACY_TX_TYPE_ID, 9, CONTRACT, 0, vec![], 100_000),
+        ];
+
+        let receipts = harness().run(&db(), txs).unwrap();
+        assert_eq!(receipts.len(), 4);
+        assert!(!receipts[2].status());
+    }
+
+    #[test]
+    fn matches_on_eip1559_and_seismic_transactions() {
+        let txs = [
+            tx(EIP1559_TX_TYPE_ID, 0, CONTRACT, 0, vec![2; 32], 100_000),
+            tx(SEISMIC_TX_TYPE_ID, 1, CONTRACT, 0, vec![3; 32], 100_000),
+            tx(SEISMIC_TX_TYPE_ID, 2, CONTRACT, 0, vec![0; 32], 100_000),
+        ];
+
+        let receipts = harness().run(&db(), txs).unwrap();
+        assert_eq!(receipts.len(), 3);
+        assert!(receipts[0].is_eip1559());
+        assert!(receipts[1].status());
+        assert!(!receipts[2].status());
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn eth_and_seismic_evm_agree(
+            txs in prop::collection::vec(
+                (
+                    prop::sample::select(vec![
+                        LEGACY_TX_TYPE_ID,
+                        EIP1559_TX_TYPE_ID,
+                        SEISMIC_TX_TYPE_ID,
+                    ]),
+                    prop::sample::select(vec![CONTRACT, SENDER, Address::with_last_byte(1)]),
+                    0u64..1_000_000,
+                    prop::collection::vec(any::<u8>(), 0..64),
+                    21_000u64..200_000,
+                ),
+                1..8,
+            )
+        ) {
+            let txs = txs.into_iter().enumerate().map(|(nonce, (ty, to, value, data, gas))| {
+                tx(ty, nonce as u64, to, value, data, gas)
+            });
+
+            if let Err(divergence) = harness().run(&db(), txs) {
+                prop_assert!(false, "{divergence}");
+            }
+        }
+    }
+}
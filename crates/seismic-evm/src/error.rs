@@ -0,0 +1,356 @@
+//! Decoding of seismic execution outcomes into user-facing errors.
+//!
+//! [`SeismicExecutionError`] is the single representation of failed executions shared by the
+//! call, gas estimation and receipt paths, so that RPC users see the same code and message no
+//! matter which endpoint surfaced the failure:
+//!
+//! - results of calls are decoded with [`SeismicExecutionError::from_result`],
+//! - gas estimation errors with [`SeismicExecutionError::from_estimate_error`], with the
+//!   `call-util` feature,
+//! - receipts carry the [`SeismicErrorCode`] of failed transactions in their metadata, see
+//!   [`SeismicReceiptMetadata::error`](crate::block::receipt_builder::SeismicReceiptMetadata::error).
+
+use alloc::{
+    format,
+    string::{String, ToString},
+};
+use alloy_primitives::{Bytes, U256};
+use core::any::Any;
+use revm::context::result::{ExecutionResult, HaltReason, OutOfGasError};
+use seismic_revm::SeismicHaltReason;
+
+/// Selector of the solidity `Error(string)` revert.
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// Selector of the solidity `Panic(uint256)` revert.
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// Stable error codes of failed seismic executions.
+///
+/// The generic codes follow the ones used by Ethereum JSON-RPC servers, seismic specific halts
+/// have dedicated codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SeismicErrorCode {
+    /// Execution reverted.
+    Reverted,
+    /// Execution ran out of gas.
+    OutOfGas,
+    /// Execution halted for a non-seismic reason other than running out of gas.
+    Halted,
+    /// Confidential storage was accessed with a public storage instruction.
+    InvalidPrivateStorageAccess,
+    /// Public storage was accessed with a confidential storage instruction.
+    InvalidPublicStorageAccess,
+}
+
+impl SeismicErrorCode {
+    /// Returns the numeric code of the error.
+    pub const fn code(&self) -> i32 {
+        match self {
+            Self::Reverted => 3,
+            Self::OutOfGas | Self::Halted => -32000,
+            Self::InvalidPrivateStorageAccess => -32100,
+            Self::InvalidPublicStorageAccess => -32101,
+        }
+    }
+}
+
+impl SeismicErrorCode {
+    /// Returns the code of a failed execution, or `None` if the execution succeeded.
+    ///
+    /// Halts are decoded if the halt reason is a [`SeismicHaltReason`] or a [`HaltReason`], other
+    /// halt reasons are reported as [`Self::Halted`]. This allows decoding the results of any EVM,
+    /// e.g. when building receipts.
+    pub fn from_result<H: 'static>(result: &ExecutionResult<H>) -> Option<Self> {
+        let reason = match result {
+            ExecutionResult::Success { .. } => return None,
+            ExecutionResult::Revert { .. } => return Some(Self::Reverted),
+            ExecutionResult::Halt { reason, .. } => reason as &dyn Any,
+        };
+        let code = if let Some(reason) = reason.downcast_ref::<SeismicHaltReason>() {
+            SeismicExecutionError::from_halt(reason).code
+        } else if let Some(reason) = reason.downcast_ref::<HaltReason>() {
+            SeismicExecutionError::from_halt(&SeismicHaltReason::Base(reason.clone())).code
+        } else {
+            Self::Halted
+        };
+        Some(code)
+    }
+}
+
+/// Reason decoded from revert output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RevertReason {
+    /// Solidity `Error(string)`, as produced by `require` and `revert` with a message.
+    Error(String),
+    /// Solidity `Panic(uint256)`, as produced by failed assertions and checked arithmetic.
+    Panic(U256),
+}
+
+impl RevertReason {
+    /// Decodes a solidity `Error(string)` or `Panic(uint256)` from revert output.
+    ///
+    /// Returns `None` for custom errors and malformed output.
+    pub fn decode(output: &[u8]) -> Option<Self> {
+        let (selector, data) = output.split_first_chunk::<4>()?;
+        match *selector {
+            ERROR_SELECTOR => {
+                let offset = word_as_usize(data.get(..32)?)?;
+                let len = word_as_usize(data.get(offset..offset.checked_add(32)?)?)?;
+                let start = offset + 32;
+                let bytes = data.get(start..start.checked_add(len)?)?;
+                Some(Self::Error(String::from_utf8_lossy(bytes).into_owned()))
+            }
+            PANIC_SELECTOR => Some(Self::Panic(U256::from_be_slice(data.get(..32)?))),
+            _ => None,
+        }
+    }
+
+    /// Returns a human readable description of the reason.
+    pub fn message(&self) -> String {
+        match self {
+            Self::Error(reason) => reason.clone(),
+            Self::Panic(code) => {
+                let description = match code.saturating_to::<u64>() {
+                    0x00 => "generic panic",
+                    0x01 => "assertion failed",
+                    0x11 => "arithmetic underflow or overflow",
+                    0x12 => "division or modulo by zero",
+                    0x21 => "invalid enum value",
+                    0x22 => "invalid encoded storage byte array",
+                    0x31 => "pop on empty array",
+                    0x32 => "array index out of bounds",
+                    0x41 => "out of memory",
+                    0x51 => "call to uninitialized function",
+                    _ => "unknown panic code",
+                };
+                format!("panic: {description} ({code:#x})")
+            }
+        }
+    }
+}
+
+/// Output of a reverted execution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RevertData {
+    /// Raw revert output.
+    pub output: Bytes,
+    /// Reason decoded from the output, if it is a standard solidity error.
+    pub reason: Option<RevertReason>,
+}
+
+/// A failed seismic execution, decoded for RPC users.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{message}")]
+pub struct SeismicExecutionError {
+    /// Stable error code.
+    pub code: SeismicErrorCode,
+    /// Human readable error message.
+    pub message: String,
+    /// Revert output, if the execution reverted.
+    pub revert: Option<RevertData>,
+}
+
+impl SeismicExecutionError {
+    /// Decodes the error of a failed execution, returns `None` if the execution succeeded.
+    pub fn from_result(result: &ExecutionResult<SeismicHaltReason>) -> Option<Self> {
+        match result {
+            ExecutionResult::Success { .. } => None,
+            ExecutionResult::Revert { output, .. } => Some(Self::from_revert(output.clone())),
+            ExecutionResult::Halt { reason, .. } => Some(Self::from_halt(reason)),
+        }
+    }
+
+    /// Decodes the error of a failed [`estimate_gas`](alloy_evm::call::estimate_gas) call.
+    ///
+    /// Returns `None` for errors that aren't caused by the execution of the transaction, like
+    /// database errors and invalid transactions.
+    #[cfg(feature = "call-util")]
+    pub fn from_estimate_error<DbError, EvmError>(
+        err: &alloy_evm::call::EstimateGasError<DbError, EvmError, SeismicHaltReason>,
+    ) -> Option<Self> {
+        use alloy_evm::call::EstimateGasError;
+
+        match err {
+            EstimateGasError::Reverted { output, .. } => Some(Self::from_revert(output.clone())),
+            EstimateGasError::Halted { reason, .. } => Some(Self::from_halt(reason)),
+            EstimateGasError::OutOfGas { gas_limit } => Some(Self {
+                code: SeismicErrorCode::OutOfGas,
+                message: format!("gas required exceeds allowance ({gas_limit})"),
+                revert: None,
+            }),
+            EstimateGasError::Call(_) | EstimateGasError::Evm(_) => None,
+        }
+    }
+
+    /// Creates the error of an execution that reverted with the given output.
+    pub fn from_revert(output: Bytes) -> Self {
+        let reason = RevertReason::decode(&output);
+        let message = match &reason {
+            Some(reason) => format!("execution reverted: {}", reason.message()),
+            None => "execution reverted".to_string(),
+        };
+        Self {
+            code: SeismicErrorCode::Reverted,
+            message,
+            revert: Some(RevertData { output, reason }),
+        }
+    }
+
+    /// Creates the error of an execution that halted with the given reason.
+    pub fn from_halt(reason: &SeismicHaltReason) -> Self {
+        let (code, message) = match reason {
+            SeismicHaltReason::Base(HaltReason::OutOfGas(err)) => {
+                let message = match err {
+                    OutOfGasError::Basic | OutOfGasError::ReentrancySentry => "out of gas",
+                    OutOfGasError::MemoryLimit | OutOfGasError::Memory => "out of gas: memory",
+                    OutOfGasError::Precompile => "out of gas: precompile",
+                    OutOfGasError::InvalidOperand => "out of gas: invalid operand",
+                };
+                (SeismicErrorCode::OutOfGas, message.to_string())
+            }
+            SeismicHaltReason::Base(reason) => {
+                (SeismicErrorCode::Halted, format!("execution halted: {reason:?}"))
+            }
+            SeismicHaltReason::InvalidPrivateStorageAccess => (
+                SeismicErrorCode::InvalidPrivateStorageAccess,
+                "invalid private storage access: confidential slot accessed with SLOAD or SSTORE"
+                    .to_string(),
+            ),
+            SeismicHaltReason::InvalidPublicStorageAccess => (
+                SeismicErrorCode::InvalidPublicStorageAccess,
+                "invalid public storage access: public slot accessed with CLOAD or CSTORE"
+                    .to_string(),
+            ),
+        };
+        Self { code, message, revert: None }
+    }
+}
+
+/// Interprets an ABI word as a `usize`, returns `None` if it doesn't fit.
+fn word_as_usize(word: &[u8]) -> Option<usize> {
+    U256::from_be_slice(word).try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_error_string() {
+        let mut output = ERROR_SELECTOR.to_vec();
+        output.extend(U256::from(32).to_be_bytes::<32>());
+        output.extend(U256::from(11).to_be_bytes::<32>());
+        output.extend(b"not allowed");
+        output.resize(4 + 3 * 32, 0);
+
+        let err = SeismicExecutionError::from_revert(output.into());
+        assert_eq!(err.code, SeismicErrorCode::Reverted);
+        assert_eq!(err.code.code(), 3);
+        assert_eq!(err.message, "execution reverted: not allowed");
+        assert_eq!(
+            err.revert.unwrap().reason,
+            Some(RevertReason::Error("not allowed".to_string()))
+        );
+    }
+
+    #[test]
+    fn decodes_panic() {
+        let mut output = PANIC_SELECTOR.to_vec();
+        output.extend(U256::from(0x11).to_be_bytes::<32>());
+        let err = SeismicExecutionError::from_revert(output.into());
+        assert_eq!(
+            err.message,
+            "execution reverted: panic: arithmetic underflow or overflow (0x11)"
+        );
+        assert_eq!(err.revert.unwrap().reason, Some(RevertReason::Panic(U256::from(0x11))));
+    }
+
+    #[test]
+    fn keeps_custom_revert_output() {
+        let output = Bytes::from_static(&[0xde, 0xad, 0xbe, 0xef, 0x01]);
+        let err = SeismicExecutionError::from_revert(output.clone());
+        assert_eq!(err.message, "execution reverted");
+        assert_eq!(err.revert, Some(RevertData { output, reason: None }));
+    }
+
+    #[test]
+    fn decodes_halts() {
+        let result = ExecutionResult::Halt {
+            reason: SeismicHaltReason::InvalidPrivateStorageAccess,
+            gas_used: 21_000,
+        };
+        let err = SeismicExecutionError::from_result(&result).unwrap();
+        assert_eq!(err.code, SeismicErrorCode::InvalidPrivateStorageAccess);
+        assert_eq!(err.code.code(), -32100);
+        assert!(err.revert.is_none());
+
+        let err = SeismicExecutionError::from_halt(&SeismicHaltReason::Base(HaltReason::OutOfGas(
+            OutOfGasError::Basic,
+        )));
+        assert_eq!(err.code, SeismicErrorCode::OutOfGas);
+        assert_eq!(err.to_string(), "out of gas");
+    }
+
+    #[test]
+    fn decodes_error_codes_of_any_result() {
+        let halt = |reason| ExecutionResult::Halt { reason, gas_used: 21_000 };
+        assert_eq!(
+            SeismicErrorCode::from_result(&halt(SeismicHaltReason::InvalidPublicStorageAccess)),
+            Some(SeismicErrorCode::InvalidPublicStorageAccess)
+        );
+        assert_eq!(
+            SeismicErrorCode::from_result(&ExecutionResult::Halt {
+                reason: HaltReason::OutOfGas(OutOfGasError::Memory),
+                gas_used: 21_000,
+            }),
+            Some(SeismicErrorCode::OutOfGas)
+        );
+        assert_eq!(
+            SeismicErrorCode::from_result(&ExecutionResult::<HaltReason>::Revert {
+                output: Bytes::new(),
+                gas_used: 21_000,
+            }),
+            Some(SeismicErrorCode::Reverted)
+        );
+    }
+
+    #[cfg(feature = "call-util")]
+    #[test]
+    fn decodes_estimate_errors() {
+        use alloy_evm::call::EstimateGasError;
+        use core::convert::Infallible;
+        use revm::context::result::EVMError;
+
+        type Error = EstimateGasError<Infallible, EVMError<Infallible>, SeismicHaltReason>;
+
+        let err =
+            Error::Halted { reason: SeismicHaltReason::InvalidPrivateStorageAccess, gas_used: 0 };
+        assert_eq!(
+            SeismicExecutionError::from_estimate_error(&err).map(|err| err.code),
+            Some(SeismicErrorCode::InvalidPrivateStorageAccess)
+        );
+
+        let err = Error::OutOfGas { gas_limit: 30_000_000 };
+        let decoded = SeismicExecutionError::from_estimate_error(&err).unwrap();
+        assert_eq!(decoded.code, SeismicErrorCode::OutOfGas);
+        assert_eq!(decoded.message, err.to_string());
+
+        assert_eq!(
+            SeismicExecutionError::from_estimate_error(&Error::Evm(EVMError::Custom("db".into()))),
+            None
+        );
+    }
+
+    #[test]
+    fn success_is_not_an_error() {
+        let result = ExecutionResult::<SeismicHaltReason>::Success {
+            reason: revm::context::result::SuccessReason::Stop,
+            gas_used: 21_000,
+            gas_refunded: 0,
+            logs: Vec::new(),
+            output: revm::context::result::Output::Call(Bytes::new()),
+        };
+        assert_eq!(SeismicExecutionError::from_result(&result), None);
+    }
+}
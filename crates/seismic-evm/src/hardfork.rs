@@ -1,5 +1,6 @@
 //! Seismic hardforks.
 
+use alloc::vec::Vec;
 use alloy_evm::eth::spec::EthExecutorSpec;
 use alloy_hardforks::{hardfork, EthereumHardfork, EthereumHardforks, ForkCondition};
 use alloy_primitives::Address;
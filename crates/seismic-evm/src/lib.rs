@@ -7,6 +7,9 @@
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 #![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
+use alloc::vec::Vec;
 use alloy_evm::{Database, Evm, EvmEnv, EvmFactory, IntoTxEnv};
 use alloy_primitives::{Address, Bytes, TxKind, U256};
 use core::ops::{Deref, DerefMut};
@@ -30,7 +33,13 @@ use seismic_revm::{
 };
 
 pub mod block;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod differential;
+pub mod error;
 pub mod hardfork;
+pub mod precompiles;
+
+use precompiles::SeismicPrecompilesMap;
 
 /// Seismic EVM implementation.
 ///
@@ -257,74 +266,100 @@ where
 }
 
 /// Factory producing [`SeismicEvm`]s.
+///
+/// With the `enclave` feature, the factory is created with purpose keys fetched from the enclave
+/// at boot time, and the RNG precompile of every created EVM uses the stored RNG keypair.
+/// Without it, EVMs use the default seismic context.
+///
+/// Created EVMs use [`SeismicPrecompilesMap`], so their precompiles can be modified, cached and
+/// profiled like those of other EVMs, except for the RNG precompile.
 #[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "enclave"), derive(Default))]
 #[non_exhaustive]
-// Factory that creates SeismicEVMs with pre-fetched purpose keys.
-// The purpose keys are provided at boot time and stored globally.
 pub struct SeismicEvmFactory {
+    #[cfg(feature = "enclave")]
     purpose_keys: &'static seismic_enclave::keys::GetPurposeKeysResponse,
 }
 
 impl SeismicEvmFactory {
+    /// Creates a new [`SeismicEvmFactory`] using the default seismic context.
+    #[cfg(not(feature = "enclave"))]
+    pub const fn new() -> Self {
+        Self {}
+    }
+
     /// Creates a new [`SeismicEvmFactory`] with pre-fetched purpose keys.
-    pub fn new_with_purpose_keys(
+    #[cfg(feature = "enclave")]
+    pub const fn new_with_purpose_keys(
         purpose_keys: &'static seismic_enclave::keys::GetPurposeKeysResponse,
     ) -> Self {
         Self { purpose_keys }
     }
 
-    /// Create an EVM using the stored RNG keypair.
+    /// Create an EVM using the stored RNG keypair, if any.
     pub fn create_evm_with_rng_key<DB: Database>(
         &self,
         db: DB,
         input: EvmEnv<SeismicSpecId>,
-    ) -> SeismicEvm<DB, NoOpInspector> {
+    ) -> SeismicEvm<DB, NoOpInspector, SeismicPrecompilesMap<SeismicContext<DB>>> {
         let context = self.create_context_with_rng_key();
+        let spec = input.cfg_env.spec;
 
         SeismicEvm {
             inner: context
                 .with_db(db)
                 .with_block(input.block_env)
                 .with_cfg(input.cfg_env)
-                .build_seismic_evm_with_inspector(NoOpInspector {}),
+                .build_seismic_evm_with_inspector(NoOpInspector {})
+                .with_precompiles(SeismicPrecompilesMap::new(spec)),
             inspect: false,
         }
     }
 
-    /// Create SeismicContext with the RNG key from purpose keys
+    /// Create SeismicContext with the RNG key from purpose keys.
+    #[cfg(feature = "enclave")]
     fn create_context_with_rng_key(&self) -> SeismicContext<EmptyDB> {
         SeismicContext::seismic_with_rng_key(self.purpose_keys.rng_keypair.clone())
     }
 
-    /// Create an EVM with inspector using the stored RNG keypair.
+    /// Create the default SeismicContext, there is no RNG key without the `enclave` feature.
+    #[cfg(not(feature = "enclave"))]
+    fn create_context_with_rng_key(&self) -> SeismicContext<EmptyDB> {
+        SeismicContext::seismic()
+    }
+
+    /// Create an EVM with inspector using the stored RNG keypair, if any.
     pub fn create_evm_with_inspector_and_rng_key<DB: Database, I: Inspector<SeismicContext<DB>>>(
         &self,
         db: DB,
         input: EvmEnv<SeismicSpecId>,
         inspector: I,
-    ) -> SeismicEvm<DB, I> {
+    ) -> SeismicEvm<DB, I, SeismicPrecompilesMap<SeismicContext<DB>>> {
         let context = self.create_context_with_rng_key();
+        let spec = input.cfg_env.spec;
 
         SeismicEvm {
             inner: context
                 .with_db(db)
                 .with_block(input.block_env)
                 .with_cfg(input.cfg_env)
-                .build_seismic_evm_with_inspector(inspector),
+                .build_seismic_evm_with_inspector(inspector)
+                .with_precompiles(SeismicPrecompilesMap::new(spec)),
             inspect: true,
         }
     }
 }
 
 impl EvmFactory for SeismicEvmFactory {
-    type Evm<DB: Database, I: Inspector<SeismicContext<DB>>> = SeismicEvm<DB, I>;
+    type Evm<DB: Database, I: Inspector<SeismicContext<DB>>> =
+        SeismicEvm<DB, I, Self::Precompiles<DB>>;
     type Context<DB: Database> = SeismicContext<DB>;
     type Tx = SeismicTransaction<TxEnv>;
     type Error<DBError: core::error::Error + Send + Sync + 'static> =
         EVMError<DBError, InvalidTransaction>;
     type HaltReason = SeismicHaltReason;
     type Spec = SeismicSpecId;
-    type Precompiles<DB: Database> = SeismicPrecompiles<Self::Context<DB>>;
+    type Precompiles<DB: Database> = SeismicPrecompilesMap<Self::Context<DB>>;
 
     fn create_evm<DB: Database>(
         &self,
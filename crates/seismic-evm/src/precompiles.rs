@@ -0,0 +1,226 @@
+//! Precompiles of the seismic EVM.
+
+use alloc::{boxed::Box, string::String};
+use alloy_evm::{precompiles::PrecompilesMap, Database};
+use alloy_primitives::{address, Address};
+use core::{
+    borrow::{Borrow, BorrowMut},
+    ops::{Deref, DerefMut},
+};
+use revm::{
+    handler::PrecompileProvider,
+    interpreter::{CallInputs, InterpreterResult},
+};
+use seismic_revm::{precompiles::SeismicPrecompiles, SeismicContext, SeismicSpecId};
+
+/// Address of the RNG precompile.
+///
+/// The RNG precompile reads the RNG state of the seismic context, so it is called through
+/// [`SeismicPrecompiles`] instead of a [`PrecompilesMap`].
+pub const RNG_PRECOMPILE_ADDRESS: Address = address!("0x0000000000000000000000000000000000000064");
+
+/// Precompiles of a [`SeismicEvm`](crate::SeismicEvm).
+///
+/// All precompiles but the RNG precompile are called through a [`PrecompilesMap`], which this
+/// type dereferences to. They can be modified, cached and profiled like the precompiles of other
+/// EVMs, and have access to the transaction and configuration of the EVM, see
+/// [`EvmInternals`](alloy_evm::EvmInternals).
+///
+/// The RNG precompile, see [`RNG_PRECOMPILE_ADDRESS`], is called through
+/// [`SeismicPrecompiles`] unless a precompile is added at its address. It isn't part of the map,
+/// so it can't be wrapped or moved.
+///
+/// The precompiles of the map are those of the spec the map was created for. State overrides
+/// moving precompiles, e.g. in simulated blocks, apply to the map.
+pub struct SeismicPrecompilesMap<CTX> {
+    map: PrecompilesMap,
+    seismic: SeismicPrecompiles<CTX>,
+}
+
+impl<CTX> SeismicPrecompilesMap<CTX> {
+    /// Creates the seismic precompiles for the given spec.
+    pub fn new(spec: SeismicSpecId) -> Self {
+        let seismic = SeismicPrecompiles::new_with_spec(spec);
+        let mut map = PrecompilesMap::from_static(seismic.precompiles());
+        map.apply_precompile(&RNG_PRECOMPILE_ADDRESS, |_| None);
+        Self { map, seismic }
+    }
+
+    /// Returns the precompiles called through a [`PrecompilesMap`].
+    pub const fn map(&self) -> &PrecompilesMap {
+        &self.map
+    }
+
+    /// Returns a mutable reference to the precompiles called through a [`PrecompilesMap`].
+    pub fn map_mut(&mut self) -> &mut PrecompilesMap {
+        &mut self.map
+    }
+}
+
+impl<CTX> Clone for SeismicPrecompilesMap<CTX>
+where
+    SeismicPrecompiles<CTX>: Clone,
+{
+    fn clone(&self) -> Self {
+        Self { map: self.map.clone(), seismic: self.seismic.clone() }
+    }
+}
+
+impl<CTX> core::fmt::Debug for SeismicPrecompilesMap<CTX> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SeismicPrecompilesMap").field("map", &self.map).finish_non_exhaustive()
+    }
+}
+
+impl<CTX> Borrow<PrecompilesMap> for SeismicPrecompilesMap<CTX> {
+    fn borrow(&self) -> &PrecompilesMap {
+        &self.map
+    }
+}
+
+impl<CTX> BorrowMut<PrecompilesMap> for SeismicPrecompilesMap<CTX> {
+    fn borrow_mut(&mut self) -> &mut PrecompilesMap {
+        &mut self.map
+    }
+}
+
+impl<CTX> Deref for SeismicPrecompilesMap<CTX> {
+    type Target = PrecompilesMap;
+
+    fn deref(&self) -> &Self::Target {
+        &self.map
+    }
+}
+
+impl<CTX> DerefMut for SeismicPrecompilesMap<CTX> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.map
+    }
+}
+
+impl<DB> PrecompileProvider<SeismicContext<DB>> for SeismicPrecompilesMap<SeismicContext<DB>>
+where
+    DB: Database,
+    SeismicPrecompiles<SeismicContext<DB>>:
+        PrecompileProvider<SeismicContext<DB>, Output = InterpreterResult>,
+{
+    type Output = InterpreterResult;
+
+    fn set_spec(&mut self, spec: SeismicSpecId) -> bool {
+        PrecompileProvider::<SeismicContext<DB>>::set_spec(&mut self.seismic, spec)
+    }
+
+    fn run(
+        &mut self,
+        context: &mut SeismicContext<DB>,
+        inputs: &CallInputs,
+    ) -> Result<Option<InterpreterResult>, String> {
+        if let Some(result) = self.map.run(context, inputs)? {
+            return Ok(Some(result));
+        }
+        if inputs.bytecode_address == RNG_PRECOMPILE_ADDRESS {
+            return self.seismic.run(context, inputs);
+        }
+        Ok(None)
+    }
+
+    fn warm_addresses(&self) -> Box<impl Iterator<Item = Address>> {
+        let rng = (self.map.get(&RNG_PRECOMPILE_ADDRESS).is_none()
+            && self.contains(&RNG_PRECOMPILE_ADDRESS))
+        .then_some(RNG_PRECOMPILE_ADDRESS);
+        Box::new(self.map.addresses().copied().chain(rng))
+    }
+
+    fn contains(&self, address: &Address) -> bool {
+        self.map.get(address).is_some()
+            || (*address == RNG_PRECOMPILE_ADDRESS
+                && PrecompileProvider::<SeismicContext<DB>>::contains(&self.seismic, address))
+    }
+}
+
+#[cfg(all(test, feature = "enclave"))]
+mod tests {
+    use super::*;
+    use crate::SeismicEvmFactory;
+    use alloy_evm::{
+        precompiles::{DynPrecompile, PrecompileCache, PrecompileInput, PrecompileProfiler},
+        Evm, EvmEnv, EvmFactory,
+    };
+    use alloy_primitives::Bytes;
+    use revm::{
+        context::{BlockEnv, CfgEnv},
+        database::EmptyDB,
+        precompile::{PrecompileId, PrecompileOutput},
+    };
+
+    const HKDF: Address = address!("0x0000000000000000000000000000000000000068");
+    const CALLER: Address = address!("0x000000000000000000000000000000000000a11c");
+
+    fn evm_factory() -> SeismicEvmFactory {
+        let purpose_keys =
+            Box::leak(Box::new(seismic_enclave::MockEnclaveServer::get_purpose_keys(
+                seismic_enclave::keys::GetPurposeKeysRequest { epoch: 0 },
+            )));
+        SeismicEvmFactory::new_with_purpose_keys(purpose_keys)
+    }
+
+    fn evm_env() -> EvmEnv<SeismicSpecId> {
+        let mut cfg_env = CfgEnv::new_with_spec(SeismicSpecId::MERCURY);
+        cfg_env.chain_id = 5124;
+        EvmEnv::new(cfg_env, BlockEnv::default())
+    }
+
+    #[test]
+    fn profiles_seismic_precompiles() {
+        let mut evm = evm_factory().create_evm(EmptyDB::default(), evm_env());
+        let profiler = PrecompileProfiler::new();
+        evm.precompiles_mut().profile_precompiles(&profiler);
+
+        evm.transact_system_call(CALLER, HKDF, Bytes::from_static(b"input key material")).unwrap();
+
+        let snapshot = profiler.snapshot();
+        assert_eq!(snapshot.precompiles.len(), 1);
+        assert_eq!(snapshot.total().calls, 1);
+        assert_eq!(snapshot.total().input_bytes, 18);
+    }
+
+    #[test]
+    fn caches_seismic_precompiles() {
+        let cache = PrecompileCache::new(16);
+        let factory = evm_factory();
+        let input = Bytes::from_static(b"input key material");
+
+        for _ in 0..2 {
+            let mut evm = factory.create_evm(EmptyDB::default(), evm_env());
+            evm.precompiles_mut().cache_pure_precompiles(&cache);
+            evm.transact_system_call(CALLER, HKDF, input.clone()).unwrap();
+        }
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 1, 1));
+    }
+
+    #[test]
+    fn exposes_tx_and_cfg_to_precompiles() {
+        let mut evm = evm_factory().create_evm(EmptyDB::default(), evm_env());
+        let address = address!("0x0000000000000000000000000000000000000100");
+        // returns the transaction origin, whether the spec is MERCURY and the chain id
+        evm.precompiles_mut().apply_precompile(&address, |_| {
+            Some(DynPrecompile::new_stateful(
+                PrecompileId::Custom("context".into()),
+                |input: PrecompileInput<'_>| {
+                    let mut output = input.tx_origin().unwrap_or_default().to_vec();
+                    output.push(u8::from(input.spec() == Some(SeismicSpecId::MERCURY)));
+                    output.extend(input.internals().chain_id().unwrap_or_default().to_be_bytes());
+                    Ok(PrecompileOutput::new(0, output.into()))
+                },
+            ))
+        });
+
+        let result = evm.transact_system_call(CALLER, address, Bytes::new()).unwrap();
+        let output = result.result.output().unwrap();
+        assert_eq!(&output[..20], CALLER.as_slice());
+        assert_eq!(output[20], 1);
+        assert_eq!(output[21..], 5124u64.to_be_bytes());
+    }
+}
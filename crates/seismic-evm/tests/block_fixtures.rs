@@ -0,0 +1,52 @@
+//! Block-level execution fixtures for seismic blocks.
+//!
+//! The runner encrypts the plaintext of each transaction of the fixtures in `tests/fixtures/block`
+//! with the keys of a deterministic key provider, executes the block through
+//! [`SeismicBlockExecutorFactory`](alloy_seismic_evm::block::SeismicBlockExecutorFactory) and
+//! reports every mismatch between the expected and the actual outcome.
+
+#![cfg(feature = "enclave")]
+
+mod common;
+
+use alloy_evm::{
+    block::{BlockExecutor, BlockExecutorFactory},
+    EvmFactory,
+};
+use common::{execution_ctx, executor_factory, load_fixtures, BlockFixture};
+use revm::database::State;
+
+/// Runs a single fixture and returns all mismatches.
+fn run_fixture(fixture: &BlockFixture) -> Vec<String> {
+    let factory = executor_factory();
+    let mut state = State::builder().with_database(fixture.pre_state()).build();
+    let transactions = fixture.transactions();
+
+    let evm_env = fixture.evm_env();
+    let evm = factory.evm_factory().create_evm(&mut state, evm_env.clone());
+    let result =
+        match factory.create_executor(evm, execution_ctx(&evm_env)).execute_block(&transactions) {
+            Ok(result) => result,
+            Err(err) => return vec![format!("block execution failed: {err}")],
+        };
+
+    fixture.check(&result, &mut state)
+}
+
+#[test]
+fn block_fixtures() {
+    let mut failures = Vec::new();
+    for (path, fixture) in load_fixtures() {
+        let errors = run_fixture(&fixture);
+        if !errors.is_empty() {
+            failures.push(format!(
+                "{} ({}):\n  {}",
+                path.display(),
+                fixture.description,
+                errors.join("\n  ")
+            ));
+        }
+    }
+
+    assert!(failures.is_empty(), "block fixtures failed:\n{}", failures.join("\n"));
+}
@@ -0,0 +1,417 @@
+//! Block execution fixtures shared by the seismic integration tests.
+//!
+//! Every JSON file in `tests/fixtures/block` describes a pre-state, a block environment, a list of
+//! seismic transactions given as plaintext together with their encryption parameters, and the
+//! expected receipts and post-state.
+//!
+//! Transactions are built by encrypting their plaintext with the keys of a deterministic key
+//! provider, see [`purpose_keys`].
+
+use alloy_consensus::{transaction::Recovered, SignableTransaction, TxReceipt};
+use alloy_evm::{block::BlockExecutionResult, eth::EthBlockExecutionCtx, EvmEnv};
+use alloy_primitives::{
+    aliases::U96, keccak256, Address, Bytes, FixedBytes, FlaggedStorage, Log, Signature, TxKind,
+    B256, U256,
+};
+use alloy_seismic_evm::{
+    block::{SeismicAlloyReceiptBuilder, SeismicBlockExecutorFactory},
+    hardfork::SeismicChainHardforks,
+    SeismicEvmFactory,
+};
+use k256::ecdsa::SigningKey;
+use revm::{
+    bytecode::Bytecode,
+    context::{BlockEnv, CfgEnv},
+    database::InMemoryDB,
+    state::AccountInfo,
+    Database,
+};
+use seismic_alloy_consensus::{SeismicTxEnvelope, TxSeismic, TxSeismicElements};
+use seismic_enclave::{
+    ecdh_encrypt, keys::GetPurposeKeysResponse, Nonce, PublicKey, Secp256k1, SecretKey,
+};
+use seismic_revm::SeismicSpecId;
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// A block execution fixture.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct BlockFixture {
+    /// Human readable description of the fixture.
+    #[serde(default)]
+    pub description: String,
+    /// Block environment.
+    pub env: FixtureEnv,
+    /// Accounts present before the block is executed.
+    #[serde(default)]
+    pub pre: BTreeMap<Address, FixtureAccount>,
+    /// Transactions of the block, in order.
+    pub transactions: Vec<FixtureTransaction>,
+    /// Expected outcome of the block.
+    pub expected: FixtureExpectation,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct FixtureEnv {
+    pub chain_id: u64,
+    pub number: u64,
+    pub timestamp: u64,
+    pub gas_limit: u64,
+    #[serde(default)]
+    pub base_fee: u64,
+    #[serde(default)]
+    pub coinbase: Address,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct FixtureAccount {
+    #[serde(default)]
+    pub balance: U256,
+    #[serde(default)]
+    pub nonce: u64,
+    #[serde(default)]
+    pub code: Bytes,
+    #[serde(default)]
+    pub storage: BTreeMap<U256, FixtureStorage>,
+}
+
+/// A storage value, either a public value or a value with its visibility.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(untagged)]
+pub enum FixtureStorage {
+    /// A public value.
+    Public(U256),
+    /// A value with its visibility.
+    #[serde(rename_all = "camelCase")]
+    Flagged { value: U256, is_private: bool },
+}
+
+impl From<FixtureStorage> for FlaggedStorage {
+    fn from(storage: FixtureStorage) -> Self {
+        match storage {
+            FixtureStorage::Public(value) => value.into(),
+            FixtureStorage::Flagged { value, is_private } => Self { value, is_private },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct FixtureTransaction {
+    /// Secret key of the sender.
+    pub secret_key: B256,
+    /// Secret key of the encryption keypair, the public key is included in the transaction.
+    pub encryption_secret_key: B256,
+    /// Nonce used to encrypt the calldata.
+    pub encryption_nonce: FixedBytes<12>,
+    #[serde(default)]
+    pub message_version: u8,
+    pub nonce: u64,
+    pub gas_price: u128,
+    pub gas_limit: u64,
+    #[serde(default)]
+    pub to: Option<Address>,
+    #[serde(default)]
+    pub value: U256,
+    /// Calldata before encryption.
+    pub plaintext: Bytes,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct FixtureExpectation {
+    #[serde(default)]
+    pub gas_used: Option<u64>,
+    pub receipts: Vec<FixtureReceipt>,
+    #[serde(default)]
+    pub post: BTreeMap<Address, FixturePostAccount>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct FixtureReceipt {
+    pub status: bool,
+    pub cumulative_gas_used: u64,
+    #[serde(default)]
+    pub logs: Vec<FixtureLog>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct FixtureLog {
+    pub address: Address,
+    #[serde(default)]
+    pub topics: Vec<B256>,
+    #[serde(default)]
+    pub data: Bytes,
+}
+
+impl FixtureLog {
+    fn to_log(&self) -> Log {
+        Log::new_unchecked(self.address, self.topics.clone(), self.data.clone())
+    }
+}
+
+/// Expected post-state of an account, only the provided fields are checked.
+///
+/// The visibility of storage slots is only checked for values given with their visibility.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct FixturePostAccount {
+    #[serde(default)]
+    pub balance: Option<U256>,
+    #[serde(default)]
+    pub nonce: Option<u64>,
+    #[serde(default)]
+    pub code: Option<Bytes>,
+    #[serde(default)]
+    pub storage: BTreeMap<U256, FixtureStorage>,
+}
+
+/// Returns the enclave keys used by all fixtures.
+///
+/// These are the epoch 0 keys of the mock enclave, which are the same on every run.
+pub fn purpose_keys() -> &'static GetPurposeKeysResponse {
+    static KEYS: OnceLock<GetPurposeKeysResponse> = OnceLock::new();
+    KEYS.get_or_init(|| {
+        seismic_enclave::MockEnclaveServer::get_purpose_keys(
+            seismic_enclave::keys::GetPurposeKeysRequest { epoch: 0 },
+        )
+    })
+}
+
+/// Returns the block executor factory used by all fixtures.
+pub fn executor_factory() -> SeismicBlockExecutorFactory {
+    SeismicBlockExecutorFactory::new(
+        SeismicAlloyReceiptBuilder::default(),
+        SeismicChainHardforks::seismic_mainnet(),
+        SeismicEvmFactory::new_with_purpose_keys(purpose_keys()),
+        purpose_keys(),
+    )
+}
+
+/// Returns the execution context of a fixture block.
+pub fn execution_ctx(_: &EvmEnv<SeismicSpecId>) -> EthBlockExecutionCtx<'_> {
+    EthBlockExecutionCtx {
+        parent_hash: B256::ZERO,
+        parent_beacon_block_root: None,
+        ommers: &[],
+        withdrawals: None,
+    }
+}
+
+/// Returns all fixtures, sorted by path.
+pub fn load_fixtures() -> Vec<(PathBuf, BlockFixture)> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/block");
+    let mut paths = fs::read_dir(dir)
+        .expect("fixtures directory exists")
+        .map(|entry| entry.expect("readable fixture entry").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect::<Vec<_>>();
+    paths.sort();
+    assert!(!paths.is_empty(), "no block fixtures found");
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let contents = fs::read_to_string(&path).expect("readable fixture");
+            let fixture = serde_json::from_str(&contents)
+                .unwrap_or_else(|err| panic!("invalid fixture {}: {err}", path.display()));
+            (path, fixture)
+        })
+        .collect()
+}
+
+fn signer_address(signing_key: &SigningKey) -> Address {
+    let public = signing_key.verifying_key().to_encoded_point(/* compress = */ false);
+    Address::from_slice(&keccak256(&public.as_bytes()[1..])[12..])
+}
+
+fn sign(tx: &TxSeismic, signing_key: &SigningKey) -> Signature {
+    let (signature, recovery_id) = signing_key
+        .sign_prehash_recoverable(tx.signature_hash().as_slice())
+        .expect("failed to sign transaction");
+
+    Signature::new(
+        U256::from_be_slice(signature.r().to_bytes().as_slice()),
+        U256::from_be_slice(signature.s().to_bytes().as_slice()),
+        recovery_id.is_y_odd(),
+    )
+}
+
+impl FixtureTransaction {
+    /// Encrypts the plaintext and signs the transaction.
+    fn build(&self, chain_id: u64, keys: &GetPurposeKeysResponse) -> Recovered<SeismicTxEnvelope> {
+        let signing_key =
+            SigningKey::from_slice(self.secret_key.as_slice()).expect("invalid secret key");
+        let encryption_sk = SecretKey::from_slice(self.encryption_secret_key.as_slice())
+            .expect("invalid encryption secret key");
+        let encryption_pubkey = PublicKey::from_secret_key(&Secp256k1::new(), &encryption_sk);
+        let nonce = Nonce(self.encryption_nonce.0.into());
+
+        let ciphertext = ecdh_encrypt(&encryption_pubkey, &keys.tx_io_sk, &self.plaintext, nonce)
+            .expect("failed to encrypt plaintext");
+
+        let tx = TxSeismic {
+            chain_id,
+            nonce: self.nonce,
+            gas_price: self.gas_price,
+            gas_limit: self.gas_limit,
+            to: self.to.map_or(TxKind::Create, TxKind::Call),
+            value: self.value,
+            input: Bytes::from(ciphertext),
+            seismic_elements: TxSeismicElements {
+                encryption_pubkey,
+                encryption_nonce: U96::from_be_slice(self.encryption_nonce.as_slice()),
+                message_version: self.message_version,
+            },
+        };
+        let signature = sign(&tx, &signing_key);
+
+        Recovered::new_unchecked(
+            SeismicTxEnvelope::Seismic(tx.into_signed(signature)),
+            signer_address(&signing_key),
+        )
+    }
+}
+
+impl BlockFixture {
+    /// Returns the state before the block.
+    pub fn pre_state(&self) -> InMemoryDB {
+        let mut db = InMemoryDB::default();
+        for (address, account) in &self.pre {
+            let mut info = AccountInfo {
+                balance: account.balance,
+                nonce: account.nonce,
+                ..Default::default()
+            };
+            if !account.code.is_empty() {
+                info.code_hash = keccak256(&account.code);
+                info.code = Some(Bytecode::new_raw(account.code.clone()));
+            }
+            db.insert_account_info(*address, info);
+            for (slot, value) in &account.storage {
+                db.insert_account_storage(*address, *slot, (*value).into())
+                    .expect("in-memory db is infallible");
+            }
+        }
+        db
+    }
+
+    /// Returns the EVM environment of the block.
+    pub fn evm_env(&self) -> EvmEnv<SeismicSpecId> {
+        let mut cfg_env = CfgEnv::new_with_spec(SeismicSpecId::MERCURY);
+        cfg_env.chain_id = self.env.chain_id;
+        let block_env = BlockEnv {
+            number: U256::from(self.env.number),
+            timestamp: U256::from(self.env.timestamp),
+            gas_limit: self.env.gas_limit,
+            basefee: self.env.base_fee,
+            beneficiary: self.env.coinbase,
+            ..Default::default()
+        };
+        EvmEnv::new(cfg_env, block_env)
+    }
+
+    /// Encrypts and signs the transactions of the block.
+    pub fn transactions(&self) -> Vec<Recovered<SeismicTxEnvelope>> {
+        self.transactions.iter().map(|tx| tx.build(self.env.chain_id, purpose_keys())).collect()
+    }
+
+    /// Checks the result of the block and the state after it, returning all mismatches.
+    pub fn check<R, DB>(&self, result: &BlockExecutionResult<R>, state: &mut DB) -> Vec<String>
+    where
+        R: TxReceipt<Log = Log>,
+        DB: Database,
+        DB::Error: core::fmt::Debug,
+    {
+        let mut errors = Vec::new();
+
+        let expected = &self.expected;
+        if let Some(gas_used) = expected.gas_used {
+            if result.gas_used != gas_used {
+                errors.push(format!("gas used: expected {gas_used}, got {}", result.gas_used));
+            }
+        }
+
+        if result.receipts.len() != expected.receipts.len() {
+            errors.push(format!(
+                "receipts: expected {}, got {}",
+                expected.receipts.len(),
+                result.receipts.len()
+            ));
+        }
+        for (idx, (receipt, expected)) in result.receipts.iter().zip(&expected.receipts).enumerate()
+        {
+            if receipt.status() != expected.status {
+                errors.push(format!(
+                    "receipt {idx} status: expected {}, got {}",
+                    expected.status,
+                    receipt.status()
+                ));
+            }
+            if receipt.cumulative_gas_used() != expected.cumulative_gas_used {
+                errors.push(format!(
+                    "receipt {idx} cumulative gas used: expected {}, got {}",
+                    expected.cumulative_gas_used,
+                    receipt.cumulative_gas_used()
+                ));
+            }
+            let logs = expected.logs.iter().map(FixtureLog::to_log).collect::<Vec<_>>();
+            if receipt.logs() != logs.as_slice() {
+                errors.push(format!(
+                    "receipt {idx} logs: expected {logs:?}, got {:?}",
+                    receipt.logs()
+                ));
+            }
+        }
+
+        for (address, expected) in &expected.post {
+            let info = state.basic(*address).expect("state is readable").unwrap_or_default();
+            if let Some(balance) = expected.balance {
+                if info.balance != balance {
+                    errors.push(format!(
+                        "{address} balance: expected {balance}, got {}",
+                        info.balance
+                    ));
+                }
+            }
+            if let Some(nonce) = expected.nonce {
+                if info.nonce != nonce {
+                    errors.push(format!("{address} nonce: expected {nonce}, got {}", info.nonce));
+                }
+            }
+            if let Some(code) = &expected.code {
+                if info.code_hash != keccak256(code) {
+                    errors.push(format!(
+                        "{address} code hash: expected {}, got {}",
+                        keccak256(code),
+                        info.code_hash
+                    ));
+                }
+            }
+            for (slot, expected) in &expected.storage {
+                let actual = state.storage(*address, *slot).expect("state is readable");
+                let matches = match *expected {
+                    FixtureStorage::Public(value) => actual.value == value,
+                    FixtureStorage::Flagged { .. } => actual == (*expected).into(),
+                };
+                if !matches {
+                    errors.push(format!(
+                        "{address} storage {slot}: expected {expected:?}, got {actual:?}"
+                    ));
+                }
+            }
+        }
+
+        errors
+    }
+}
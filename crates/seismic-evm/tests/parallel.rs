@@ -0,0 +1,52 @@
+//! Parallel execution of seismic blocks.
+//!
+//! Every block fixture, see `tests/fixtures/block`, is executed with a [`ParallelBlockExecutor`]
+//! and compared with sequential execution, including blocks with encrypted calldata and private
+//! storage.
+
+#![cfg(feature = "enclave")]
+
+mod common;
+
+use alloy_evm::{
+    block::{parallel::ParallelBlockExecutor, BlockExecutor, BlockExecutorFactory},
+    EvmFactory,
+};
+use common::{execution_ctx, executor_factory, load_fixtures};
+use core::num::NonZeroUsize;
+use revm::database::{states::bundle_state::BundleRetention, State};
+
+#[test]
+fn matches_sequential_execution() {
+    let factory = executor_factory();
+
+    for (path, fixture) in load_fixtures() {
+        let name = format!("{} ({})", path.display(), fixture.description);
+        let transactions = fixture.transactions();
+        let evm_env = fixture.evm_env();
+
+        let mut sequential_db =
+            State::builder().with_database(fixture.pre_state()).with_bundle_update().build();
+        let evm = factory.evm_factory().create_evm(&mut sequential_db, evm_env.clone());
+        let sequential = factory
+            .create_executor(evm, execution_ctx(&evm_env))
+            .execute_block(&transactions)
+            .unwrap_or_else(|err| panic!("{name}: sequential execution failed: {err}"));
+        sequential_db.merge_transitions(BundleRetention::Reverts);
+
+        let mut parallel_db =
+            State::builder().with_database(fixture.pre_state()).with_bundle_update().build();
+        let (parallel, stats) = ParallelBlockExecutor::new(&factory)
+            .with_threads(NonZeroUsize::new(2).unwrap())
+            .execute_block(&mut parallel_db, evm_env, execution_ctx, &transactions)
+            .unwrap_or_else(|err| panic!("{name}: parallel execution failed: {err}"));
+        parallel_db.merge_transitions(BundleRetention::Reverts);
+
+        assert_eq!(parallel, sequential, "{name}");
+        assert_eq!(stats.transactions, transactions.len(), "{name}");
+
+        let errors = fixture.check(&parallel, &mut parallel_db);
+        assert!(errors.is_empty(), "{name}:\n  {}", errors.join("\n  "));
+        assert_eq!(parallel_db.take_bundle(), sequential_db.take_bundle(), "{name}");
+    }
+}
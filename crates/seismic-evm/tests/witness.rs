@@ -0,0 +1,61 @@
+//! Stateless execution of seismic blocks from an execution witness.
+//!
+//! Every block fixture, see `tests/fixtures/block`, is executed on top of a
+//! [`RecordingDatabase`], then executed again from the recorded witness only.
+
+#![cfg(feature = "enclave")]
+
+mod common;
+
+use alloy_evm::{
+    block::{BlockExecutor, BlockExecutorFactory},
+    witness::{execute_block_with_witness, RecordingDatabase, WitnessDatabase},
+    EvmFactory,
+};
+use alloy_primitives::FlaggedStorage;
+use common::{execution_ctx, executor_factory, load_fixtures};
+use revm::database::State;
+
+#[test]
+fn executes_fixtures_from_witness() {
+    let factory = executor_factory();
+
+    for (path, fixture) in load_fixtures() {
+        let name = format!("{} ({})", path.display(), fixture.description);
+        let transactions = fixture.transactions();
+
+        let mut db = State::builder()
+            .with_database(RecordingDatabase::new(fixture.pre_state()))
+            .with_bundle_update()
+            .build();
+        let (expected, witness) = execute_block_with_witness(
+            &factory,
+            &mut db,
+            fixture.evm_env(),
+            execution_ctx,
+            &transactions,
+        )
+        .unwrap_or_else(|err| panic!("{name}: block execution failed: {err}"));
+
+        // storage is recorded along with its visibility
+        for (address, account) in &fixture.pre {
+            for (slot, value) in &account.storage {
+                if let Some(recorded) = witness.storage.get(address).and_then(|s| s.get(slot)) {
+                    assert_eq!(*recorded, FlaggedStorage::from(*value), "{name}: {address} {slot}");
+                }
+            }
+        }
+
+        let mut db = State::builder().with_database(WitnessDatabase::new(witness)).build();
+        let evm_env = fixture.evm_env();
+        let evm = factory.evm_factory().create_evm(&mut db, evm_env.clone());
+        let result = factory
+            .create_executor(evm, execution_ctx(&evm_env))
+            .execute_block(&transactions)
+            .unwrap_or_else(|err| panic!("{name}: stateless execution failed: {err}"));
+        assert_eq!(result, expected, "{name}");
+
+        let errors = fixture.check(&result, &mut db);
+        assert!(errors.is_empty(), "{name}:\n  {}", errors.join("\n  "));
+    }
+}
@@ -4,6 +4,7 @@ set -eo pipefail
 no_std_packages=(
   alloy-evm
   alloy-op-evm
+  alloy-seismic-evm
 )
 
 for package in "${no_std_packages[@]}"; do